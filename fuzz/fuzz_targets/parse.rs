@@ -0,0 +1,83 @@
+//! Parses arbitrary sources with every consumer of the parser. Run with
+//! `cargo fuzz run parse -- -max_len=65536 -timeout=2` so that inputs are long enough to reach
+//! super-linear scans and deep nesting, and slow inputs are reported rather than just slow.
+//! The first byte picks the options, the rest is the source
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use simple_markdown_parser::{
+    arguments::CommandArguments,
+    cst::SyntaxTree,
+    document::Document,
+    extras::emit::{markdown_to_html_with_options, BlankFeatureEmitter},
+    fmt::{format_document, FormatOptions},
+    parse_with_positions,
+    streaming::StreamingParser,
+    MarkdownElement, MarkdownTextElement, ParseOptions,
+};
+
+/// Matches `-max_len` above. Every consumer should handle this much in well under the timeout
+const MAX_LEN: usize = 1 << 16;
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() > MAX_LEN {
+        return;
+    }
+    let Some((selector, source)) = data.split_first() else {
+        return;
+    };
+    let Ok(source) = std::str::from_utf8(source) else {
+        return;
+    };
+    let options = match selector % 4 {
+        0 => ParseOptions::default(),
+        1 => ParseOptions::commonmark(),
+        2 => ParseOptions::gfm(),
+        _ => ParseOptions::obsidian(),
+    }
+    .max_nesting_depth(Some(32))
+    .max_line_length(Some(4096))
+    .max_elements(Some(4096));
+
+    let _ = parse_with_positions(source, &options, |element, _| {
+        if let Some(text) = element.parts_like() {
+            for part in text.parts_with_options(&options) {
+                if let MarkdownTextElement::Link { on, .. } = part {
+                    for _ in on.parts_with_options(&options) {}
+                }
+            }
+        }
+        if let MarkdownElement::Table(table) = element {
+            for row in table.rows() {
+                for _ in row.cells() {}
+            }
+        }
+        let _ = element.as_markdown();
+    });
+
+    let _ = CommandArguments::parse_with_options(source, &options);
+
+    let mut output = Vec::new();
+    let _ = markdown_to_html_with_options(source, &options, &mut output, &mut BlankFeatureEmitter);
+    let _ = SyntaxTree::parse_with_options(source, &options);
+    if let Ok(document) = Document::parse_with_options(source, &options) {
+        let _ = format_document(&document, &FormatOptions::default());
+    }
+
+    // Chunks split at the selector, on character boundaries
+    let mut parser = StreamingParser::new(&options);
+    let mut rest = source;
+    while !rest.is_empty() {
+        let mut split = usize::from(*selector).max(1).min(rest.len());
+        while !rest.is_char_boundary(split) {
+            split += 1;
+        }
+        let (chunk, after) = rest.split_at(split);
+        if parser.push(chunk, |_| {}).is_err() {
+            return;
+        }
+        rest = after;
+    }
+    let _ = parser.finish(|_| {});
+});
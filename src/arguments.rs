@@ -0,0 +1,285 @@
+//! Arguments of `{% name arguments %}` commands
+
+use std::borrow::Cow;
+use std::ops::Range;
+
+use crate::ParseOptions;
+
+/// Parsed arguments, for example `{% tabs "Intro" sync size=2 labels=["a", "b"] %}`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CommandArguments<'a> {
+    /// Values without a key (`"Intro"`), in order
+    pub positional: Vec<ArgumentValue<'a>>,
+    /// `key=value` pairs (`size=2`), in order
+    pub named: Vec<(&'a str, ArgumentValue<'a>)>,
+    /// Bare identifiers (`sync`), in order
+    pub flags: Vec<&'a str>,
+}
+
+impl<'a> CommandArguments<'a> {
+    /// # Errors
+    /// for unclosed strings and lists, missing values and lists nested deeper than the default
+    /// [`ParseOptions::max_nesting_depth`]
+    pub fn parse(on: &'a str) -> Result<Self, ArgumentsError> {
+        Self::parse_with_options(on, &ParseOptions::default())
+    }
+
+    /// # Errors
+    /// for unclosed strings and lists, missing values and lists nested deeper than
+    /// [`ParseOptions::max_nesting_depth`]
+    pub fn parse_with_options(on: &'a str, options: &ParseOptions) -> Result<Self, ArgumentsError> {
+        let mut arguments = Self::default();
+        let mut cursor = Cursor {
+            on,
+            idx: 0,
+            max_depth: options.max_nesting_depth,
+        };
+
+        loop {
+            cursor.skip_whitespace();
+            let Some(chr) = cursor.peek() else {
+                break;
+            };
+
+            if let '"' | '\'' | '[' = chr {
+                arguments.positional.push(cursor.value(0)?);
+                continue;
+            }
+
+            let word_start = cursor.idx;
+            let word = cursor.bare_word();
+            if word.is_empty() {
+                return Err(ArgumentsError {
+                    reason: ArgumentsErrorReason::UnexpectedCharacter(chr),
+                    position: word_start..(word_start + chr.len_utf8()),
+                });
+            }
+
+            let before_equals = cursor.idx;
+            cursor.skip_whitespace();
+            if let Some('=') = cursor.peek() {
+                cursor.idx += '='.len_utf8();
+                cursor.skip_whitespace();
+                arguments.named.push((word, cursor.value(0)?));
+            } else {
+                cursor.idx = before_equals;
+                match bare_value(word) {
+                    ArgumentValue::String(_) => arguments.flags.push(word),
+                    value => arguments.positional.push(value),
+                }
+            }
+        }
+
+        Ok(arguments)
+    }
+
+    /// Value of the last `key=value` with a matching key
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&ArgumentValue<'a>> {
+        self.named
+            .iter()
+            .rev()
+            .find_map(|(name, value)| (*name == key).then_some(value))
+    }
+
+    #[must_use]
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags.contains(&flag)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgumentValue<'a> {
+    /// `"quoted"` (with escapes resolved) or an unquoted value after `=`
+    String(Cow<'a, str>),
+    Number(f64),
+    /// `true` or `false`
+    Boolean(bool),
+    /// `[value, value]`
+    List(Vec<ArgumentValue<'a>>),
+}
+
+impl ArgumentValue<'_> {
+    #[must_use]
+    pub fn as_str(&self) -> Option<&str> {
+        if let ArgumentValue::String(value) = self {
+            Some(value)
+        } else {
+            None
+        }
+    }
+}
+
+/// Error in an argument list. `position` is a byte range in the parsed arguments, such as
+/// [`crate::CommandBlock::arguments`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArgumentsError {
+    pub reason: ArgumentsErrorReason,
+    pub position: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgumentsErrorReason {
+    /// Missing closing `"` or `'`
+    UnclosedString,
+    /// Missing closing `]`
+    UnclosedList,
+    /// `key=` without a value
+    ExpectedValue,
+    UnexpectedCharacter(char),
+    /// A list nested deeper than [`ParseOptions::max_nesting_depth`]
+    TooDeep,
+}
+
+impl std::fmt::Display for ArgumentsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.reason {
+            ArgumentsErrorReason::UnclosedString => write!(f, "missing closing quote")?,
+            ArgumentsErrorReason::UnclosedList => write!(f, "missing closing ']'")?,
+            ArgumentsErrorReason::ExpectedValue => write!(f, "expected value")?,
+            ArgumentsErrorReason::UnexpectedCharacter(chr) => {
+                write!(f, "unexpected character {chr:?}")?;
+            }
+            ArgumentsErrorReason::TooDeep => write!(f, "lists nested too deeply")?,
+        }
+        write!(
+            f,
+            " (at {start}..{end})",
+            start = self.position.start,
+            end = self.position.end
+        )
+    }
+}
+
+impl std::error::Error for ArgumentsError {}
+
+struct Cursor<'a> {
+    on: &'a str,
+    idx: usize,
+    max_depth: Option<usize>,
+}
+
+impl<'a> Cursor<'a> {
+    fn peek(&self) -> Option<char> {
+        self.on[self.idx..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.on[self.idx..];
+        self.idx += rest.len() - rest.trim_start().len();
+    }
+
+    /// Up to whitespace or a delimiter
+    fn bare_word(&mut self) -> &'a str {
+        let rest = &self.on[self.idx..];
+        let length = rest
+            .find(|chr: char| {
+                chr.is_whitespace() || matches!(chr, '=' | ',' | '[' | ']' | '"' | '\'')
+            })
+            .unwrap_or(rest.len());
+        self.idx += length;
+        &rest[..length]
+    }
+
+    /// Value in `depth` lists
+    fn value(&mut self, depth: usize) -> Result<ArgumentValue<'a>, ArgumentsError> {
+        let start = self.idx;
+        match self.peek() {
+            Some(quote @ ('"' | '\'')) => {
+                self.idx += quote.len_utf8();
+                self.string(quote, start)
+            }
+            Some('[') => {
+                if self.max_depth.is_some_and(|limit| depth >= limit) {
+                    return Err(ArgumentsError {
+                        reason: ArgumentsErrorReason::TooDeep,
+                        position: start..(start + '['.len_utf8()),
+                    });
+                }
+                self.idx += '['.len_utf8();
+                let mut items = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(']') => {
+                            self.idx += ']'.len_utf8();
+                            return Ok(ArgumentValue::List(items));
+                        }
+                        Some(',') => {
+                            self.idx += ','.len_utf8();
+                        }
+                        Some(_) => items.push(self.value(depth + 1)?),
+                        None => {
+                            return Err(ArgumentsError {
+                                reason: ArgumentsErrorReason::UnclosedList,
+                                position: start..self.on.len(),
+                            });
+                        }
+                    }
+                }
+            }
+            _ => {
+                let word = self.bare_word();
+                if word.is_empty() {
+                    let end = self.idx + self.peek().map_or(0, char::len_utf8);
+                    Err(ArgumentsError {
+                        reason: ArgumentsErrorReason::ExpectedValue,
+                        position: start..end,
+                    })
+                } else {
+                    Ok(bare_value(word))
+                }
+            }
+        }
+    }
+
+    /// After the opening quote. Resolves `\"`, `\'`, `\\`, `\n` and `\t`
+    fn string(&mut self, quote: char, start: usize) -> Result<ArgumentValue<'a>, ArgumentsError> {
+        let on = self.on;
+        let content_start = self.idx;
+        let mut owned: Option<String> = None;
+        let mut chars = on[content_start..].char_indices();
+
+        while let Some((offset, chr)) = chars.next() {
+            if chr == quote {
+                self.idx = content_start + offset + quote.len_utf8();
+                let value = match owned {
+                    Some(owned) => Cow::Owned(owned),
+                    None => Cow::Borrowed(&on[content_start..(content_start + offset)]),
+                };
+                return Ok(ArgumentValue::String(value));
+            } else if chr == '\\' {
+                let owned = owned
+                    .get_or_insert_with(|| on[content_start..(content_start + offset)].to_owned());
+                match chars.next() {
+                    Some((_, 'n')) => owned.push('\n'),
+                    Some((_, 't')) => owned.push('\t'),
+                    Some((_, escaped)) => owned.push(escaped),
+                    None => break,
+                }
+            } else if let Some(owned) = &mut owned {
+                owned.push(chr);
+            }
+        }
+
+        Err(ArgumentsError {
+            reason: ArgumentsErrorReason::UnclosedString,
+            position: start..on.len(),
+        })
+    }
+}
+
+/// Unquoted values are booleans, numbers or else strings
+fn bare_value(word: &str) -> ArgumentValue<'_> {
+    let numeric = word
+        .trim_start_matches(['-', '+'])
+        .starts_with(|chr: char| chr.is_ascii_digit() || chr == '.');
+    match word {
+        "true" => ArgumentValue::Boolean(true),
+        "false" => ArgumentValue::Boolean(false),
+        word => match word.parse::<f64>() {
+            Ok(number) if numeric => ArgumentValue::Number(number),
+            _ => ArgumentValue::String(Cow::Borrowed(word)),
+        },
+    }
+}
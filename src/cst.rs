@@ -0,0 +1,475 @@
+//! Trivia overlay over parsed elements. The source is parsed as usual, then each element's source
+//! is split into tokens around the slices its [`MarkdownElement`] borrows, and its text into tokens
+//! around the slices of its [`MarkdownTextElement`]s, so that every byte (including markers,
+//! indentation, inline delimiters, link brackets and blank lines) belongs to exactly one token.
+//! Edits can then replace only the bytes they change rather than reformatting
+//!
+//! The parser still discards trivia, so tokens are only as precise as the slices of the elements:
+//! the tree is built from the elements rather than the elements from the tree
+//!
+//! ```
+//! use simple_markdown_parser::cst::{SyntaxTree, TokenKind};
+//!
+//! let source = "#  Title  \n- [ ] task";
+//! let tree = SyntaxTree::parse(source).unwrap();
+//! let title = tree.root().nodes().next().unwrap().token(TokenKind::Text).unwrap();
+//!
+//! let mut edited = source.to_owned();
+//! edited.replace_range(title.range.clone(), "Renamed");
+//! assert_eq!(edited, "#  Renamed  \n- [ ] task");
+//! ```
+
+use std::ops::Range;
+
+use crate::{
+    parse_with_positions, MarkdownElement, MarkdownTextElement, ParseError, ParseOptions,
+    RawMarkdown, RawText,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxTree<'a> {
+    root: SyntaxNode<'a>,
+}
+
+impl<'a> SyntaxTree<'a> {
+    /// # Errors
+    /// errors from markdown parsing (including of command and list item contents)
+    pub fn parse(source: &'a str) -> Result<Self, ParseError> {
+        Self::parse_with_options(source, &ParseOptions::default())
+    }
+
+    /// Parses `source` with [`crate::parse_with_positions`] and splits each element into tokens
+    ///
+    /// # Errors
+    /// errors from markdown parsing (including of command and list item contents)
+    pub fn parse_with_options(source: &'a str, options: &ParseOptions) -> Result<Self, ParseError> {
+        let mut children = Vec::new();
+        let mut start = 0;
+        if source.starts_with('\u{feff}') {
+            start = '\u{feff}'.len_utf8();
+            children.push(token(source, TokenKind::ByteOrderMark, 0..start));
+        }
+        nodes(source, start..source.len(), options, &mut children)?;
+        Ok(Self {
+            root: SyntaxNode {
+                kind: NodeKind::Document,
+                range: 0..source.len(),
+                text: source,
+                children,
+            },
+        })
+    }
+
+    #[must_use]
+    pub fn root(&self) -> &SyntaxNode<'a> {
+        &self.root
+    }
+
+    /// Top level elements, as given by [`crate::parse_with_options`]
+    pub fn elements(&self) -> impl Iterator<Item = MarkdownElement<'a>> + '_ {
+        self.root.nodes().filter_map(SyntaxNode::element)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxNode<'a> {
+    pub kind: NodeKind<'a>,
+    /// Byte range in the source
+    pub range: Range<usize>,
+    pub text: &'a str,
+    /// Tokens and nested nodes (list item children and command contents), in source order. Their
+    /// ranges cover `range` without gaps
+    pub children: Vec<SyntaxElement<'a>>,
+}
+
+impl<'a> SyntaxNode<'a> {
+    #[must_use]
+    pub fn element(&self) -> Option<MarkdownElement<'a>> {
+        match &self.kind {
+            NodeKind::Document => None,
+            NodeKind::Element(element) => Some(element.clone()),
+        }
+    }
+
+    /// Direct child nodes
+    pub fn nodes(&self) -> impl Iterator<Item = &SyntaxNode<'a>> {
+        self.children.iter().filter_map(|child| match child {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+
+    /// Direct tokens (not those of child nodes)
+    pub fn tokens(&self) -> impl Iterator<Item = &SyntaxToken<'a>> {
+        self.children.iter().filter_map(|child| match child {
+            SyntaxElement::Token(token) => Some(token),
+            SyntaxElement::Node(_) => None,
+        })
+    }
+
+    /// First direct token of `kind`
+    #[must_use]
+    pub fn token(&self, kind: TokenKind) -> Option<&SyntaxToken<'a>> {
+        self.tokens().find(|token| token.kind == kind)
+    }
+
+    /// Tokens at any depth, in source order. Their text joined is the text of the node
+    #[must_use]
+    pub fn descendant_tokens(&self) -> Vec<&SyntaxToken<'a>> {
+        fn collect<'b, 'a>(node: &'b SyntaxNode<'a>, tokens: &mut Vec<&'b SyntaxToken<'a>>) {
+            for child in &node.children {
+                match child {
+                    SyntaxElement::Token(token) => tokens.push(token),
+                    SyntaxElement::Node(node) => collect(node, tokens),
+                }
+            }
+        }
+
+        let mut tokens = Vec::new();
+        collect(self, &mut tokens);
+        tokens
+    }
+
+    /// Token containing the byte at `offset`
+    #[must_use]
+    pub fn token_at(&self, offset: usize) -> Option<&SyntaxToken<'a>> {
+        self.children.iter().find_map(|child| match child {
+            SyntaxElement::Token(token) => token.range.contains(&offset).then_some(token),
+            SyntaxElement::Node(node) => node
+                .range
+                .contains(&offset)
+                .then(|| node.token_at(offset))?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NodeKind<'a> {
+    Document,
+    Element(MarkdownElement<'a>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyntaxElement<'a> {
+    Node(SyntaxNode<'a>),
+    Token(SyntaxToken<'a>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxToken<'a> {
+    pub kind: TokenKind,
+    /// Byte range in the source
+    pub range: Range<usize>,
+    pub text: &'a str,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TokenKind {
+    ByteOrderMark,
+    /// Indentation, spaces after markers and trailing whitespace. Blank lines are whitespace and
+    /// line ending tokens between element nodes
+    Whitespace,
+    /// `\n`, `\r\n` or `\r`
+    LineEnding,
+    /// `#`s of headings, `-` or `1.` of list items, `>` of quotes and `>` and `[!kind]` of callouts
+    Marker,
+    /// `[ ]` or `[x]` at the start of a list item
+    TaskMarker,
+    /// Syntax around block contents: code fences, `$$`, `%%`, `---`, table pipes and separator
+    /// rows, and command tags (apart from the name and arguments of the opening tag). And inline
+    /// syntax: emphasis delimiters, link and media brackets, backticks, `#` of tags etc
+    Delimiter,
+    /// Text of headings, paragraphs, quotes, callout titles, list items and table cells, including
+    /// emphasised text, link text, media alt text, tag names and emoji shortcodes
+    Text,
+    /// Of links and media
+    Destination,
+    /// Language of code blocks
+    Language,
+    CommandName,
+    CommandArguments,
+    /// Code, LaTeX, comments, HTML, frontmatter and the data of custom blocks. Multiline contents
+    /// include their line endings. Inline, code spans, math, expressions and custom elements
+    Content,
+    /// Source not part of any element, such as an unclosed code block
+    Unparsed,
+}
+
+/// Part of an element, for splitting its source into tokens
+enum Part<'a> {
+    Token(TokenKind, &'a str),
+    /// Text split into inline tokens
+    Text(&'a str),
+    /// Blocks parsed as child nodes
+    Children(&'a str),
+}
+
+fn token(source: &str, kind: TokenKind, range: Range<usize>) -> SyntaxElement<'_> {
+    SyntaxElement::Token(SyntaxToken {
+        kind,
+        text: &source[range.clone()],
+        range,
+    })
+}
+
+/// Element nodes in `range` of `source` and the tokens between them
+fn nodes<'a>(
+    source: &'a str,
+    range: Range<usize>,
+    options: &ParseOptions,
+    out: &mut Vec<SyntaxElement<'a>>,
+) -> Result<(), ParseError> {
+    let offset = range.start;
+    let mut elements = Vec::new();
+    parse_with_positions(&source[range.clone()], options, |element, position| {
+        elements.push((element, position.start + offset..position.end + offset));
+    })
+    .map_err(|error| ParseError {
+        position: error.position.start + offset..error.position.end + offset,
+        ..error
+    })?;
+
+    let mut last = offset;
+    for (element, position) in elements {
+        trivia(source, last..position.start, TokenKind::Unparsed, out);
+        last = position.end;
+        out.push(SyntaxElement::Node(element_node(
+            source, element, position, options,
+        )?));
+    }
+    trivia(source, last..range.end, TokenKind::Unparsed, out);
+    Ok(())
+}
+
+/// Node of `element`, with tokens for the slices it borrows. The rest of `range` is `fill` trivia
+fn element_node<'a>(
+    source: &'a str,
+    element: MarkdownElement<'a>,
+    range: Range<usize>,
+    options: &ParseOptions,
+) -> Result<SyntaxNode<'a>, ParseError> {
+    let mut parts = Vec::new();
+    let mut fill = TokenKind::Delimiter;
+    match element {
+        MarkdownElement::Heading { text, .. } => {
+            fill = TokenKind::Marker;
+            parts.push(Part::Text(text.0));
+        }
+        MarkdownElement::Paragraph(text) => parts.push(Part::Text(text.0)),
+        MarkdownElement::Quote(RawMarkdown(inner)) => {
+            fill = TokenKind::Marker;
+            parts.push(Part::Text(inner.trim_start()));
+        }
+        MarkdownElement::Callout { title, .. } => {
+            fill = TokenKind::Marker;
+            parts.push(Part::Text(title.0));
+        }
+        MarkdownElement::ListItem { text, children, .. } => {
+            fill = TokenKind::Marker;
+            let task = ["[ ]", "[x]", "[X]"].into_iter().find_map(|marker| {
+                let rest = text.0.strip_prefix(marker)?;
+                (rest.is_empty() || rest.starts_with(char::is_whitespace))
+                    .then(|| (&text.0[..marker.len()], rest.trim_start()))
+            });
+            if let Some((marker, rest)) = task {
+                parts.push(Part::Token(TokenKind::TaskMarker, marker));
+                parts.push(Part::Text(rest));
+            } else {
+                parts.push(Part::Text(text.0));
+            }
+            parts.push(Part::Children(children.0));
+        }
+        MarkdownElement::Table(table) => {
+            for row in table.rows() {
+                parts.extend(row.cells().map(|cell| Part::Text(cell.0.trim())));
+            }
+        }
+        MarkdownElement::CodeBlock { language, code } => {
+            parts.push(Part::Token(TokenKind::Language, language));
+            parts.push(Part::Token(TokenKind::Content, code.0));
+        }
+        MarkdownElement::LaTeXBlock { script: content }
+        | MarkdownElement::CommentBlock(content)
+        | MarkdownElement::HTMLElement(content)
+        | MarkdownElement::Frontmatter(content) => {
+            parts.push(Part::Token(TokenKind::Content, content));
+        }
+        MarkdownElement::CommandBlock(command) => {
+            parts.push(Part::Token(TokenKind::CommandName, command.name));
+            parts.push(Part::Token(TokenKind::CommandArguments, command.arguments));
+            parts.push(Part::Children(command.inner.0));
+        }
+        MarkdownElement::Custom(ref block) => {
+            parts.push(Part::Token(TokenKind::Content, block.data.arguments));
+            parts.push(Part::Token(TokenKind::Content, block.data.content));
+        }
+        MarkdownElement::HorizontalRule | MarkdownElement::Footnote | MarkdownElement::Empty => {}
+    }
+
+    let mut children = Vec::new();
+    let mut last = range.start;
+    for part in parts {
+        let (Part::Token(_, slice) | Part::Text(slice) | Part::Children(slice)) = part;
+        let Some(start) = start_in(source, slice) else {
+            continue;
+        };
+        // List item children slices include the following blank lines
+        let end = (start + slice.len()).min(range.end);
+        if start < last || start >= end {
+            continue;
+        }
+        trivia(source, last..start, fill, &mut children);
+        match part {
+            Part::Token(kind, _) => children.push(token(source, kind, start..end)),
+            Part::Text(_) => inline_tokens(source, start..end, options, 0, &mut children),
+            Part::Children(_) => nodes(source, start..end, options, &mut children)?,
+        }
+        last = end;
+    }
+    trivia(source, last..range.end, fill, &mut children);
+
+    Ok(SyntaxNode {
+        kind: NodeKind::Element(element),
+        text: &source[range.clone()],
+        range,
+        children,
+    })
+}
+
+/// Start of `slice` in `source`. Empty slices (and data from extensions) may not be slices of the
+/// source
+fn start_in(source: &str, slice: &str) -> Option<usize> {
+    (slice.as_ptr() as usize)
+        .checked_sub(source.as_ptr() as usize)
+        .filter(|start| !slice.is_empty() && start + slice.len() <= source.len())
+}
+
+/// Tokens of the text in `range`, at `depth` in emphasis and links. The slices of its
+/// [`MarkdownTextElement`]s are tokens (or split again) and the bytes between them are delimiters
+fn inline_tokens<'a>(
+    source: &'a str,
+    range: Range<usize>,
+    options: &ParseOptions,
+    depth: usize,
+    out: &mut Vec<SyntaxElement<'a>>,
+) {
+    let mut last = range.start;
+    for part in RawText(&source[range.clone()]).parts_with_options(options) {
+        let mut slices = match part {
+            MarkdownTextElement::Plain(text)
+            | MarkdownTextElement::Emoji(text)
+            | MarkdownTextElement::Tag(text) => vec![Part::Token(TokenKind::Text, text)],
+            MarkdownTextElement::Bold(text)
+            | MarkdownTextElement::Italic(text)
+            | MarkdownTextElement::BoldAndItalic(text)
+            | MarkdownTextElement::StrikeThrough(text)
+            | MarkdownTextElement::Highlight(text)
+            | MarkdownTextElement::Superscript(text)
+            | MarkdownTextElement::Subscript(text) => vec![Part::Text(text)],
+            MarkdownTextElement::Code(content)
+            | MarkdownTextElement::Latex(content)
+            | MarkdownTextElement::DisplayLatex(content)
+            | MarkdownTextElement::Expression(content) => {
+                vec![Part::Token(TokenKind::Content, content)]
+            }
+            MarkdownTextElement::Link { on, to } => {
+                vec![Part::Text(on.0), Part::Token(TokenKind::Destination, to)]
+            }
+            MarkdownTextElement::Media { alt, source } => vec![
+                Part::Token(TokenKind::Text, alt),
+                Part::Token(TokenKind::Destination, source),
+            ],
+            MarkdownTextElement::Command(command) => vec![
+                Part::Token(TokenKind::CommandName, command.name),
+                Part::Token(TokenKind::CommandArguments, command.arguments),
+            ],
+            MarkdownTextElement::Custom(custom) => {
+                vec![Part::Token(TokenKind::Content, custom.content)]
+            }
+        };
+        // Wikilinks give the destination before the text
+        slices.sort_by_key(
+            |(Part::Token(_, slice) | Part::Text(slice) | Part::Children(slice))| slice.as_ptr(),
+        );
+        for slice in slices {
+            let (Part::Token(_, text) | Part::Text(text) | Part::Children(text)) = slice;
+            // Slices can be the same, such as the text and destination of `<link>`
+            let Some(start) = start_in(source, text).filter(|start| *start >= last) else {
+                continue;
+            };
+            let end = (start + text.len()).min(range.end);
+            delimiters(source, last..start, out);
+            match slice {
+                // Bounded like nested link text
+                Part::Text(_) if options.max_nesting_depth.is_none_or(|limit| depth < limit) => {
+                    inline_tokens(source, start..end, options, depth + 1, out);
+                }
+                Part::Token(kind, _) => out.push(token(source, kind, start..end)),
+                Part::Text(_) | Part::Children(_) => {
+                    out.push(token(source, TokenKind::Text, start..end));
+                }
+            }
+            last = end;
+        }
+    }
+    delimiters(source, last..range.end, out);
+}
+
+/// Splits inline syntax in `range` into tokens for each delimiter
+fn delimiters<'a>(source: &'a str, range: Range<usize>, out: &mut Vec<SyntaxElement<'a>>) {
+    const PAIRS: [&str; 12] = [
+        "/%}", "{%", "%}", "](", "![", "[[", "]]", "$$", "\\[", "\\]", "\\(", "\\)",
+    ];
+
+    let mut start = range.start;
+    while start < range.end {
+        let rest = &source[start..range.end];
+        let Some(first) = rest.chars().next() else {
+            break;
+        };
+        if first.is_whitespace() {
+            let length = rest
+                .find(|chr: char| !chr.is_whitespace())
+                .unwrap_or(rest.len());
+            trivia(source, start..start + length, TokenKind::Delimiter, out);
+            start += length;
+            continue;
+        }
+        let length = PAIRS
+            .iter()
+            .find(|pair| rest.starts_with(**pair))
+            .map_or_else(
+                || rest.find(|chr| chr != first).unwrap_or(rest.len()),
+                |pair| pair.len(),
+            );
+        out.push(token(source, TokenKind::Delimiter, start..start + length));
+        start += length;
+    }
+}
+
+/// Splits `range` into whitespace, line ending and `fill` tokens
+fn trivia<'a>(
+    source: &'a str,
+    range: Range<usize>,
+    fill: TokenKind,
+    out: &mut Vec<SyntaxElement<'a>>,
+) {
+    let is_whitespace = |chr: char| chr.is_whitespace() && !matches!(chr, '\n' | '\r');
+    let mut start = range.start;
+    while start < range.end {
+        let rest = &source[start..range.end];
+        let (kind, length) = if rest.starts_with("\r\n") {
+            (TokenKind::LineEnding, "\r\n".len())
+        } else if rest.starts_with(['\n', '\r']) {
+            (TokenKind::LineEnding, 1)
+        } else if rest.starts_with(is_whitespace) {
+            let length = rest.find(|chr| !is_whitespace(chr)).unwrap_or(rest.len());
+            (TokenKind::Whitespace, length)
+        } else {
+            let length = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            (fill, length)
+        };
+        out.push(token(source, kind, start..start + length));
+        start += length;
+    }
+}
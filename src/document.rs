@@ -0,0 +1,537 @@
+//! Owned tree of a whole document. Built from [`crate::parse`], with blocks nested under the
+//! [`Section`] of their heading and list items grouped into [`List`]s
+
+use crate::{
+    arguments::{ArgumentsError, CommandArguments},
+    fmt::{format_document, FormatOptions},
+    parse_with_positions, Alignment, CommandBlock, MarkdownElement, MarkdownTextElement,
+    ParseError, ParseOptions, RawMarkdown, RawText,
+};
+
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Document {
+    /// Blocks before the first heading, then top level sections
+    pub children: Vec<Block>,
+}
+
+impl Document {
+    /// # Errors
+    /// errors from markdown parsing (including of command and list item contents)
+    pub fn parse(on: &str) -> Result<Self, ParseError> {
+        Self::parse_with_options(on, &ParseOptions::default())
+    }
+
+    /// # Errors
+    /// errors from markdown parsing (including of command and list item contents)
+    pub fn parse_with_options(on: &str, options: &ParseOptions) -> Result<Self, ParseError> {
+        Ok(Self {
+            children: blocks(on, options)?,
+        })
+    }
+
+    /// Markdown source for the tree. [`Document::parse`] of the result gives an equal tree.
+    /// There are no escapes, so emphasis is written with whichever of `*` and `_` is not in the
+    /// text around it. Trees that need both as text, or that have unbalanced delimiters paired
+    /// into emphasis starting with whitespace (as for `- * **a** *`), are not kept
+    #[must_use]
+    pub fn as_markdown(&self) -> String {
+        let options = FormatOptions {
+            align_tables: false,
+            ..FormatOptions::default()
+        };
+        format_document(self, &options)
+    }
+
+    /// Sections at any depth, in source order
+    #[must_use]
+    pub fn sections(&self) -> Vec<&Section> {
+        fn collect<'b>(blocks: &'b [Block], sections: &mut Vec<&'b Section>) {
+            for block in blocks {
+                if let Block::Section(section) = block {
+                    sections.push(section);
+                    collect(&section.children, sections);
+                }
+            }
+        }
+
+        let mut sections = Vec::new();
+        collect(&self.children, &mut sections);
+        sections
+    }
+}
+
+/// A heading and the blocks up to the next heading of the same or a lower level
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Section {
+    pub heading: Heading,
+    /// Includes sections of deeper headings
+    pub children: Vec<Block>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Heading {
+    pub level: u8,
+    pub content: Vec<Inline>,
+}
+
+impl Heading {
+    /// Text without decoration
+    #[must_use]
+    pub fn text(&self) -> String {
+        inlines_text(&self.content)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Block {
+    Section(Section),
+    Paragraph(Vec<Inline>),
+    Quote(Vec<Block>),
+    /// `> [!kind] title`
+    Callout {
+        kind: String,
+        title: Vec<Inline>,
+    },
+    List(List),
+    Table(Table),
+    CodeBlock {
+        language: String,
+        code: String,
+    },
+    LaTeXBlock {
+        script: String,
+    },
+    Command(Command),
+    /// Inside `%%`
+    Comment(String),
+    HTMLElement(String),
+    Frontmatter(String),
+    HorizontalRule,
+    Footnote,
+    /// Block of a [`crate::extensions::BlockExtension`]
+    Custom {
+        name: String,
+        arguments: String,
+        content: String,
+        /// Whole block, for formatting
+        source: String,
+        /// [`crate::extensions::CustomData::value`]. Not serialized
+        #[cfg_attr(feature = "serde", serde(skip))]
+        value: Option<crate::extensions::CustomValue>,
+    },
+}
+
+/// Consecutive list items of the same depth and kind
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct List {
+    /// Number of the first item of ordered lists
+    pub start: Option<u32>,
+    pub loose: bool,
+    pub items: Vec<ListItem>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ListItem {
+    pub content: Vec<Inline>,
+    /// Indented blocks and nested lists
+    pub children: Vec<Block>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Table {
+    pub header: Vec<Vec<Inline>>,
+    pub rows: Vec<Vec<Vec<Inline>>>,
+    /// Of each column, as [`crate::Table::alignments`]
+    pub alignments: Vec<Option<Alignment>>,
+}
+
+/// `{% name arguments %}` block or inline command
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Command {
+    pub name: String,
+    pub arguments: String,
+    pub children: Vec<Block>,
+}
+
+impl Command {
+    /// # Errors
+    /// for malformed argument lists
+    pub fn arguments(&self) -> Result<CommandArguments<'_>, ArgumentsError> {
+        CommandArguments::parse(&self.arguments)
+    }
+}
+
+/// Owned [`MarkdownTextElement`]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Inline {
+    Plain(String),
+    Italic(String),
+    Bold(String),
+    BoldAndItalic(String),
+    Code(String),
+    StrikeThrough(String),
+    /// Shortcode name
+    Emoji(String),
+    Latex(String),
+    DisplayLatex(String),
+    Expression(String),
+    Highlight(String),
+    Superscript(String),
+    Subscript(String),
+    Tag(String),
+    Link {
+        content: Vec<Inline>,
+        to: String,
+    },
+    Media {
+        alt: String,
+        source: String,
+    },
+    Command(Command),
+    /// Syntax of a [`crate::extensions::InlineExtension`]
+    Custom {
+        name: String,
+        content: String,
+        /// Whole element, for formatting
+        source: String,
+    },
+}
+
+impl Inline {
+    /// Text without decoration, as [`MarkdownTextElement::no_decoration`]. Includes link text
+    #[must_use]
+    pub fn text(&self) -> String {
+        match self {
+            Inline::Plain(i)
+            | Inline::Italic(i)
+            | Inline::Bold(i)
+            | Inline::BoldAndItalic(i)
+            | Inline::Code(i)
+            | Inline::StrikeThrough(i)
+            | Inline::Latex(i)
+            | Inline::DisplayLatex(i)
+            | Inline::Highlight(i)
+            | Inline::Superscript(i)
+            | Inline::Subscript(i)
+            | Inline::Tag(i)
+            | Inline::Custom { content: i, .. } => i.clone(),
+            Inline::Emoji(name) => MarkdownTextElement::Emoji(name).no_decoration().to_owned(),
+            Inline::Link { content, .. } => inlines_text(content),
+            Inline::Expression(_) | Inline::Media { .. } | Inline::Command(_) => String::new(),
+        }
+    }
+}
+
+fn inlines_text(inlines: &[Inline]) -> String {
+    inlines.iter().map(Inline::text).collect()
+}
+
+/// Removes the indentation shared by all lines
+fn dedent(on: &str) -> String {
+    dedented_lines(on).collect()
+}
+
+/// Lines of `on` (with line endings) without the indentation shared by all lines
+pub(crate) fn dedented_lines(on: &str) -> impl Iterator<Item = &str> {
+    let indentation = on
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start_matches([' ', '\t']).len())
+        .min()
+        .unwrap_or(0);
+    on.split_inclusive('\n').map(move |line| {
+        line.get(indentation..)
+            .unwrap_or(line.trim_start_matches([' ', '\t']))
+    })
+}
+
+fn blocks(on: &str, options: &ParseOptions) -> Result<Vec<Block>, ParseError> {
+    let mut builder = Builder {
+        options: options.clone(),
+        ..Builder::default()
+    };
+    let mut result = Ok(());
+    let mut last_end = 0;
+    parse_with_positions(on, options, |element, position| {
+        // Quote lines after a blank line start a new quote
+        if result.is_ok() && on[last_end..position.start].lines().count() > 1 {
+            result = builder.close_quote();
+        }
+        if result.is_ok() {
+            result = builder.element(element);
+        }
+        last_end = position.end;
+    })?;
+    result?;
+    builder.finish()
+}
+
+/// Adjacent plain text is joined, as the parser can split it at delimiters that are literal
+fn inlines(text: RawText, options: &ParseOptions) -> Result<Vec<Inline>, ParseError> {
+    let mut result = Vec::new();
+    for part in text.parts_with_options(options) {
+        let inline = match part {
+            MarkdownTextElement::Plain(i) => Inline::Plain(i.to_owned()),
+            MarkdownTextElement::Italic(i) => Inline::Italic(i.to_owned()),
+            MarkdownTextElement::Bold(i) => Inline::Bold(i.to_owned()),
+            MarkdownTextElement::BoldAndItalic(i) => Inline::BoldAndItalic(i.to_owned()),
+            MarkdownTextElement::Code(i) => Inline::Code(i.to_owned()),
+            MarkdownTextElement::StrikeThrough(i) => Inline::StrikeThrough(i.to_owned()),
+            MarkdownTextElement::Emoji(i) => Inline::Emoji(i.to_owned()),
+            MarkdownTextElement::Latex(i) => Inline::Latex(i.to_owned()),
+            MarkdownTextElement::DisplayLatex(i) => Inline::DisplayLatex(i.to_owned()),
+            MarkdownTextElement::Expression(i) => Inline::Expression(i.to_owned()),
+            MarkdownTextElement::Highlight(i) => Inline::Highlight(i.to_owned()),
+            MarkdownTextElement::Superscript(i) => Inline::Superscript(i.to_owned()),
+            MarkdownTextElement::Subscript(i) => Inline::Subscript(i.to_owned()),
+            MarkdownTextElement::Tag(i) => Inline::Tag(i.to_owned()),
+            MarkdownTextElement::Link { on, to } => Inline::Link {
+                content: inlines(on, options)?,
+                to: to.to_owned(),
+            },
+            MarkdownTextElement::Media { alt, source } => Inline::Media {
+                alt: alt.to_owned(),
+                source: source.to_owned(),
+            },
+            MarkdownTextElement::Command(command) => {
+                Inline::Command(self::command(command, options)?)
+            }
+            MarkdownTextElement::Custom(custom) => Inline::Custom {
+                name: custom.name.to_owned(),
+                content: custom.content.to_owned(),
+                source: custom.source.to_owned(),
+            },
+        };
+        if let (Some(Inline::Plain(last)), Inline::Plain(text)) = (result.last_mut(), &inline) {
+            last.push_str(text);
+        } else {
+            result.push(inline);
+        }
+    }
+    Ok(result)
+}
+
+fn command(command: CommandBlock, options: &ParseOptions) -> Result<Command, ParseError> {
+    Ok(Command {
+        name: command.name.to_owned(),
+        arguments: command.arguments.to_owned(),
+        children: blocks(command.inner.0, options)?,
+    })
+}
+
+#[derive(Default)]
+struct Builder {
+    options: ParseOptions,
+    /// Blocks before the first heading
+    root: Vec<Block>,
+    /// Open sections, innermost last
+    sections: Vec<Section>,
+    /// Open lists, innermost last. Nested lists are added to the last item of their parent
+    lists: Vec<List>,
+    /// Content of the consecutive quote lines so far, which are one quote
+    quote: Option<String>,
+}
+
+impl Builder {
+    fn current(&mut self) -> &mut Vec<Block> {
+        match self.sections.last_mut() {
+            Some(section) => &mut section.children,
+            None => &mut self.root,
+        }
+    }
+
+    fn close_list(&mut self) {
+        if let Some(list) = self.lists.pop() {
+            match self
+                .lists
+                .last_mut()
+                .and_then(|parent| parent.items.last_mut())
+            {
+                Some(parent) => parent.children.push(Block::List(list)),
+                None => self.current().push(Block::List(list)),
+            }
+        }
+    }
+
+    fn close_quote(&mut self) -> Result<(), ParseError> {
+        if let Some(content) = self.quote.take() {
+            let children = blocks(&content, &self.options)?;
+            self.current().push(Block::Quote(children));
+        }
+        Ok(())
+    }
+
+    fn close_section(&mut self) {
+        if let Some(section) = self.sections.pop() {
+            self.current().push(Block::Section(section));
+        }
+    }
+
+    /// Adds an item to the list at `depth`, starting the list if it is not open
+    fn item(
+        &mut self,
+        depth: usize,
+        number: Option<u32>,
+        text: RawText,
+        children: RawMarkdown,
+        loose: bool,
+    ) -> Result<(), ParseError> {
+        while self.lists.len() > depth
+            || self.lists.len() == depth
+                && self
+                    .lists
+                    .last()
+                    .is_some_and(|list| list.start.is_some() != number.is_some())
+        {
+            self.close_list();
+        }
+        if self.lists.len() < depth {
+            self.lists.push(List {
+                start: number,
+                loose,
+                items: Vec::new(),
+            });
+        }
+        let item = ListItem {
+            content: inlines(text, &self.options)?,
+            children: blocks(&dedent(children.0), &self.options)?,
+        };
+        if let Some(list) = self.lists.last_mut() {
+            list.items.push(item);
+        }
+        Ok(())
+    }
+
+    /// Adds more children to the last item of the list at `depth`
+    fn continue_item(&mut self, depth: usize, children: RawMarkdown) -> Result<(), ParseError> {
+        while self.lists.len() > depth {
+            self.close_list();
+        }
+        let children = blocks(&dedent(children.0), &self.options)?;
+        if let Some(item) = self.lists.last_mut().and_then(|list| list.items.last_mut()) {
+            item.children.extend(children);
+        }
+        Ok(())
+    }
+
+    fn element(&mut self, element: MarkdownElement) -> Result<(), ParseError> {
+        if let MarkdownElement::Quote(RawMarkdown(inner)) = element {
+            while !self.lists.is_empty() {
+                self.close_list();
+            }
+            let content = self.quote.get_or_insert_with(String::new);
+            if !content.is_empty() {
+                content.push('\n');
+            }
+            content.push_str(inner);
+            return Ok(());
+        }
+        self.close_quote()?;
+
+        if let MarkdownElement::ListItem {
+            level,
+            number,
+            text,
+            children,
+            loose,
+            continues,
+        } = element
+        {
+            // Parser levels only increase by one at a time
+            let depth = (usize::from(level) + 1).min(self.lists.len() + 1);
+            return if continues {
+                self.continue_item(depth, children)
+            } else {
+                self.item(depth, number, text, children, loose)
+            };
+        }
+
+        while !self.lists.is_empty() {
+            self.close_list();
+        }
+
+        let block = match element {
+            MarkdownElement::Heading { level, text } => {
+                while self
+                    .sections
+                    .last()
+                    .is_some_and(|section| section.heading.level >= level)
+                {
+                    self.close_section();
+                }
+                self.sections.push(Section {
+                    heading: Heading {
+                        level,
+                        content: inlines(text, &self.options)?,
+                    },
+                    children: Vec::new(),
+                });
+                return Ok(());
+            }
+            MarkdownElement::Paragraph(text) => Block::Paragraph(inlines(text, &self.options)?),
+            MarkdownElement::Callout { kind, title } => Block::Callout {
+                kind: kind.to_owned(),
+                title: inlines(title, &self.options)?,
+            },
+            MarkdownElement::Table(table) => {
+                let mut rows = table.rows().map(|row| {
+                    row.cells()
+                        .map(|cell| inlines(cell, &self.options))
+                        .collect()
+                });
+                Block::Table(Table {
+                    header: rows.next().transpose()?.unwrap_or_default(),
+                    rows: rows.collect::<Result<_, _>>()?,
+                    alignments: table.alignments().collect(),
+                })
+            }
+            MarkdownElement::CodeBlock { language, code } => Block::CodeBlock {
+                language: language.to_owned(),
+                code: code.to_string(),
+            },
+            MarkdownElement::LaTeXBlock { script } => Block::LaTeXBlock {
+                script: script.to_owned(),
+            },
+            MarkdownElement::CommandBlock(block) => Block::Command(command(block, &self.options)?),
+            MarkdownElement::CommentBlock(comment) => Block::Comment(comment.to_owned()),
+            MarkdownElement::HTMLElement(element) => Block::HTMLElement(element.to_owned()),
+            MarkdownElement::Frontmatter(frontmatter) => Block::Frontmatter(frontmatter.to_owned()),
+            MarkdownElement::HorizontalRule => Block::HorizontalRule,
+            MarkdownElement::Footnote => Block::Footnote,
+            MarkdownElement::Custom(block) => Block::Custom {
+                name: block.name.to_owned(),
+                arguments: block.data.arguments.to_owned(),
+                content: block.data.content.to_owned(),
+                source: block.source.to_owned(),
+                value: block.data.value,
+            },
+            MarkdownElement::Quote(_)
+            | MarkdownElement::ListItem { .. }
+            | MarkdownElement::Empty => {
+                return Ok(());
+            }
+        };
+        self.current().push(block);
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Vec<Block>, ParseError> {
+        self.close_quote()?;
+        while !self.lists.is_empty() {
+            self.close_list();
+        }
+        while !self.sections.is_empty() {
+            self.close_section();
+        }
+        Ok(self.root)
+    }
+}
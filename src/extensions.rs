@@ -0,0 +1,327 @@
+//! Syntax added by users of the crate. Extensions are registered on [`ParseOptions`] and are tried
+//! before the built-in syntax. [`BlockExtension`]s give [`MarkdownElement::Custom`]s and
+//! [`InlineExtension`]s give [`MarkdownTextElement::Custom`]s
+//!
+//! ```
+//! use simple_markdown_parser::extensions::{BlockExtension, BlockLine, CustomData};
+//! use simple_markdown_parser::{parse_with_options, MarkdownElement, ParseOptions};
+//!
+//! /// `:::kind` ... `:::` admonitions
+//! #[derive(Debug)]
+//! struct Admonition;
+//!
+//! impl BlockExtension for Admonition {
+//!     fn name(&self) -> &'static str {
+//!         "admonition"
+//!     }
+//!
+//!     fn starts(&self, line: &str) -> bool {
+//!         line.starts_with(":::") && line.len() > 3
+//!     }
+//!
+//!     fn next_line(&self, _opening: &str, line: &str) -> BlockLine {
+//!         if line.trim() == ":::" {
+//!             BlockLine::Close
+//!         } else {
+//!             BlockLine::Continue
+//!         }
+//!     }
+//!
+//!     fn element<'a>(&self, opening: &'a str, body: &'a str) -> CustomData<'a> {
+//!         CustomData::new(opening[3..].trim(), body)
+//!     }
+//! }
+//!
+//! let options = ParseOptions::default().block_extension(Admonition);
+//! let mut elements = Vec::new();
+//! parse_with_options(":::note\nText\n:::", &options, |element| elements.push(element)).unwrap();
+//! let MarkdownElement::Custom(block) = &elements[0] else {
+//!     panic!()
+//! };
+//! assert_eq!((block.name, block.data.arguments), ("admonition", "note"));
+//! ```
+//!
+//!
+//! An inline extension for `@mentions`
+//!
+//! ```
+//! use simple_markdown_parser::extensions::InlineExtension;
+//! use simple_markdown_parser::{MarkdownTextElement, ParseOptions, RawText};
+//!
+//! #[derive(Debug)]
+//! struct Mention;
+//!
+//! impl InlineExtension for Mention {
+//!     fn name(&self) -> &'static str {
+//!         "mention"
+//!     }
+//!
+//!     fn triggers(&self) -> &[char] {
+//!         &['@']
+//!     }
+//!
+//!     fn parse<'a>(&self, rest: &'a str, preceding: Option<char>) -> Option<(&'a str, usize)> {
+//!         // Not in email addresses
+//!         if preceding.is_some_and(char::is_alphanumeric) {
+//!             return None;
+//!         }
+//!         let name = &rest[1..];
+//!         let length = name.find(|chr: char| !chr.is_alphanumeric()).unwrap_or(name.len());
+//!         (length > 0).then(|| (&name[..length], 1 + length))
+//!     }
+//! }
+//!
+//! let options = ParseOptions::default().inline_extension(Mention);
+//! let parts: Vec<_> = RawText("Thanks @ben!").parts_with_options(&options).collect();
+//! let MarkdownTextElement::Custom(mention) = &parts[1] else {
+//!     panic!()
+//! };
+//! assert_eq!((mention.source, mention.content), ("@ben", "ben"));
+//! ```
+//!
+//! Blocks can also carry a [`CustomValue`] of the extension's own type
+//!
+//! ```
+//! use simple_markdown_parser::extensions::{BlockExtension, BlockLine, CustomData, CustomValue};
+//! use simple_markdown_parser::{parse_with_options, MarkdownElement, ParseOptions};
+//!
+//! #[derive(Debug)]
+//! struct Columns;
+//!
+//! impl BlockExtension for Columns {
+//!     fn name(&self) -> &'static str {
+//!         "columns"
+//!     }
+//!
+//!     fn starts(&self, line: &str) -> bool {
+//!         line.starts_with("::: columns ")
+//!     }
+//!
+//!     fn next_line(&self, _opening: &str, line: &str) -> BlockLine {
+//!         if line == ":::" {
+//!             BlockLine::Close
+//!         } else {
+//!             BlockLine::Continue
+//!         }
+//!     }
+//!
+//!     fn element<'a>(&self, opening: &'a str, body: &'a str) -> CustomData<'a> {
+//!         let arguments = &opening["::: columns ".len()..];
+//!         let count: u8 = arguments.parse().unwrap_or(1);
+//!         CustomData::new(arguments, body).value(CustomValue::new(count))
+//!     }
+//! }
+//!
+//! let options = ParseOptions::default().block_extension(Columns);
+//! let mut elements = Vec::new();
+//! parse_with_options("::: columns 2\nText\n:::", &options, |element| elements.push(element))
+//!     .unwrap();
+//! let MarkdownElement::Custom(block) = &elements[0] else {
+//!     panic!()
+//! };
+//! assert_eq!(block.value::<u8>(), Some(&2));
+//! ```
+//!
+//! [`ParseOptions`]: crate::ParseOptions
+//! [`MarkdownElement::Custom`]: crate::MarkdownElement::Custom
+//! [`MarkdownTextElement::Custom`]: crate::MarkdownTextElement::Custom
+
+use std::{any::Any, sync::Arc};
+
+/// Block syntax, registered with [`crate::ParseOptions::block_extension`]. Blocks start at a
+/// line that the extension [`BlockExtension::starts`] and continue until
+/// [`BlockExtension::next_line`] closes or ends them (or the end of the source)
+pub trait BlockExtension: std::fmt::Debug + Send + Sync {
+    /// Identifies the blocks of the extension, such as to [`FeatureEmitter::custom_block`]
+    ///
+    /// [`FeatureEmitter::custom_block`]: crate::extras::emit::FeatureEmitter::custom_block
+    fn name(&self) -> &'static str;
+
+    /// Whether `line` (with its indentation and without its line ending) opens a block
+    fn starts(&self, line: &str) -> bool;
+
+    /// For each line after the opening line of a block, whether the block includes it
+    fn next_line(&self, opening: &str, line: &str) -> BlockLine;
+
+    /// Data of a block from its opening line and the lines after it (up to but excluding a
+    /// closing line, with line endings). The data should be slices of `opening` and `body`, as
+    /// other slices are empty in reparsed elements (see [`crate::incremental`])
+    fn element<'a>(&self, opening: &'a str, body: &'a str) -> CustomData<'a>;
+}
+
+/// How a line after the opening line relates to a [`BlockExtension`] block
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BlockLine {
+    /// The line is part of the body
+    Continue,
+    /// The line closes the block (and is part of it, but not the body)
+    Close,
+    /// The block ended at the previous line. The line is parsed as normal
+    End,
+}
+
+/// [`crate::MarkdownElement::Custom`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CustomBlock<'a> {
+    /// [`BlockExtension::name`] of the extension that parsed the block
+    pub name: &'static str,
+    /// Whole block, from the start of the opening line to the end of the last line
+    pub source: &'a str,
+    pub data: CustomData<'a>,
+}
+
+impl CustomBlock<'_> {
+    /// [`CustomData::value`] if it is a `T`
+    #[must_use]
+    pub fn value<T: Any>(&self) -> Option<&T> {
+        self.data.value.as_ref()?.downcast_ref()
+    }
+}
+
+/// Parts of a custom block, as given by [`BlockExtension::element`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CustomData<'a> {
+    /// Such as the kind or title from the opening line
+    pub arguments: &'a str,
+    /// Such as Markdown or code in the body
+    pub content: &'a str,
+    /// Anything else the extension parsed from the block. Not serialized
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub value: Option<CustomValue>,
+}
+
+impl<'a> CustomData<'a> {
+    #[must_use]
+    pub fn new(arguments: &'a str, content: &'a str) -> Self {
+        Self {
+            arguments,
+            content,
+            value: None,
+        }
+    }
+
+    #[must_use]
+    pub fn value(mut self, value: CustomValue) -> Self {
+        self.value = Some(value);
+        self
+    }
+}
+
+/// Value of a type defined by an extension. Clones share the value. Values are equal when they
+/// are of the same type and equal by its [`Eq`]
+#[derive(Clone)]
+pub struct CustomValue(Arc<dyn Value>);
+
+impl CustomValue {
+    pub fn new(value: impl Any + Eq + Send + Sync) -> Self {
+        Self(Arc::new(value))
+    }
+
+    /// The value if it is a `T`
+    #[must_use]
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.0.as_any().downcast_ref()
+    }
+}
+
+impl PartialEq for CustomValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.equals(other.0.as_any())
+    }
+}
+
+impl Eq for CustomValue {}
+
+impl std::fmt::Debug for CustomValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CustomValue(..)")
+    }
+}
+
+/// [`CustomValue`] contents, compared with the equality of their type
+trait Value: Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+
+    fn equals(&self, other: &dyn Any) -> bool;
+}
+
+impl<T: Any + Eq + Send + Sync> Value for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn equals(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<T>() == Some(self)
+    }
+}
+
+/// Inline syntax, registered with [`crate::ParseOptions::inline_extension`]
+pub trait InlineExtension: std::fmt::Debug + Send + Sync {
+    /// Identifies the elements of the extension, such as to [`FeatureEmitter::custom_inline`]
+    ///
+    /// [`FeatureEmitter::custom_inline`]: crate::extras::emit::FeatureEmitter::custom_inline
+    fn name(&self) -> &'static str;
+
+    /// Characters that can start the syntax. [`InlineExtension::parse`] is only called at these
+    fn triggers(&self) -> &[char];
+
+    /// For `rest` of the text (starting with a trigger character) and the character before it,
+    /// the content and length of the syntax at the start of `rest`. `None` leaves the text to the
+    /// other syntax
+    fn parse<'a>(&self, rest: &'a str, preceding: Option<char>) -> Option<(&'a str, usize)>;
+}
+
+/// [`crate::MarkdownTextElement::Custom`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CustomInline<'a> {
+    /// [`InlineExtension::name`] of the extension that parsed the element
+    pub name: &'static str,
+    /// Whole element, including its delimiters
+    pub source: &'a str,
+    /// As given by [`InlineExtension::parse`]
+    pub content: &'a str,
+}
+
+/// Registered extensions, which are equal when they are the same instances. Clones share them
+pub(crate) struct Extensions<T: ?Sized>(pub(crate) Arc<[Arc<T>]>);
+
+impl<T: ?Sized> Extensions<T> {
+    /// With `extension` after the others
+    pub(crate) fn with(&self, extension: Arc<T>) -> Self {
+        Self(self.0.iter().cloned().chain([extension]).collect())
+    }
+}
+
+impl<T: ?Sized> Default for Extensions<T> {
+    fn default() -> Self {
+        Self(Arc::new([]))
+    }
+}
+
+impl<T: ?Sized> Clone for Extensions<T> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl<T: ?Sized> PartialEq for Extensions<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.len() == other.0.len()
+            && self
+                .0
+                .iter()
+                .zip(other.0.iter())
+                .all(|(extension, other)| Arc::ptr_eq(extension, other))
+    }
+}
+
+impl<T: ?Sized> Eq for Extensions<T> {}
+
+impl<T: ?Sized + std::fmt::Debug> std::fmt::Debug for Extensions<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.0.iter()).finish()
+    }
+}
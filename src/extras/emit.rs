@@ -1,5 +1,11 @@
-use crate::{MarkdownElement, MarkdownTextElement, RawText};
-use std::io::Write;
+use crate::{
+    arguments::{ArgumentsError, CommandArguments},
+    document::dedented_lines,
+    extensions::{CustomBlock, CustomInline},
+    streaming::parse_reader,
+    MarkdownElement, MarkdownTextElement, ParseOptions, RawMarkdown, RawText, Table, TableRow,
+};
+use std::io::{BufRead, Write};
 
 #[cfg(target_family = "wasm")]
 use wasm_bindgen::prelude::*;
@@ -19,52 +25,253 @@ pub fn markdown_to_html_string(source: &str, emitter: Option<FeatureEmitterWASM>
     }
 }
 
+/// # Errors
+/// errors from parsing and writing to `out`
 pub fn markdown_to_html(
     source: &str,
     out: &mut impl Write,
     emitter: &mut impl FeatureEmitter,
-) -> Result<(), ()> {
-    let mut last_was_list_item: bool = false;
-    crate::parse(source, |item| {
-        let is_list_item = matches!(&item, MarkdownElement::ListItem { .. });
-        if !last_was_list_item && is_list_item {
-            writeln!(out, "<ul>").unwrap();
-        } else if last_was_list_item && !is_list_item {
-            writeln!(out, "</ul>").unwrap();
+) -> Result<(), Box<dyn std::error::Error>> {
+    markdown_to_html_with_options(source, &ParseOptions::default(), out, emitter)
+}
+
+/// # Errors
+/// errors from parsing and writing to `out`
+pub fn markdown_to_html_with_options(
+    source: &str,
+    options: &ParseOptions,
+    out: &mut impl Write,
+    emitter: &mut impl FeatureEmitter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut open = OpenBlocks::default();
+    let mut result = Ok(());
+    let mut emitter = InSource {
+        emitter: &*emitter,
+        source,
+    };
+    crate::parse_with_options(source, options, |item| {
+        if result.is_ok() {
+            result = open.element(out, &mut emitter, options, item);
         }
-        element_to_html(out, emitter, item).unwrap();
-        last_was_list_item = is_list_item;
-    })
+    })?;
+    result?;
+    open.close_all(out)
+}
+
+/// [`markdown_to_html_with_options`] for sources read in chunks. Output for each block is written
+/// as soon as the block is complete
+/// # Errors
+/// errors from reading (including invalid UTF-8), parsing and writing to `out`
+pub fn markdown_to_html_from_reader(
+    reader: impl BufRead,
+    options: &ParseOptions,
+    out: &mut impl Write,
+    emitter: &mut impl FeatureEmitter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut open = OpenBlocks::default();
+    let mut result = Ok(());
+    parse_reader(reader, options, |item| {
+        if result.is_ok() {
+            result = open.element(out, emitter, options, item);
+        }
+    })?;
+    result?;
+    open.close_all(out)
+}
+
+/// Lists and table that later elements can continue
+#[derive(Default)]
+struct OpenBlocks {
+    /// Tags of the open lists, innermost last. Each has an open `<li>`
+    lists: Vec<&'static str>,
+    /// Whether a table is open, for the parts of long tables from streaming
+    table: bool,
+}
+
+impl OpenBlocks {
+    fn element(
+        &mut self,
+        out: &mut impl Write,
+        emitter: &mut impl FeatureEmitter,
+        options: &ParseOptions,
+        item: MarkdownElement,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if let MarkdownElement::ListItem {
+            level,
+            number,
+            text,
+            children,
+            loose,
+            continues,
+        } = item
+        {
+            self.close_table(out)?;
+            let depth = usize::from(level) + 1;
+            if continues {
+                while self.lists.len() > depth {
+                    self.close(out)?;
+                }
+                return list_item_children_to_html(out, emitter, options, children, loose);
+            }
+            let tag = if number.is_some() { "ol" } else { "ul" };
+            while self.lists.len() > depth
+                || (self.lists.len() == depth && self.lists.last() != Some(&tag))
+            {
+                self.close(out)?;
+            }
+            if self.lists.len() == depth {
+                writeln!(out, "</li>")?;
+            }
+            while self.lists.len() < depth {
+                match number {
+                    Some(start) if start != 1 => writeln!(out, "<ol start=\"{start}\">")?,
+                    _ => writeln!(out, "<{tag}>")?,
+                }
+                self.lists.push(tag);
+            }
+            list_item_to_html(out, emitter, options, text, children, loose)
+        } else if let MarkdownElement::Table(table) = item {
+            while !self.lists.is_empty() {
+                self.close(out)?;
+            }
+            if !(self.table && table.continues()) {
+                self.close_table(out)?;
+                table_head_to_html(out, emitter, options, &table)?;
+                self.table = true;
+            }
+            table_rows_to_html(out, emitter, options, &table)
+        } else {
+            self.close_all(out)?;
+            element_to_html_with_options(out, emitter, options, item)
+        }
+    }
+
+    /// Closes the innermost list and its open `<li>`
+    fn close(&mut self, out: &mut impl Write) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(tag) = self.lists.pop() {
+            writeln!(out, "</li>\n</{tag}>")?;
+        }
+        Ok(())
+    }
+
+    fn close_table(&mut self, out: &mut impl Write) -> Result<(), Box<dyn std::error::Error>> {
+        if std::mem::take(&mut self.table) {
+            writeln!(out, "</tbody>\n</table>")?;
+        }
+        Ok(())
+    }
+
+    fn close_all(&mut self, out: &mut impl Write) -> Result<(), Box<dyn std::error::Error>> {
+        while !self.lists.is_empty() {
+            self.close(out)?;
+        }
+        self.close_table(out)
+    }
 }
 
 pub trait FeatureEmitter {
     fn code_block(&self, language: &str, code: &str) -> String;
 
-    fn latex(&self, code: &str) -> String;
+    /// Defaults to the LaTeX source
+    fn latex(&self, code: &str) -> String {
+        code.to_owned()
+    }
+
+    /// Defaults to the inside of the command, as is
+    fn command(&self, _name: &str, _args: &CommandArguments, inner: &str) -> String {
+        inner.to_owned()
+    }
+
+    /// For commands with arguments that could not be parsed. `error` is positioned in the source
+    /// given to [`markdown_to_html_with_options`] and otherwise in `arguments`. Defaults to
+    /// [`FeatureEmitter::command`] without arguments
+    fn malformed_command(
+        &self,
+        name: &str,
+        _arguments: &str,
+        _error: &ArgumentsError,
+        inner: &str,
+    ) -> String {
+        self.command(name, &CommandArguments::default(), inner)
+    }
+
+    /// Defaults to the expression
+    fn interpolation(&self, expression: &str) -> String {
+        expression.to_owned()
+    }
 
-    fn command(&self, name: &str, args: Vec<(&str, &str)>, inner: &str) -> String;
+    /// For blocks of [`crate::extensions::BlockExtension`]s, which can be told apart by their
+    /// `name`. Defaults to the content in a `<div>` with the name as `data-block`
+    fn custom_block(&self, block: &CustomBlock) -> String {
+        format!(
+            "<div data-block=\"{name}\">\n{content}\n</div>",
+            name = block.name,
+            content = block.data.content.trim_end()
+        )
+    }
 
-    fn interpolation(&self, expression: &str) -> String;
+    /// For elements of [`crate::extensions::InlineExtension`]s, which can be told apart by their
+    /// `name`. Defaults to the content
+    fn custom_inline(&self, inline: &CustomInline) -> String {
+        inline.content.to_owned()
+    }
 }
 
-/// Un-highlighted code and panics on `RegExp`
-pub struct BlankFeatureEmitter;
+/// Positions argument errors in `source`, which the elements are slices of
+struct InSource<'a, E> {
+    emitter: &'a E,
+    source: &'a str,
+}
 
-impl FeatureEmitter for BlankFeatureEmitter {
-    fn code_block(&self, _language: &str, code: &str) -> String {
-        code.to_owned()
+impl<E: FeatureEmitter> FeatureEmitter for InSource<'_, E> {
+    fn code_block(&self, language: &str, code: &str) -> String {
+        self.emitter.code_block(language, code)
+    }
+
+    fn latex(&self, code: &str) -> String {
+        self.emitter.latex(code)
+    }
+
+    fn command(&self, name: &str, args: &CommandArguments, inner: &str) -> String {
+        self.emitter.command(name, args, inner)
+    }
+
+    fn malformed_command(
+        &self,
+        name: &str,
+        arguments: &str,
+        error: &ArgumentsError,
+        inner: &str,
+    ) -> String {
+        let mut error = error.clone();
+        let offset = (arguments.as_ptr() as usize).wrapping_sub(self.source.as_ptr() as usize);
+        if offset <= self.source.len() {
+            error.position = (error.position.start + offset)..(error.position.end + offset);
+        }
+        self.emitter
+            .malformed_command(name, arguments, &error, inner)
     }
 
-    fn latex(&self, _code: &str) -> String {
-        panic!("`BlankFeatureEmitter` does implement LaTeX HTML generation");
+    fn interpolation(&self, expression: &str) -> String {
+        self.emitter.interpolation(expression)
     }
 
-    fn command(&self, _name: &str, _args: Vec<(&str, &str)>, _inner: &str) -> String {
-        panic!("`BlankFeatureEmitter` does implement command generation")
+    fn custom_block(&self, block: &CustomBlock) -> String {
+        self.emitter.custom_block(block)
     }
 
-    fn interpolation(&self, _expression: &str) -> String {
-        panic!("`BlankFeatureEmitter` does implement interpolation")
+    fn custom_inline(&self, inline: &CustomInline) -> String {
+        self.emitter.custom_inline(inline)
+    }
+}
+
+/// Un-highlighted code and the [`FeatureEmitter`] defaults for everything else, which emit
+/// features as their source
+pub struct BlankFeatureEmitter;
+
+impl FeatureEmitter for BlankFeatureEmitter {
+    fn code_block(&self, _language: &str, code: &str) -> String {
+        code.to_owned()
     }
 }
 
@@ -80,11 +287,19 @@ pub struct FeatureEmitterWASM {
 #[cfg(target_family = "wasm")]
 #[wasm_bindgen(typescript_custom_section)]
 const TS_APPEND_CONTENT: &'static str = r#"
+type ArgumentValue = string | number | boolean | Array<ArgumentValue>;
+
+export interface CommandArguments {
+    positional: Array<ArgumentValue>,
+    named: Record<string, ArgumentValue>,
+    flags: Array<string>,
+}
+
 export class FeatureEmitterWASM { 
     constructor(
         code_block_callback: (language: string, code: string) => string,
         latex_callback: (code: string) => string,
-        command_callback: (name: string, args: Array<[string, string]>, inner: string) => string,
+        command_callback: (name: string, args: CommandArguments, inner: string) => string,
         interpolation_callback: (expression: string) => string,
     );
 }
@@ -136,17 +351,40 @@ impl FeatureEmitter for FeatureEmitterWASM {
         result_to_string(result)
     }
 
-    fn command(&self, name: &str, args: Vec<(&str, &str)>, inner: &str) -> String {
-        use js_sys::Array;
+    fn command(&self, name: &str, args: &CommandArguments, inner: &str) -> String {
+        use crate::arguments::ArgumentValue;
+        use js_sys::{Array, Object, Reflect};
+
+        fn value_to_js(value: &ArgumentValue) -> JsValue {
+            match value {
+                ArgumentValue::String(value) => JsValue::from_str(value),
+                ArgumentValue::Number(value) => JsValue::from_f64(*value),
+                ArgumentValue::Boolean(value) => JsValue::from_bool(*value),
+                ArgumentValue::List(items) => {
+                    items.iter().map(value_to_js).collect::<Array>().into()
+                }
+            }
+        }
+
+        let named = Object::new();
+        for (key, value) in &args.named {
+            let _ = Reflect::set(&named, &JsValue::from_str(key), &value_to_js(value));
+        }
+        let args_object = Object::new();
+        let positional: Array = args.positional.iter().map(value_to_js).collect();
+        let flags: Array = args
+            .flags
+            .iter()
+            .map(|flag| JsValue::from_str(flag))
+            .collect();
+        let _ = Reflect::set(&args_object, &JsValue::from_str("positional"), &positional);
+        let _ = Reflect::set(&args_object, &JsValue::from_str("named"), &named);
+        let _ = Reflect::set(&args_object, &JsValue::from_str("flags"), &flags);
 
-        let args_array = Array::new();
-        args.into_iter().for_each(|(l, r)| {
-            args_array.push(&Array::of2(&JsValue::from_str(l), &JsValue::from_str(r)).into());
-        });
         let result = self.command_callback.call3(
             &JsValue::NULL,
             &JsValue::from_str(name),
-            &args_array.into(),
+            &args_object.into(),
             &JsValue::from_str(inner),
         );
         result_to_string(result)
@@ -160,69 +398,78 @@ impl FeatureEmitter for FeatureEmitterWASM {
     }
 }
 
-#[allow(clippy::match_same_arms)]
 pub fn element_to_html(
     out: &mut impl Write,
     emitter: &mut impl FeatureEmitter,
     item: MarkdownElement,
+) -> Result<(), Box<dyn std::error::Error>> {
+    element_to_html_with_options(out, emitter, &ParseOptions::default(), item)
+}
+
+/// [`element_to_html`] parsing contents with `options`
+#[allow(clippy::match_same_arms)]
+pub fn element_to_html_with_options(
+    out: &mut impl Write,
+    emitter: &mut impl FeatureEmitter,
+    options: &ParseOptions,
+    item: MarkdownElement,
 ) -> Result<(), Box<dyn std::error::Error>> {
     match item {
         MarkdownElement::Heading { level, text } => {
-            assert!(level < 7, "heading level too much for HTML");
+            // Elements can be made without the parser
+            let level = level.clamp(1, 6);
             writeln!(out, "<h{level}>")?;
-            inner_to_html(out, emitter, text)?;
+            inner_to_html_with_options(out, emitter, options, text)?;
             writeln!(out, "</h{level}>")?;
         }
         MarkdownElement::Quote(_text) => {
             writeln!(out, "<blockquote>")?;
             // TODO
-            // inner_to_html(out, emitter, text)?;
+            // inner_to_html_with_options(out, emitter, options, text)?;
             writeln!(out, "</blockquote>")?;
         }
+        MarkdownElement::Callout { kind, title } => {
+            writeln!(out, "<div class=\"callout\" data-callout=\"{kind}\">")?;
+            write!(out, "<div class=\"callout-title\">")?;
+            inner_to_html_with_options(out, emitter, options, title)?;
+            writeln!(out, "</div>\n</div>")?;
+        }
         MarkdownElement::Paragraph(text) => {
             if text.0.starts_with("![") || text.0.starts_with("[![") {
                 // Don't wrap media in `<p>`
-                inner_to_html(out, emitter, text)?;
+                inner_to_html_with_options(out, emitter, options, text)?;
             } else {
                 writeln!(out, "<p>")?;
-                inner_to_html(out, emitter, text)?;
+                inner_to_html_with_options(out, emitter, options, text)?;
                 writeln!(out, "</p>")?;
             }
         }
         MarkdownElement::ListItem {
-            level: _level,
+            children,
+            loose,
+            continues: true,
+            ..
+        } => {
+            list_item_children_to_html(out, emitter, options, children, loose)?;
+        }
+        MarkdownElement::ListItem {
             text,
+            children,
+            loose,
+            ..
         } => {
-            writeln!(out, "<li>")?;
-            inner_to_html(out, emitter, text)?;
+            list_item_to_html(out, emitter, options, text, children, loose)?;
             writeln!(out, "</li>")?;
         }
         // TODO
         MarkdownElement::Table(table) => {
-            writeln!(out, "<table>")?;
-            let mut rows = table.rows();
-            writeln!(out, "<thead><tr>")?;
-            for cell in rows.next().unwrap().cells() {
-                write!(out, "<th>")?;
-                inner_to_html(out, emitter, cell)?;
-                writeln!(out, "</th>")?;
-            }
-            writeln!(out, "</tr></thead>")?;
-            writeln!(out, "<tbody>")?;
-            for row in rows {
-                write!(out, "<tr>")?;
-                for cell in row.cells() {
-                    write!(out, "<td>")?;
-                    inner_to_html(out, emitter, cell)?;
-                    write!(out, "</td>")?;
-                }
-                writeln!(out, "</tr>")?;
-            }
+            table_head_to_html(out, emitter, options, &table)?;
+            table_rows_to_html(out, emitter, options, &table)?;
             writeln!(out, "</tbody>")?;
             writeln!(out, "</table>")?;
         }
         MarkdownElement::CodeBlock { language, code } => {
-            let inner = emitter.code_block(language, code);
+            let inner = emitter.code_block(language, &code.to_string());
             writeln!(out, "<pre>{inner}</pre>")?;
         }
         MarkdownElement::LaTeXBlock { script: _ } => {}
@@ -236,11 +483,16 @@ pub fn element_to_html(
             writeln!(out, "<hr>")?;
         }
         MarkdownElement::CommandBlock(command) => {
-            writeln!(
-                out,
-                "{result}",
-                result = emitter.command(command.name, command.arguments(), command.inner.0)
-            )?;
+            let result = match command.arguments_with_options(options) {
+                Ok(arguments) => emitter.command(command.name, &arguments, command.inner.0),
+                Err(error) => emitter.malformed_command(
+                    command.name,
+                    command.arguments,
+                    &error,
+                    command.inner.0,
+                ),
+            };
+            writeln!(out, "{result}")?;
         }
         // MarkdownElement::Media {
         //     alt: _,
@@ -248,28 +500,136 @@ pub fn element_to_html(
         //     source: _,
         // } => {}
         MarkdownElement::Footnote => {}
+        MarkdownElement::Custom(block) => {
+            writeln!(out, "{result}", result = emitter.custom_block(&block))?;
+        }
         MarkdownElement::CommentBlock(_) | MarkdownElement::Empty => {}
     }
 
     Ok(())
 }
 
+/// Opening `<table>`, the header row and opening `<tbody>`
+fn table_head_to_html(
+    out: &mut impl Write,
+    emitter: &mut impl FeatureEmitter,
+    options: &ParseOptions,
+    table: &Table,
+) -> Result<(), Box<dyn std::error::Error>> {
+    writeln!(out, "<table>")?;
+    writeln!(out, "<thead><tr>")?;
+    for cell in table.rows().next().iter().flat_map(TableRow::cells) {
+        write!(out, "<th>")?;
+        inner_to_html_with_options(out, emitter, options, cell)?;
+        writeln!(out, "</th>")?;
+    }
+    writeln!(out, "</tr></thead>")?;
+    writeln!(out, "<tbody>")?;
+    Ok(())
+}
+
+fn table_rows_to_html(
+    out: &mut impl Write,
+    emitter: &mut impl FeatureEmitter,
+    options: &ParseOptions,
+    table: &Table,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for row in table.rows().skip(1) {
+        write!(out, "<tr>")?;
+        for cell in row.cells() {
+            write!(out, "<td>")?;
+            inner_to_html_with_options(out, emitter, options, cell)?;
+            write!(out, "</td>")?;
+        }
+        writeln!(out, "</tr>")?;
+    }
+    Ok(())
+}
+
+/// Paragraphs of items in tight lists are not wrapped in `<p>`
+/// Opening `<li>` and content. Leaves the item open for nested lists
+fn list_item_to_html(
+    out: &mut impl Write,
+    emitter: &mut impl FeatureEmitter,
+    options: &ParseOptions,
+    text: RawText,
+    children: RawMarkdown,
+    loose: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    writeln!(out, "<li>")?;
+    if loose {
+        writeln!(out, "<p>")?;
+        inner_to_html_with_options(out, emitter, options, text)?;
+        writeln!(out, "</p>")?;
+    } else {
+        inner_to_html_with_options(out, emitter, options, text)?;
+        writeln!(out)?;
+    }
+    list_item_children_to_html(out, emitter, options, children, loose)
+}
+
+fn list_item_children_to_html(
+    out: &mut impl Write,
+    emitter: &mut impl FeatureEmitter,
+    options: &ParseOptions,
+    children: RawMarkdown,
+    loose: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let children: String = dedented_lines(children.0).collect();
+    let mut open = OpenBlocks::default();
+    let mut result = Ok(());
+    crate::parse_with_options(&children, options, |item| {
+        if result.is_err() {
+            return;
+        }
+        result = match item {
+            MarkdownElement::Paragraph(text) if !loose => open.close_all(out).and_then(|()| {
+                inner_to_html_with_options(out, emitter, options, text)?;
+                writeln!(out).map_err(Into::into)
+            }),
+            item => open.element(out, emitter, options, item),
+        };
+    })?;
+    result?;
+    open.close_all(out)
+}
+
 pub fn inner_to_html(
     out: &mut impl Write,
     emitter: &mut impl FeatureEmitter,
     text: RawText,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    for part in text.parts() {
-        text_element_to_html(out, emitter, part)?;
+    inner_to_html_with_options(out, emitter, &ParseOptions::default(), text)
+}
+
+/// [`inner_to_html`] with only the inline syntax enabled in `options`
+pub fn inner_to_html_with_options(
+    out: &mut impl Write,
+    emitter: &mut impl FeatureEmitter,
+    options: &ParseOptions,
+    text: RawText,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for part in text.parts_with_options(options) {
+        text_element_to_html_with_options(out, emitter, options, part)?;
     }
     Ok(())
 }
 
-#[allow(clippy::match_same_arms)]
 pub fn text_element_to_html(
     out: &mut impl Write,
     emitter: &mut impl FeatureEmitter,
     item: MarkdownTextElement,
+) -> Result<(), Box<dyn std::error::Error>> {
+    text_element_to_html_with_options(out, emitter, &ParseOptions::default(), item)
+}
+
+/// [`text_element_to_html`] parsing link text with `options`
+#[allow(clippy::match_same_arms)]
+pub fn text_element_to_html_with_options(
+    out: &mut impl Write,
+    emitter: &mut impl FeatureEmitter,
+    options: &ParseOptions,
+    item: MarkdownTextElement,
 ) -> Result<(), Box<dyn std::error::Error>> {
     match item {
         MarkdownTextElement::Plain(content) => write!(out, "{content}")?,
@@ -280,8 +640,13 @@ pub fn text_element_to_html(
         }
         MarkdownTextElement::Code(content) => write!(out, "<code>{content}</code>")?,
         MarkdownTextElement::StrikeThrough(content) => write!(out, "{content}")?,
-        MarkdownTextElement::Emoji(content) => write!(out, "{content}")?,
-        MarkdownTextElement::Latex(content) => write!(out, "{content}")?,
+        MarkdownTextElement::Emoji(content) => match crate::emoji::shortcode_to_unicode(content) {
+            Some(emoji) => write!(out, "{emoji}")?,
+            None => write!(out, ":{content}:")?,
+        },
+        MarkdownTextElement::Latex(content) | MarkdownTextElement::DisplayLatex(content) => {
+            write!(out, "{content}")?;
+        }
         MarkdownTextElement::Highlight(content) => write!(out, "{content}")?,
         MarkdownTextElement::Subscript(content) => write!(out, "{content}")?,
         MarkdownTextElement::Superscript(content) => write!(out, "{content}")?,
@@ -293,12 +658,27 @@ pub fn text_element_to_html(
         MarkdownTextElement::Expression(item) => {
             write!(out, "{result}", result = emitter.interpolation(item))?;
         }
+        MarkdownTextElement::Command(command) => {
+            let result = match command.arguments_with_options(options) {
+                Ok(arguments) => emitter.command(command.name, &arguments, command.inner.0),
+                Err(error) => emitter.malformed_command(
+                    command.name,
+                    command.arguments,
+                    &error,
+                    command.inner.0,
+                ),
+            };
+            write!(out, "{result}")?;
+        }
         MarkdownTextElement::Link { on, to } => {
             write!(out, "<a href=\"{to}\">")?;
-            inner_to_html(out, emitter, on)?;
+            inner_to_html_with_options(out, emitter, options, on)?;
             write!(out, "</a>")?;
         }
-    };
+        MarkdownTextElement::Custom(custom) => {
+            write!(out, "{result}", result = emitter.custom_inline(&custom))?;
+        }
+    }
 
     Ok(())
 }
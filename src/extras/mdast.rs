@@ -0,0 +1,585 @@
+//! [mdast](https://github.com/syntax-tree/mdast) JSON, for remark and other unified tooling.
+//! Built from the parser rather than [`crate::document`] so that nodes have positions
+//!
+//! Positions follow unist: lines and columns start at 1 and offsets at 0. Columns and offsets
+//! count UTF-16 code units, as JavaScript indexes strings. Syntax without an mdast node uses the
+//! nodes of common mdast extensions (`math`, `inlineMath`, `yaml`, directives for commands and
+//! `mdxTextExpression`), otherwise a `type` of its own (`highlight`, `tag`, `comment` and the
+//! names of [`crate::extensions`])
+//!
+//! ```
+//! use simple_markdown_parser::{extras::mdast::to_mdast, ParseOptions};
+//!
+//! let root = to_mdast("# Title\n\nSome *text*", &ParseOptions::default()).unwrap();
+//! assert_eq!(root["children"][0]["type"], "heading");
+//! assert_eq!(root["children"][1]["children"][1]["type"], "emphasis");
+//! assert_eq!(root["children"][1]["position"]["start"]["line"], 3);
+//! ```
+
+use crate::{
+    arguments::{ArgumentValue, CommandArguments},
+    document::dedented_lines,
+    parse_with_positions, Alignment, MarkdownElement, MarkdownTextElement, ParseError,
+    ParseOptions, PartsIterator,
+};
+use serde_json::{json, Map, Value};
+use std::ops::Range;
+
+/// `root` node of `source`
+/// # Errors
+/// errors from markdown parsing (including of command and list item contents)
+pub fn to_mdast(source: &str, options: &ParseOptions) -> Result<Value, ParseError> {
+    let converter = Converter {
+        options: options.clone(),
+        locator: Locator::new(source),
+    };
+    let children = converter.blocks(&Text::new(source))?;
+    Ok(json!({
+        "type": "root",
+        "children": children,
+        "position": converter.locator.position(0..source.len()),
+    }))
+}
+
+/// Converts byte offsets in the source to unist points
+struct Locator {
+    /// Byte offsets of the start of each line
+    line_starts: Vec<usize>,
+    /// For each character that is more UTF-8 bytes than UTF-16 code units, the byte offset after
+    /// it and the total difference up to there. So offsets are found without counting
+    multibyte: Vec<(usize, usize)>,
+}
+
+impl Locator {
+    fn new(source: &str) -> Self {
+        let mut line_starts = vec![0];
+        let mut multibyte = Vec::new();
+        let mut difference = 0;
+        for (idx, chr) in source.char_indices() {
+            if chr == '\n' {
+                line_starts.push(idx + 1);
+            } else if chr.len_utf8() > chr.len_utf16() {
+                difference += chr.len_utf8() - chr.len_utf16();
+                multibyte.push((idx + chr.len_utf8(), difference));
+            }
+        }
+        Self {
+            line_starts,
+            multibyte,
+        }
+    }
+
+    fn utf16_offset(&self, offset: usize) -> usize {
+        let before = self.multibyte.partition_point(|(end, _)| *end <= offset);
+        let difference = before.checked_sub(1).map_or(0, |idx| self.multibyte[idx].1);
+        offset - difference
+    }
+
+    fn point(&self, offset: usize) -> Value {
+        let line = self
+            .line_starts
+            .partition_point(|start| *start <= offset)
+            .saturating_sub(1);
+        let utf16_offset = self.utf16_offset(offset);
+        let column = utf16_offset - self.utf16_offset(self.line_starts[line]);
+        json!({
+            "line": line + 1,
+            "column": column + 1,
+            "offset": utf16_offset,
+        })
+    }
+
+    fn position(&self, range: Range<usize>) -> Value {
+        json!({ "start": self.point(range.start), "end": self.point(range.end) })
+    }
+}
+
+/// Markdown being converted, which may be made of pieces of the source (such as dedented list
+/// item contents)
+struct Text<'a> {
+    on: &'a str,
+    /// Starts of the pieces in `on` and their offsets in the source, in order
+    pieces: Vec<(usize, usize)>,
+}
+
+impl<'a> Text<'a> {
+    fn new(on: &'a str) -> Self {
+        Self {
+            on,
+            pieces: vec![(0, 0)],
+        }
+    }
+
+    /// Offset in the source of the start of a range in `on`
+    fn start(&self, local: usize) -> usize {
+        let piece = self.pieces.partition_point(|(start, _)| *start <= local);
+        let (start, offset) = self.pieces[piece.saturating_sub(1)];
+        offset + local - start
+    }
+
+    /// Offset in the source of the end of a range in `on`, which is in the piece before the
+    /// one starting at `local`
+    fn end(&self, local: usize) -> usize {
+        let piece = self.pieces.partition_point(|(start, _)| *start < local);
+        let (start, offset) = self.pieces[piece.saturating_sub(1)];
+        offset + local - start
+    }
+
+    fn range(&self, range: Range<usize>) -> Range<usize> {
+        self.start(range.start)..self.end(range.end)
+    }
+
+    /// Range in the source of a slice of `on`
+    fn range_of(&self, slice: &str) -> Option<Range<usize>> {
+        let start = offset_in(self.on, slice)?;
+        Some(self.range(start..(start + slice.len())))
+    }
+
+    /// For a slice of `on`
+    fn slice(&self, slice: &'a str) -> Self {
+        let start = offset_in(self.on, slice).unwrap_or_default();
+        let end = start + slice.len();
+        let pieces = std::iter::once((0, self.start(start)))
+            .chain(
+                self.pieces
+                    .iter()
+                    .filter(|(piece, _)| start < *piece && *piece < end)
+                    .map(|(piece, offset)| (piece - start, *offset)),
+            )
+            .collect();
+        Self { on: slice, pieces }
+    }
+}
+
+/// Of a slice in `on`
+fn offset_in(on: &str, slice: &str) -> Option<usize> {
+    (slice.as_ptr() as usize)
+        .checked_sub(on.as_ptr() as usize)
+        .filter(|offset| offset + slice.len() <= on.len())
+}
+
+struct Converter {
+    options: ParseOptions,
+    locator: Locator,
+}
+
+/// List with items yet to be added
+struct OpenList {
+    number: Option<u32>,
+    loose: bool,
+    /// Children and ranges in the source
+    items: Vec<(Vec<Value>, Range<usize>)>,
+}
+
+/// Consecutive quote lines
+struct OpenQuote<'a> {
+    callout: Option<&'a str>,
+    children: Vec<Value>,
+    /// In the text
+    range: Range<usize>,
+}
+
+impl Converter {
+    fn position(&self, range: Range<usize>) -> Value {
+        self.locator.position(range)
+    }
+
+    #[allow(clippy::too_many_lines)]
+    fn blocks(&self, text: &Text) -> Result<Vec<Value>, ParseError> {
+        let mut elements = Vec::new();
+        parse_with_positions(text.on, &self.options, |element, position| {
+            elements.push((element, position));
+        })?;
+
+        let mut nodes = Vec::new();
+        let mut lists: Vec<OpenList> = Vec::new();
+        let mut quote: Option<OpenQuote> = None;
+
+        for (element, position) in elements {
+            if !matches!(element, MarkdownElement::ListItem { .. }) {
+                while let Some(list) = lists.pop() {
+                    self.close_list(list, &mut lists, &mut nodes);
+                }
+            }
+            // Quote lines continue the previous ones on the line before
+            let continues_quote = matches!(element, MarkdownElement::Quote(_))
+                && quote.as_ref().is_some_and(|quote| {
+                    matches!(
+                        text.on.get(quote.range.end..position.start),
+                        Some("\n" | "\r\n")
+                    )
+                });
+            if !continues_quote {
+                if let Some(quote) = quote.take() {
+                    nodes.push(self.quote(text, quote));
+                }
+            }
+
+            let node = match element {
+                MarkdownElement::Heading {
+                    level,
+                    text: content,
+                } => json!({
+                    "type": "heading",
+                    "depth": level.min(6),
+                    "children": self.inlines(&text.slice(content.0)),
+                }),
+                MarkdownElement::Quote(inner) => {
+                    let children = self.blocks(&text.slice(inner.0))?;
+                    let quote = quote.get_or_insert(OpenQuote {
+                        callout: None,
+                        children: Vec::new(),
+                        range: position.clone(),
+                    });
+                    quote.children.extend(children);
+                    quote.range.end = position.end;
+                    continue;
+                }
+                MarkdownElement::Callout { kind, title } => {
+                    let mut children = Vec::new();
+                    if let Some(range) = text.range_of(title.0).filter(|_| !title.0.is_empty()) {
+                        children.push(json!({
+                            "type": "paragraph",
+                            "children": self.inlines(&text.slice(title.0)),
+                            "position": self.position(range),
+                        }));
+                    }
+                    quote = Some(OpenQuote {
+                        callout: Some(kind),
+                        children,
+                        range: position,
+                    });
+                    continue;
+                }
+                MarkdownElement::ListItem {
+                    level,
+                    number,
+                    text: content,
+                    children,
+                    loose,
+                    continues,
+                } => {
+                    // Parser levels only increase by one at a time
+                    let depth = (usize::from(level) + 1).min(lists.len() + 1);
+                    if continues {
+                        while lists.len() > depth {
+                            if let Some(list) = lists.pop() {
+                                self.close_list(list, &mut lists, &mut nodes);
+                            }
+                        }
+                        let children = self.blocks(&dedented(&text.slice(children.0)).as_text())?;
+                        let end = text.range(position).end;
+                        if let Some((item, range)) =
+                            lists.last_mut().and_then(|list| list.items.last_mut())
+                        {
+                            item.extend(children);
+                            range.end = end;
+                        }
+                        continue;
+                    }
+                    while lists.len() > depth
+                        || lists.len() == depth
+                            && lists
+                                .last()
+                                .is_some_and(|list| list.number.is_some() != number.is_some())
+                    {
+                        if let Some(list) = lists.pop() {
+                            self.close_list(list, &mut lists, &mut nodes);
+                        }
+                    }
+                    if lists.len() < depth {
+                        lists.push(OpenList {
+                            number,
+                            loose,
+                            items: Vec::new(),
+                        });
+                    }
+
+                    let mut item = Vec::new();
+                    if let Some(range) = text.range_of(content.0).filter(|_| !content.0.is_empty())
+                    {
+                        item.push(json!({
+                            "type": "paragraph",
+                            "children": self.inlines(&text.slice(content.0)),
+                            "position": self.position(range),
+                        }));
+                    }
+                    item.extend(self.blocks(&dedented(&text.slice(children.0)).as_text())?);
+                    if let Some(list) = lists.last_mut() {
+                        list.items.push((item, text.range(position)));
+                    }
+                    continue;
+                }
+                MarkdownElement::Paragraph(content) => json!({
+                    "type": "paragraph",
+                    "children": self.inlines(&text.slice(content.0)),
+                }),
+                MarkdownElement::Table(table) => {
+                    let align: Vec<_> = table
+                        .alignments()
+                        .map(|alignment| match alignment {
+                            Some(Alignment::Left) => json!("left"),
+                            Some(Alignment::Center) => json!("center"),
+                            Some(Alignment::Right) => json!("right"),
+                            None => Value::Null,
+                        })
+                        .collect();
+                    let rows: Vec<_> = table
+                        .rows()
+                        .map(|row| {
+                            let cells: Vec<_> = row
+                                .cells()
+                                .map(|cell| {
+                                    let trimmed = cell.0.trim();
+                                    json!({
+                                        "type": "tableCell",
+                                        "children": self.inlines(&text.slice(trimmed)),
+                                        "position": text.range_of(trimmed).map(|range| self.position(range)),
+                                    })
+                                })
+                                .collect();
+                            json!({
+                                "type": "tableRow",
+                                "children": cells,
+                                "position": text.range_of(row.0.trim_end()).map(|range| self.position(range)),
+                            })
+                        })
+                        .collect();
+                    json!({ "type": "table", "align": align, "children": rows })
+                }
+                MarkdownElement::CodeBlock { language, code } => {
+                    let (language, meta) = language
+                        .split_once(char::is_whitespace)
+                        .map_or((language, None), |(language, meta)| {
+                            (language, Some(meta.trim()))
+                        });
+                    json!({
+                        "type": "code",
+                        "lang": (!language.is_empty()).then_some(language),
+                        "meta": meta,
+                        "value": code.lines().collect::<Vec<_>>().join("\n"),
+                    })
+                }
+                MarkdownElement::LaTeXBlock { script } => json!({
+                    "type": "math",
+                    "value": script.trim_end_matches('\n'),
+                }),
+                MarkdownElement::CommandBlock(command) => {
+                    let children = self.blocks(&text.slice(command.inner.0))?;
+                    json!({
+                        "type": if children.is_empty() { "leafDirective" } else { "containerDirective" },
+                        "name": command.name,
+                        "attributes": attributes(command.arguments),
+                        "children": children,
+                    })
+                }
+                MarkdownElement::CommentBlock(comment) => {
+                    json!({ "type": "comment", "value": comment })
+                }
+                MarkdownElement::HTMLElement(html) => json!({ "type": "html", "value": html }),
+                MarkdownElement::Frontmatter(frontmatter) => json!({
+                    "type": "yaml",
+                    "value": frontmatter.trim_end_matches('\n'),
+                }),
+                MarkdownElement::HorizontalRule => json!({ "type": "thematicBreak" }),
+                MarkdownElement::Custom(block) => json!({
+                    "type": block.name,
+                    "arguments": block.data.arguments,
+                    "value": block.data.content,
+                }),
+                MarkdownElement::Footnote | MarkdownElement::Empty => continue,
+            };
+            nodes.push(with_position(node, self.position(text.range(position))));
+        }
+
+        while let Some(list) = lists.pop() {
+            self.close_list(list, &mut lists, &mut nodes);
+        }
+        if let Some(quote) = quote {
+            nodes.push(self.quote(text, quote));
+        }
+        Ok(nodes)
+    }
+
+    /// Adds a list to the last item of its parent, or to `nodes` at the top level
+    fn close_list(&self, list: OpenList, parents: &mut [OpenList], nodes: &mut Vec<Value>) {
+        let range = list
+            .items
+            .first()
+            .zip(list.items.last())
+            .map_or(0..0, |(first, last)| first.1.start..last.1.end);
+        let items: Vec<_> = list
+            .items
+            .into_iter()
+            .map(|(children, range)| {
+                json!({
+                    "type": "listItem",
+                    "spread": list.loose,
+                    "checked": null,
+                    "children": children,
+                    "position": self.position(range),
+                })
+            })
+            .collect();
+        let node = json!({
+            "type": "list",
+            "ordered": list.number.is_some(),
+            "start": list.number,
+            "spread": list.loose,
+            "children": items,
+            "position": self.position(range.clone()),
+        });
+        match parents
+            .last_mut()
+            .and_then(|parent| parent.items.last_mut())
+        {
+            Some((children, parent_range)) => {
+                children.push(node);
+                parent_range.end = parent_range.end.max(range.end);
+            }
+            None => nodes.push(node),
+        }
+    }
+
+    fn quote(&self, text: &Text, quote: OpenQuote) -> Value {
+        let mut node = json!({
+            "type": "blockquote",
+            "children": quote.children,
+            "position": self.position(text.range(quote.range)),
+        });
+        if let Some(kind) = quote.callout {
+            node["data"] = json!({ "callout": kind });
+        }
+        node
+    }
+
+    fn inlines(&self, text: &Text) -> Vec<Value> {
+        let mut nodes = Vec::new();
+        let mut parts = PartsIterator::with_options(text.on, &self.options);
+        // Opening delimiters are consumed with the text before them, so parts start where the
+        // previous one ended
+        let mut start = 0;
+        while let Some(part) = parts.next() {
+            let end = match part {
+                MarkdownTextElement::Plain(plain) => {
+                    offset_in(text.on, plain).map_or(parts.last, |offset| offset + plain.len())
+                }
+                _ => parts.last,
+            };
+            let literal = |kind: &str, value: &str| json!({ "type": kind, "value": value });
+            let wrapping = |kind: &str, value: &str| {
+                json!({
+                    "type": kind,
+                    "children": [with_position(
+                        literal("text", value),
+                        text.range_of(value).map(|range| self.position(range)).into(),
+                    )],
+                })
+            };
+            let node = match part {
+                MarkdownTextElement::Plain(value) => literal("text", value),
+                MarkdownTextElement::Italic(value) => wrapping("emphasis", value),
+                MarkdownTextElement::Bold(value) => wrapping("strong", value),
+                MarkdownTextElement::BoldAndItalic(value) => json!({
+                    "type": "emphasis",
+                    "children": [wrapping("strong", value)],
+                }),
+                MarkdownTextElement::Code(value) => literal("inlineCode", value),
+                MarkdownTextElement::StrikeThrough(value) => wrapping("delete", value),
+                MarkdownTextElement::Emoji(_) => literal("text", part.no_decoration()),
+                MarkdownTextElement::Latex(value) | MarkdownTextElement::DisplayLatex(value) => {
+                    literal("inlineMath", value)
+                }
+                MarkdownTextElement::Expression(value) => literal("mdxTextExpression", value),
+                MarkdownTextElement::Highlight(value) => wrapping("highlight", value),
+                MarkdownTextElement::Superscript(value) => wrapping("superscript", value),
+                MarkdownTextElement::Subscript(value) => wrapping("subscript", value),
+                MarkdownTextElement::Tag(value) => literal("tag", value),
+                MarkdownTextElement::Link { on, to } => json!({
+                    "type": "link",
+                    "url": to,
+                    "title": null,
+                    "children": self.inlines(&text.slice(on.0)),
+                }),
+                MarkdownTextElement::Media { alt, source } => json!({
+                    "type": "image",
+                    "url": source,
+                    "title": null,
+                    "alt": alt,
+                }),
+                MarkdownTextElement::Command(command) => json!({
+                    "type": "textDirective",
+                    "name": command.name,
+                    "attributes": attributes(command.arguments),
+                    "children": [],
+                }),
+                MarkdownTextElement::Custom(custom) => literal(custom.name, custom.content),
+            };
+            nodes.push(with_position(node, self.position(text.range(start..end))));
+            start = end;
+        }
+        nodes
+    }
+}
+
+fn with_position(mut node: Value, position: Value) -> Value {
+    node["position"] = position;
+    node
+}
+
+/// List item contents without their indentation, keeping the offsets of each line
+struct Dedented {
+    on: String,
+    pieces: Vec<(usize, usize)>,
+}
+
+impl Dedented {
+    fn as_text(&self) -> Text<'_> {
+        Text {
+            on: &self.on,
+            pieces: if self.pieces.is_empty() {
+                vec![(0, 0)]
+            } else {
+                self.pieces.clone()
+            },
+        }
+    }
+}
+
+fn dedented(text: &Text) -> Dedented {
+    let mut dedented = Dedented {
+        on: String::new(),
+        pieces: Vec::new(),
+    };
+    let mut line_start = 0;
+    for (line, dedented_line) in text.on.split_inclusive('\n').zip(dedented_lines(text.on)) {
+        let removed = line.len() - dedented_line.len();
+        dedented
+            .pieces
+            .push((dedented.on.len(), text.start(line_start + removed)));
+        dedented.on.push_str(dedented_line);
+        line_start += line.len();
+    }
+    dedented
+}
+
+/// Directive attributes from the named and flag arguments of a command
+fn attributes(arguments: &str) -> Map<String, Value> {
+    let Ok(arguments) = CommandArguments::parse(arguments) else {
+        return Map::new();
+    };
+    let named = arguments.named.iter().filter_map(|(key, value)| {
+        let value = match value {
+            ArgumentValue::String(value) => value.to_string(),
+            ArgumentValue::Number(value) => value.to_string(),
+            ArgumentValue::Boolean(value) => value.to_string(),
+            ArgumentValue::List(_) => return None,
+        };
+        Some(((*key).to_owned(), Value::String(value)))
+    });
+    let flags = arguments
+        .flags
+        .iter()
+        .map(|flag| ((*flag).to_owned(), Value::String(String::new())));
+    named.chain(flags).collect()
+}
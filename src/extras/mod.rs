@@ -1 +1,5 @@
 pub mod emit;
+#[cfg(feature = "serde")]
+pub mod mdast;
+#[cfg(feature = "serde")]
+pub mod pandoc;
@@ -0,0 +1,551 @@
+//! [Pandoc](https://pandoc.org) JSON AST (the `pandoc-types` schema), so that `pandoc -f json`
+//! converts documents as this parser reads them rather than parsing the source itself
+//!
+//! - Headings get identifiers from a trailing `{#id .class key=value}` or from their text (as
+//!   Pandoc's `auto_identifiers`)
+//! - `[^label]` references to `[^label]: text` paragraphs become notes
+//! - `{% commands %}` become `Div`s (and `Span`s in text) with the name and flags as classes
+//!   and named arguments as attributes
+//! - Callouts are `Div`s as Pandoc's `alerts`, containing the quote after them
+//! - Comments and frontmatter are left out
+//!
+//! ```
+//! use simple_markdown_parser::{extras::pandoc::markdown_to_pandoc, ParseOptions};
+//!
+//! let pandoc = markdown_to_pandoc("# Title {#intro}\n\nText", &ParseOptions::default()).unwrap();
+//! assert_eq!(pandoc["blocks"][0]["t"], "Header");
+//! assert_eq!(pandoc["blocks"][0]["c"][1][0], "intro");
+//! ```
+
+use crate::{
+    arguments::{ArgumentValue, CommandArguments},
+    document::{Block, Command, Document, Heading, Inline, List, Table},
+    Alignment, MarkdownTextElement, ParseError, ParseOptions,
+};
+use serde_json::{json, Map, Value};
+use std::collections::{HashMap, HashSet};
+
+/// Of the `pandoc-types` schema the output follows
+pub const API_VERSION: [u32; 3] = [1, 23, 1];
+
+/// # Errors
+/// errors from markdown parsing (including of command and list item contents)
+pub fn markdown_to_pandoc(source: &str, options: &ParseOptions) -> Result<Value, ParseError> {
+    Document::parse_with_options(source, options).map(|document| document_to_pandoc(&document))
+}
+
+#[must_use]
+pub fn document_to_pandoc(document: &Document) -> Value {
+    let mut converter = Converter::default();
+    converter.collect_notes(&document.children);
+    json!({
+        "pandoc-api-version": API_VERSION,
+        "meta": {},
+        "blocks": converter.blocks(&document.children),
+    })
+}
+
+/// `{"t": tag}` for constructors without fields
+fn node(tag: &str) -> Value {
+    json!({ "t": tag })
+}
+
+/// `{"t": tag, "c": content}`
+fn constructor(tag: &str, content: Value) -> Value {
+    let mut object = Map::new();
+    object.insert("t".to_owned(), Value::from(tag));
+    object.insert("c".to_owned(), content);
+    Value::Object(object)
+}
+
+/// Identifier, classes and key value pairs
+type Attributes = (String, Vec<String>, Vec<(String, String)>);
+
+/// `[identifier, classes, key value pairs]`
+fn attributes(identifier: &str, classes: &[String], pairs: &[(String, String)]) -> Value {
+    json!([identifier, classes, pairs])
+}
+
+fn no_attributes() -> Value {
+    attributes("", &[], &[])
+}
+
+/// Classes and key value pairs for commands. Positional arguments are left out
+fn command_attributes(name: &str, arguments: &str) -> Value {
+    let mut classes = vec![name.to_owned()];
+    let mut pairs = Vec::new();
+    if let Ok(arguments) = CommandArguments::parse(arguments) {
+        classes.extend(arguments.flags.iter().map(|flag| (*flag).to_owned()));
+        for (key, value) in &arguments.named {
+            let value = match value {
+                ArgumentValue::String(value) => value.to_string(),
+                ArgumentValue::Number(value) => value.to_string(),
+                ArgumentValue::Boolean(value) => value.to_string(),
+                ArgumentValue::List(_) => continue,
+            };
+            pairs.push(((*key).to_owned(), value));
+        }
+    }
+    attributes("", &classes, &pairs)
+}
+
+/// Label of a `[^label]` reference
+fn note_label(inline: &Inline) -> Option<&str> {
+    if let Inline::Link { content, to } = inline {
+        if let ([Inline::Plain(text)], "") = (content.as_slice(), to.as_str()) {
+            return text.strip_prefix('^');
+        }
+    }
+    None
+}
+
+/// Label and content of a `[^label]: content` paragraph
+fn note_definition(paragraph: &[Inline]) -> Option<(&str, Vec<Inline>)> {
+    let [reference, Inline::Plain(after), rest @ ..] = paragraph else {
+        return None;
+    };
+    let label = note_label(reference)?;
+    let after = after.strip_prefix(':')?.trim_start();
+    let mut content = Vec::new();
+    if !after.is_empty() {
+        content.push(Inline::Plain(after.to_owned()));
+    }
+    content.extend_from_slice(rest);
+    Some((label, content))
+}
+
+/// `{#id .class key=value}` heading attributes, from the content of an expression
+fn heading_attributes(expression: &str) -> Option<Attributes> {
+    let mut identifier = String::new();
+    let mut classes = Vec::new();
+    let mut pairs = Vec::new();
+    for item in expression.split_whitespace() {
+        if let Some(id) = item.strip_prefix('#') {
+            id.clone_into(&mut identifier);
+        } else if let Some(class) = item.strip_prefix('.') {
+            classes.push(class.to_owned());
+        } else if let Some((key, value)) = item.split_once('=') {
+            pairs.push((key.to_owned(), value.trim_matches('"').to_owned()));
+        } else {
+            return None;
+        }
+    }
+    Some((identifier, classes, pairs))
+}
+
+/// As Pandoc's `auto_identifiers`
+fn identifier(text: &str) -> String {
+    let identifier: String = text
+        .chars()
+        .filter_map(|chr| {
+            if chr.is_whitespace() {
+                Some('-')
+            } else if chr.is_alphanumeric() || matches!(chr, '_' | '-' | '.') {
+                Some(chr)
+            } else {
+                None
+            }
+        })
+        .flat_map(char::to_lowercase)
+        .skip_while(|chr| !chr.is_alphabetic())
+        .collect();
+    if identifier.is_empty() {
+        "section".to_owned()
+    } else {
+        identifier
+    }
+}
+
+/// Callout waiting for the quote after it
+struct OpenCallout {
+    attributes: Value,
+    children: Vec<Value>,
+}
+
+impl OpenCallout {
+    fn finish(self) -> Value {
+        constructor("Div", json!([self.attributes, self.children]))
+    }
+}
+
+#[derive(Default)]
+struct Converter {
+    /// Content of `[^label]: content` paragraphs
+    notes: HashMap<String, Vec<Inline>>,
+    /// Heading identifiers so far, which are made unique as in Pandoc
+    identifiers: HashSet<String>,
+    /// Whether a note is being expanded. Pandoc notes cannot nest, so references in them (which
+    /// may be to themselves) are left as text
+    expanding: bool,
+}
+
+impl Converter {
+    fn collect_notes(&mut self, blocks: &[Block]) {
+        for block in blocks {
+            match block {
+                Block::Paragraph(content) => {
+                    if let Some((label, content)) = note_definition(content) {
+                        self.notes.insert(label.to_owned(), content);
+                    }
+                }
+                Block::Section(section) => self.collect_notes(&section.children),
+                Block::Quote(children) | Block::Command(Command { children, .. }) => {
+                    self.collect_notes(children);
+                }
+                Block::List(list) => {
+                    for item in &list.items {
+                        self.collect_notes(&item.children);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn blocks(&mut self, blocks: &[Block]) -> Vec<Value> {
+        let mut out = Vec::new();
+        self.extend_blocks(blocks, &mut out);
+        out
+    }
+
+    #[allow(clippy::too_many_lines)]
+    fn extend_blocks(&mut self, blocks: &[Block], out: &mut Vec<Value>) {
+        // The quote after a callout is its content
+        let mut callout: Option<OpenCallout> = None;
+        for block in blocks {
+            if let Block::Quote(children) = block {
+                let children = self.blocks(children);
+                out.push(match callout.take() {
+                    Some(mut callout) => {
+                        callout.children.extend(children);
+                        callout.finish()
+                    }
+                    None => constructor("BlockQuote", json!(children)),
+                });
+                continue;
+            }
+            out.extend(callout.take().map(OpenCallout::finish));
+
+            match block {
+                Block::Section(section) => {
+                    out.push(self.heading(&section.heading));
+                    self.extend_blocks(&section.children, out);
+                }
+                Block::Paragraph(content) => {
+                    if note_definition(content).is_none() {
+                        out.push(constructor("Para", json!(self.inlines(content))));
+                    }
+                }
+                Block::Callout { kind, title } => {
+                    let kind = kind.to_lowercase();
+                    let title = if title.is_empty() {
+                        let mut chars = kind.chars();
+                        let capitalised: String = chars
+                            .next()
+                            .map(|first| first.to_uppercase().chain(chars).collect())
+                            .unwrap_or_default();
+                        self.inlines(&[Inline::Plain(capitalised)])
+                    } else {
+                        self.inlines(title)
+                    };
+                    let title = constructor(
+                        "Div",
+                        json!([
+                            attributes("", &["title".to_owned()], &[]),
+                            [constructor("Para", json!(title))]
+                        ]),
+                    );
+                    callout = Some(OpenCallout {
+                        attributes: attributes("", &[kind], &[]),
+                        children: vec![title],
+                    });
+                }
+                Block::List(list) => out.push(self.list(list)),
+                Block::Table(table) => out.push(self.table(table)),
+                Block::CodeBlock { language, code } => {
+                    let mut words = language.split_whitespace();
+                    let mut classes: Vec<String> =
+                        words.next().map(str::to_owned).into_iter().collect();
+                    let mut pairs = Vec::new();
+                    for word in words {
+                        match word.split_once('=') {
+                            Some((key, value)) => {
+                                pairs.push((key.to_owned(), value.trim_matches('"').to_owned()));
+                            }
+                            None => classes.push(word.to_owned()),
+                        }
+                    }
+                    out.push(constructor(
+                        "CodeBlock",
+                        json!([
+                            attributes("", &classes, &pairs),
+                            code.strip_suffix('\n').unwrap_or(code)
+                        ]),
+                    ));
+                }
+                Block::LaTeXBlock { script } => out.push(constructor(
+                    "Para",
+                    json!([constructor(
+                        "Math",
+                        json!([node("DisplayMath"), script.trim()])
+                    )]),
+                )),
+                Block::Command(command) => out.push(constructor(
+                    "Div",
+                    json!([
+                        command_attributes(&command.name, &command.arguments),
+                        self.blocks(&command.children)
+                    ]),
+                )),
+                Block::HTMLElement(html) => {
+                    out.push(constructor("RawBlock", json!(["html", html])));
+                }
+                Block::HorizontalRule => out.push(node("HorizontalRule")),
+                Block::Custom {
+                    name,
+                    arguments,
+                    content,
+                    ..
+                } => out.push(constructor(
+                    "Div",
+                    json!([
+                        attributes(
+                            "",
+                            std::slice::from_ref(name),
+                            &[("arguments".to_owned(), arguments.clone())]
+                        ),
+                        [constructor(
+                            "Plain",
+                            json!(self.inlines(&[Inline::Plain(content.trim_end().to_owned())]))
+                        )]
+                    ]),
+                )),
+                // Quotes are added above
+                Block::Quote(_) | Block::Comment(_) | Block::Frontmatter(_) | Block::Footnote => {}
+            }
+        }
+        out.extend(callout.map(OpenCallout::finish));
+    }
+
+    fn heading(&mut self, heading: &Heading) -> Value {
+        let mut content = heading.content.as_slice();
+        let mut identifier = String::new();
+        let mut classes = Vec::new();
+        let mut pairs = Vec::new();
+        if let [before @ .., Inline::Expression(expression)] = content {
+            if let Some(attributes) = heading_attributes(expression) {
+                (identifier, classes, pairs) = attributes;
+                content = before;
+            }
+        }
+        if identifier.is_empty() {
+            let text: String = content.iter().map(Inline::text).collect();
+            let base = self::identifier(&text);
+            identifier.clone_from(&base);
+            let mut count = 0;
+            while self.identifiers.contains(&identifier) {
+                count += 1;
+                identifier = format!("{base}-{count}");
+            }
+        }
+        self.identifiers.insert(identifier.clone());
+        constructor(
+            "Header",
+            json!([
+                heading.level,
+                attributes(&identifier, &classes, &pairs),
+                self.inlines(content)
+            ]),
+        )
+    }
+
+    fn list(&mut self, list: &List) -> Value {
+        let items: Vec<_> = list
+            .items
+            .iter()
+            .map(|item| {
+                let mut blocks = Vec::new();
+                if !item.content.is_empty() {
+                    let tag = if list.loose { "Para" } else { "Plain" };
+                    blocks.push(constructor(tag, json!(self.inlines(&item.content))));
+                }
+                self.extend_blocks(&item.children, &mut blocks);
+                blocks
+            })
+            .collect();
+        match list.start {
+            Some(start) => constructor(
+                "OrderedList",
+                json!([[start, node("Decimal"), node("Period")], items]),
+            ),
+            None => constructor("BulletList", json!(items)),
+        }
+    }
+
+    fn table(&mut self, table: &Table) -> Value {
+        let columns = table.header.len();
+        let alignment = |column: usize| {
+            node(match table.alignments.get(column).copied().flatten() {
+                None => "AlignDefault",
+                Some(Alignment::Left) => "AlignLeft",
+                Some(Alignment::Center) => "AlignCenter",
+                Some(Alignment::Right) => "AlignRight",
+            })
+        };
+        let column_specifications: Vec<_> = (0..columns)
+            .map(|column| json!([alignment(column), node("ColWidthDefault")]))
+            .collect();
+        let mut row = |cells: &[Vec<Inline>]| {
+            let cells: Vec<_> = cells
+                .iter()
+                .take(columns)
+                .map(|cell| {
+                    json!([
+                        no_attributes(),
+                        node("AlignDefault"),
+                        1,
+                        1,
+                        [constructor("Plain", json!(self.inlines(cell)))]
+                    ])
+                })
+                .collect();
+            json!([no_attributes(), cells])
+        };
+        let head = json!([no_attributes(), [row(&table.header)]]);
+        let body: Vec<_> = table.rows.iter().map(|cells| row(cells)).collect();
+        constructor(
+            "Table",
+            json!([
+                no_attributes(),
+                [null, []],
+                column_specifications,
+                head,
+                [[no_attributes(), 0, [], body]],
+                [no_attributes(), []]
+            ]),
+        )
+    }
+
+    fn inlines(&mut self, inlines: &[Inline]) -> Vec<Value> {
+        let mut out = Vec::new();
+        for inline in inlines {
+            self.inline(inline, &mut out);
+        }
+        // Pandoc has no space at the start or end of a block
+        while out.last().is_some_and(is_space) {
+            out.pop();
+        }
+        let leading = out.iter().take_while(|inline| is_space(inline)).count();
+        out.drain(..leading);
+        out
+    }
+
+    fn inline(&mut self, inline: &Inline, out: &mut Vec<Value>) {
+        let wrapped = |tag: &str, text: &str| {
+            let mut inner = Vec::new();
+            words(text, &mut inner);
+            constructor(tag, json!(inner))
+        };
+        let span = |classes: &[String], text: &str| {
+            let mut inner = Vec::new();
+            words(text, &mut inner);
+            constructor("Span", json!([attributes("", classes, &[]), inner]))
+        };
+        let element = match inline {
+            Inline::Plain(text) => {
+                words(text, out);
+                return;
+            }
+            Inline::Italic(text) => wrapped("Emph", text),
+            Inline::Bold(text) => wrapped("Strong", text),
+            Inline::BoldAndItalic(text) => constructor("Emph", json!([wrapped("Strong", text)])),
+            Inline::Code(code) => constructor("Code", json!([no_attributes(), code])),
+            Inline::StrikeThrough(text) => wrapped("Strikeout", text),
+            Inline::Emoji(name) => constructor(
+                "Span",
+                json!([
+                    attributes(
+                        "",
+                        &["emoji".to_owned()],
+                        &[("data-emoji".to_owned(), name.clone())]
+                    ),
+                    [constructor(
+                        "Str",
+                        json!(MarkdownTextElement::Emoji(name).no_decoration())
+                    )]
+                ]),
+            ),
+            Inline::Latex(math) => constructor("Math", json!([node("InlineMath"), math])),
+            Inline::DisplayLatex(math) => constructor("Math", json!([node("DisplayMath"), math])),
+            Inline::Expression(expression) => constructor(
+                "Code",
+                json!([attributes("", &["expression".to_owned()], &[]), expression]),
+            ),
+            Inline::Highlight(text) => span(&["mark".to_owned()], text),
+            Inline::Superscript(text) => wrapped("Superscript", text),
+            Inline::Subscript(text) => wrapped("Subscript", text),
+            Inline::Tag(tag) => span(&["tag".to_owned()], &format!("#{tag}")),
+            Inline::Link { content, to } => {
+                let label = note_label(inline).filter(|label| self.notes.contains_key(*label));
+                if let Some(label) = label {
+                    if self.expanding {
+                        constructor("Str", json!(format!("[^{label}]")))
+                    } else {
+                        let note = self.notes[label].clone();
+                        self.expanding = true;
+                        let content = self.inlines(&note);
+                        self.expanding = false;
+                        constructor("Note", json!([constructor("Para", json!(content))]))
+                    }
+                } else {
+                    constructor(
+                        "Link",
+                        json!([no_attributes(), self.inlines(content), [to, ""]]),
+                    )
+                }
+            }
+            Inline::Media { alt, source } => {
+                let mut description = Vec::new();
+                words(alt, &mut description);
+                constructor("Image", json!([no_attributes(), description, [source, ""]]))
+            }
+            Inline::Command(command) => constructor(
+                "Span",
+                json!([command_attributes(&command.name, &command.arguments), []]),
+            ),
+            Inline::Custom { name, content, .. } => span(std::slice::from_ref(name), content),
+        };
+        out.push(element);
+    }
+}
+
+fn is_space(inline: &Value) -> bool {
+    inline["t"] == "Space" || inline["t"] == "SoftBreak"
+}
+
+/// `Str`s separated by `Space`s and `SoftBreak`s
+fn words(text: &str, out: &mut Vec<Value>) {
+    let mut word = String::new();
+    for chr in text.chars() {
+        if chr.is_whitespace() {
+            if !word.is_empty() {
+                out.push(constructor("Str", json!(std::mem::take(&mut word))));
+            }
+            let separator = if chr == '\n' { "SoftBreak" } else { "Space" };
+            match out.last_mut() {
+                // Line breaks take the place of spaces before them
+                Some(last) if is_space(last) => {
+                    if separator == "SoftBreak" {
+                        *last = node(separator);
+                    }
+                }
+                _ => out.push(node(separator)),
+            }
+        } else {
+            word.push(chr);
+        }
+    }
+    if !word.is_empty() {
+        out.push(constructor("Str", json!(word)));
+    }
+}
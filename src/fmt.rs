@@ -0,0 +1,679 @@
+//! Formatting of markdown source in a consistent style. Comments, frontmatter and commands are kept
+
+use crate::{
+    document::{Block, Command, Document, Inline, List, Section, Table},
+    Alignment, ParseError, ParseOptions,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatOptions {
+    pub bullet: BulletMarker,
+    pub heading_style: HeadingStyle,
+    /// Backticks in code fences. At least three, and longer than any fence in the code
+    pub fence_length: usize,
+    /// For italic text (and bold and italic text)
+    pub emphasis: EmphasisMarker,
+    /// For bold text
+    pub strong: EmphasisMarker,
+    pub ordered_list_delimiter: OrderedListDelimiter,
+    /// Between blocks. Blocks in loose lists always have at least one blank line and blocks in
+    /// tight lists have none
+    pub blank_lines: usize,
+    /// Pad table cells so that columns line up
+    pub align_tables: bool,
+    /// Wrap paragraphs and list items at spaces to fit this many columns. Consecutive lines of text
+    /// are then parsed as one paragraph (as [`ParseOptions::multiline_paragraphs`]) and the
+    /// whitespace in them is collapsed to single spaces before wrapping
+    pub max_width: Option<usize>,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            bullet: BulletMarker::Dash,
+            heading_style: HeadingStyle::Atx,
+            fence_length: 3,
+            emphasis: EmphasisMarker::Asterisk,
+            strong: EmphasisMarker::Asterisk,
+            ordered_list_delimiter: OrderedListDelimiter::Period,
+            blank_lines: 1,
+            align_tables: true,
+            max_width: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BulletMarker {
+    /// `- item`
+    Dash,
+    /// `* item`
+    Asterisk,
+    /// `+ item`
+    Plus,
+}
+
+/// Setext headings (underlined with `=` or `-`) are not parsed, so are not an option
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeadingStyle {
+    /// `## Heading`
+    Atx,
+    /// `## Heading ##`
+    AtxClosed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmphasisMarker {
+    /// `*italic*` and `**bold**`
+    Asterisk,
+    /// `_italic_` and `__bold__`
+    Underscore,
+}
+
+impl EmphasisMarker {
+    fn character(self) -> char {
+        match self {
+            EmphasisMarker::Asterisk => '*',
+            EmphasisMarker::Underscore => '_',
+        }
+    }
+
+    fn other(self) -> Self {
+        match self {
+            EmphasisMarker::Asterisk => EmphasisMarker::Underscore,
+            EmphasisMarker::Underscore => EmphasisMarker::Asterisk,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderedListDelimiter {
+    /// `1.`
+    Period,
+    /// `1)`
+    Parenthesis,
+}
+
+/// # Errors
+/// errors from markdown parsing
+pub fn format(on: &str, options: &FormatOptions) -> Result<String, ParseError> {
+    // Lines of a paragraph stay one paragraph, which is kept as lines unless it is wrapped
+    let parse_options = ParseOptions::default().multiline_paragraphs(true);
+    Document::parse_with_options(on, &parse_options)
+        .map(|document| format_document(&document, options))
+}
+
+#[must_use]
+pub fn format_document(document: &Document, options: &FormatOptions) -> String {
+    let writer = Writer { options };
+    let mut out = String::new();
+    // `---` on the first line would start frontmatter
+    if let Some(Block::HorizontalRule) = document.children.first() {
+        out.push('\n');
+    }
+    writer.blocks(
+        &document.children,
+        &writer.separator(),
+        options.max_width,
+        &mut out,
+    );
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out
+}
+
+struct Writer<'o> {
+    options: &'o FormatOptions,
+}
+
+impl Writer<'_> {
+    fn separator(&self) -> String {
+        "\n".repeat(self.options.blank_lines + 1)
+    }
+
+    /// `separator` goes between blocks. `width` is the columns available to wrap text in
+    fn blocks(&self, blocks: &[Block], separator: &str, width: Option<usize>, out: &mut String) {
+        for (idx, block) in blocks.iter().enumerate() {
+            if idx > 0 {
+                let adjacent_tight_lists = matches!(
+                    (&blocks[idx - 1], block),
+                    (
+                        Block::List(List { loose: false, .. }),
+                        Block::List(List { loose: false, .. })
+                    )
+                );
+                out.push_str(if adjacent_tight_lists {
+                    "\n"
+                } else {
+                    separator
+                });
+            }
+            self.block(block, separator, width, out);
+        }
+    }
+
+    fn block(&self, block: &Block, separator: &str, width: Option<usize>, out: &mut String) {
+        match block {
+            Block::Section(Section { heading, children }) => {
+                let hashes = "#".repeat(heading.level.into());
+                let mut content = String::new();
+                self.inlines(&heading.content, &mut content);
+                out.push_str(&hashes);
+                out.push(' ');
+                out.push_str(&content);
+                // Text ending in ` #` would otherwise be read as a closing sequence
+                let before_closing = content.trim_end_matches('#');
+                let ambiguous = before_closing.is_empty() || before_closing.ends_with(' ');
+                let closed = self.options.heading_style == HeadingStyle::AtxClosed || ambiguous;
+                if closed && !content.is_empty() {
+                    out.push(' ');
+                    out.push_str(&hashes);
+                }
+                if !children.is_empty() {
+                    out.push_str(separator);
+                    self.blocks(children, separator, width, out);
+                }
+            }
+            Block::Paragraph(content) => self.wrapped_inlines(content, width, out),
+            Block::Quote(children) => {
+                let mut inner = String::new();
+                self.blocks(children, separator, None, &mut inner);
+                if inner.is_empty() {
+                    out.push('>');
+                }
+                for (idx, line) in inner.lines().enumerate() {
+                    if idx > 0 {
+                        out.push('\n');
+                    }
+                    out.push('>');
+                    if !line.is_empty() {
+                        out.push(' ');
+                        out.push_str(line);
+                    }
+                }
+            }
+            Block::Callout { kind, title } => {
+                out.push_str("> [!");
+                out.push_str(kind);
+                out.push(']');
+                if !title.is_empty() {
+                    out.push(' ');
+                    self.inlines(title, out);
+                }
+            }
+            Block::List(list) => self.list(list, width, out),
+            Block::Table(table) => self.table(table, out),
+            Block::CodeBlock { language, code } => {
+                // Longer than lines of backticks in the code, which would close it
+                let longest_fence = code
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| line.bytes().all(|byte| byte == b'`'))
+                    .map(str::len)
+                    .max()
+                    .unwrap_or(0);
+                let fence = "`".repeat(self.options.fence_length.max(3).max(longest_fence + 1));
+                out.push_str(&fence);
+                out.push_str(language);
+                out.push('\n');
+                out.push_str(code);
+                if !code.is_empty() && !code.ends_with('\n') {
+                    out.push('\n');
+                }
+                out.push_str(&fence);
+            }
+            Block::LaTeXBlock { script } => {
+                if script.contains('\n') || script.contains("$$") {
+                    out.push_str("$$\n");
+                    out.push_str(script);
+                    out.push_str("\n$$");
+                } else {
+                    surround(out, "$$", script, "$$");
+                }
+            }
+            Block::Command(command) => {
+                command_tag(command, out);
+                if !command.children.is_empty() {
+                    out.push('\n');
+                    self.blocks(&command.children, &self.separator(), width, out);
+                    out.push_str("\n{% /");
+                    out.push_str(&command.name);
+                    out.push_str(" %}");
+                }
+            }
+            Block::Comment(comment) => {
+                if comment.contains('\n') {
+                    surround(out, "%%\n", comment, "\n%%");
+                } else {
+                    surround(out, "%% ", comment, " %%");
+                }
+            }
+            Block::HTMLElement(element) => out.push_str(element),
+            Block::Frontmatter(frontmatter) => surround(out, "---\n", frontmatter, "---"),
+            Block::HorizontalRule => out.push_str("---"),
+            Block::Footnote => {}
+            Block::Custom { source, .. } => out.push_str(source.trim_end()),
+        }
+    }
+
+    fn list(&self, list: &List, width: Option<usize>, out: &mut String) {
+        let separator = if list.loose {
+            "\n".repeat(self.options.blank_lines.max(1) + 1)
+        } else {
+            "\n".to_owned()
+        };
+        let delimiter = match self.options.ordered_list_delimiter {
+            OrderedListDelimiter::Period => '.',
+            OrderedListDelimiter::Parenthesis => ')',
+        };
+        for (idx, item) in list.items.iter().enumerate() {
+            if idx > 0 {
+                out.push_str(&separator);
+            }
+            let marker = match list.start {
+                Some(start) => format!("{number}{delimiter} ", number = start as usize + idx),
+                None => match self.options.bullet {
+                    BulletMarker::Dash => "- ",
+                    BulletMarker::Asterisk => "* ",
+                    BulletMarker::Plus => "+ ",
+                }
+                .to_owned(),
+            };
+            let indentation = " ".repeat(marker.len());
+            let width = width.map(|width| width.saturating_sub(marker.len()));
+
+            if item.content.is_empty() {
+                out.push_str(marker.trim_end());
+            } else {
+                out.push_str(&marker);
+                let mut content = String::new();
+                self.wrapped_inlines(&item.content, width, &mut content);
+                // Wrapped lines are children of the item
+                out.push_str(&content.replace('\n', &format!("\n{indentation}")));
+            }
+
+            if !item.children.is_empty() {
+                out.push_str(&separator);
+                let mut children = String::new();
+                self.blocks(&item.children, &separator, width, &mut children);
+                for (idx, line) in children.split('\n').enumerate() {
+                    if idx > 0 {
+                        out.push('\n');
+                    }
+                    if !line.is_empty() {
+                        out.push_str(&indentation);
+                        out.push_str(line);
+                    }
+                }
+            }
+        }
+    }
+
+    fn table(
+        &self,
+        Table {
+            header,
+            rows,
+            alignments,
+        }: &Table,
+        out: &mut String,
+    ) {
+        let cells = |row: &Vec<Vec<Inline>>| -> Vec<String> {
+            row.iter()
+                .map(|cell| {
+                    let mut markdown = String::new();
+                    self.inlines(cell, &mut markdown);
+                    if self.options.align_tables {
+                        markdown.trim().to_owned()
+                    } else {
+                        markdown
+                    }
+                })
+                .collect()
+        };
+        let header = cells(header);
+        let rows: Vec<_> = rows.iter().map(cells).collect();
+
+        if !self.options.align_tables {
+            for (idx, row) in std::iter::once(&header).chain(&rows).enumerate() {
+                if idx > 0 {
+                    out.push('\n');
+                }
+                out.push('|');
+                for cell in row {
+                    out.push_str(cell);
+                    out.push('|');
+                }
+                if idx == 0 {
+                    out.push_str("\n|");
+                    for column in 0..header.len() {
+                        out.push_str(&separator(alignments.get(column).copied().flatten(), 3));
+                        out.push('|');
+                    }
+                }
+            }
+            return;
+        }
+
+        let mut widths = vec![3; header.len()];
+        for row in std::iter::once(&header).chain(&rows) {
+            for (idx, cell) in row.iter().enumerate() {
+                let cell_width = cell.chars().count();
+                match widths.get_mut(idx) {
+                    Some(width) => *width = (*width).max(cell_width),
+                    None => widths.push(cell_width),
+                }
+            }
+        }
+
+        let write_row = |row: &[String], out: &mut String| {
+            out.push('|');
+            for (cell, width) in row.iter().zip(&widths) {
+                out.push(' ');
+                out.push_str(cell);
+                out.push_str(&" ".repeat(width - cell.chars().count()));
+                out.push_str(" |");
+            }
+        };
+        write_row(&header, out);
+        out.push_str("\n|");
+        for (column, width) in widths.iter().take(header.len()).enumerate() {
+            out.push(' ');
+            out.push_str(&separator(
+                alignments.get(column).copied().flatten(),
+                *width,
+            ));
+            out.push_str(" |");
+        }
+        for row in &rows {
+            out.push('\n');
+            write_row(row, out);
+        }
+    }
+
+    /// Inline markdown, broken at spaces in plain text to fit `width` (when possible)
+    fn wrapped_inlines(&self, inlines: &[Inline], width: Option<usize>, out: &mut String) {
+        let Some(width) = width else {
+            // Lines are kept, without the indentation from the source
+            let mut text = String::new();
+            self.inlines(inlines, &mut text);
+            for (idx, line) in text.split('\n').enumerate() {
+                if idx > 0 {
+                    out.push('\n');
+                }
+                out.push_str(if idx > 0 { line.trim_start() } else { line });
+            }
+            return;
+        };
+
+        let context = InlinesContext::new(inlines);
+        let mut line = String::new();
+        // Positions in `line` of spaces in plain text
+        let mut breaks = Vec::new();
+        for (idx, inline) in inlines.iter().enumerate() {
+            if let Inline::Plain(text) = inline {
+                let text = collapse_whitespace(text);
+                for (offset, chr) in text.char_indices() {
+                    if chr == ' ' {
+                        breaks.push(line.len() + offset);
+                    }
+                }
+                line.push_str(&text);
+            } else {
+                // Line breaks from the source are spaces
+                let mut markdown = String::new();
+                self.inline(&context, idx, &mut markdown);
+                line.push_str(&markdown.replace('\n', " "));
+            }
+        }
+
+        let mut line_start = 0;
+        let mut last_break = None;
+        for position in breaks {
+            let columns = line[line_start..position].chars().count();
+            if columns > width {
+                if let Some(last_break) = last_break.take() {
+                    out.push_str(&line[line_start..last_break]);
+                    out.push('\n');
+                    line_start = last_break + 1;
+                }
+            }
+            // Lines starting with block syntax would not be parsed as text
+            if !starts_block(&line[(position + 1)..]) {
+                last_break = Some(position);
+            }
+        }
+        if line[line_start..].chars().count() > width {
+            if let Some(last_break) = last_break {
+                out.push_str(&line[line_start..last_break]);
+                out.push('\n');
+                line_start = last_break + 1;
+            }
+        }
+        out.push_str(&line[line_start..]);
+    }
+
+    fn inlines(&self, inlines: &[Inline], out: &mut String) {
+        let context = InlinesContext::new(inlines);
+        for idx in 0..inlines.len() {
+            self.inline(&context, idx, out);
+        }
+    }
+
+    /// Writes `context.inlines[idx]`, which can depend on the inlines around it
+    fn inline(&self, context: &InlinesContext, idx: usize, out: &mut String) {
+        let inlines = context.inlines;
+        // There are no escapes, so the other marker is used if there are literal ones or if it
+        // would join the delimiters of the inline before
+        let emphasis = |marker: EmphasisMarker| {
+            if context.literal(marker) || out.ends_with(marker.character()) {
+                marker.other().character()
+            } else {
+                marker.character()
+            }
+        };
+
+        match &inlines[idx] {
+            Inline::Plain(text) => out.push_str(text),
+            Inline::Italic(text) => {
+                // `* ` at the start of a line would be a list item
+                let marker = if text.starts_with(char::is_whitespace) {
+                    EmphasisMarker::Underscore
+                } else {
+                    self.options.emphasis
+                };
+                let delimiter = emphasis(marker).to_string();
+                surround(out, &delimiter, text, &delimiter);
+            }
+            Inline::Bold(text) => {
+                let delimiter = emphasis(self.options.strong).to_string().repeat(2);
+                surround(out, &delimiter, text, &delimiter);
+            }
+            Inline::BoldAndItalic(text) => {
+                let delimiter = emphasis(self.options.strong).to_string().repeat(3);
+                surround(out, &delimiter, text, &delimiter);
+            }
+            Inline::Code(code) => {
+                let longest_run = code
+                    .split(|chr| chr != '`')
+                    .map(str::len)
+                    .max()
+                    .unwrap_or(0);
+                let fence = "`".repeat(longest_run + 1);
+                // One space either side is removed when both are present
+                let padded = code.starts_with('`')
+                    || code.ends_with('`')
+                    || (code.starts_with(' ') && code.ends_with(' ') && code.trim() != "");
+                if padded {
+                    surround(out, &format!("{fence} "), code, &format!(" {fence}"));
+                } else {
+                    surround(out, &fence, code, &fence);
+                }
+            }
+            Inline::StrikeThrough(text) => surround(out, "~~", text, "~~"),
+            Inline::Emoji(name) => surround(out, ":", name, ":"),
+            Inline::Latex(script) => {
+                // `$` cannot open after a digit or close before one
+                let preceding_digit = out
+                    .chars()
+                    .next_back()
+                    .is_some_and(|chr| chr.is_ascii_digit());
+                // Other inlines start with their syntax
+                let following_digit = matches!(
+                    inlines.get(idx + 1),
+                    Some(Inline::Plain(text) | Inline::Custom { source: text, .. })
+                        if text.starts_with(|chr: char| chr.is_ascii_digit())
+                );
+                let dollars = !(script.is_empty()
+                    || script.contains('$')
+                    || script.trim() != script
+                    || preceding_digit
+                    || following_digit);
+                if dollars {
+                    surround(out, "$", script, "$");
+                } else {
+                    surround(out, "\\(", script, "\\)");
+                }
+            }
+            Inline::DisplayLatex(script) => {
+                if script.contains('$') {
+                    surround(out, "\\[", script, "\\]");
+                } else {
+                    surround(out, "$$", script, "$$");
+                }
+            }
+            Inline::Expression(expression) => surround(out, "{", expression, "}"),
+            Inline::Highlight(text) => surround(out, "==", text, "=="),
+            Inline::Superscript(text) => surround(out, "^", text, "^"),
+            Inline::Subscript(text) => surround(out, "~", text, "~"),
+            Inline::Tag(tag) => surround(out, "#", tag, ""),
+            Inline::Link { content, to } => {
+                out.push('[');
+                self.inlines(content, out);
+                // Bracketed text, such as task markers and wikilinks, has no destination
+                if to.is_empty() {
+                    out.push(']');
+                } else {
+                    surround(out, "](", to, ")");
+                }
+            }
+            Inline::Media { alt, source } => {
+                surround(out, "![", alt, "](");
+                surround(out, "", source, ")");
+            }
+            Inline::Command(command) => command_tag(command, out),
+            Inline::Custom { source, .. } => out.push_str(source),
+        }
+    }
+}
+
+/// Inlines being written, with what is found across them computed once
+struct InlinesContext<'i> {
+    inlines: &'i [Inline],
+    /// Whether text outside of emphasis delimiters contains `*` and `_`. The parser pairs
+    /// delimiters across all of the text, including in code and links
+    literal_markers: [bool; 2],
+}
+
+impl<'i> InlinesContext<'i> {
+    fn new(inlines: &'i [Inline]) -> Self {
+        fn contains(inline: &Inline, chr: char) -> bool {
+            match inline {
+                Inline::Link { content, to } => {
+                    to.contains(chr) || content.iter().any(|inline| contains(inline, chr))
+                }
+                Inline::Media { alt, source } => alt.contains(chr) || source.contains(chr),
+                Inline::Command(command) => {
+                    command.name.contains(chr) || command.arguments.contains(chr)
+                }
+                Inline::Custom { source, .. } => source.contains(chr),
+                Inline::Plain(text)
+                | Inline::Italic(text)
+                | Inline::Bold(text)
+                | Inline::BoldAndItalic(text)
+                | Inline::Code(text)
+                | Inline::StrikeThrough(text)
+                | Inline::Emoji(text)
+                | Inline::Latex(text)
+                | Inline::DisplayLatex(text)
+                | Inline::Expression(text)
+                | Inline::Highlight(text)
+                | Inline::Superscript(text)
+                | Inline::Subscript(text)
+                | Inline::Tag(text) => text.contains(chr),
+            }
+        }
+
+        let contains = |chr: char| inlines.iter().any(|inline| contains(inline, chr));
+        Self {
+            inlines,
+            literal_markers: [contains('*'), contains('_')],
+        }
+    }
+
+    fn literal(&self, marker: EmphasisMarker) -> bool {
+        match marker {
+            EmphasisMarker::Asterisk => self.literal_markers[0],
+            EmphasisMarker::Underscore => self.literal_markers[1],
+        }
+    }
+}
+
+/// Opening tag, self closing if there are no children
+fn command_tag(command: &Command, out: &mut String) {
+    out.push_str("{% ");
+    out.push_str(&command.name);
+    if !command.arguments.is_empty() {
+        out.push(' ');
+        out.push_str(&command.arguments);
+    }
+    out.push_str(if command.children.is_empty() {
+        " /%}"
+    } else {
+        " %}"
+    });
+}
+
+/// Whether a line starting with `on` could be something other than a paragraph
+fn starts_block(on: &str) -> bool {
+    let mut chars = on.chars();
+    match chars.next() {
+        // Bullets, rather than emphasis
+        Some('*' | '+') => chars.next().is_none_or(char::is_whitespace),
+        Some(chr) => {
+            chr.is_ascii_digit()
+                || matches!(chr, '#' | '>' | '-' | '|' | '%' | '{' | '$' | '\\' | '`')
+        }
+        None => false,
+    }
+}
+
+/// `text` with each run of whitespace as a single space
+fn collapse_whitespace(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for (idx, word) in text.split(char::is_whitespace).enumerate() {
+        if idx > 0 && !out.ends_with(' ') {
+            out.push(' ');
+        }
+        out.push_str(word);
+    }
+    out
+}
+
+fn surround(out: &mut String, left: &str, inner: &str, right: &str) {
+    out.push_str(left);
+    out.push_str(inner);
+    out.push_str(right);
+}
+
+/// Separator row cell of a column, at least three characters wide
+fn separator(alignment: Option<Alignment>, width: usize) -> String {
+    let dashes = |count: usize| "-".repeat(count);
+    match alignment {
+        None => dashes(width),
+        Some(Alignment::Left) => format!(":{}", dashes(width - 1)),
+        Some(Alignment::Center) => format!(":{}:", dashes(width - 2)),
+        Some(Alignment::Right) => format!("{}:", dashes(width - 1)),
+    }
+}
@@ -0,0 +1,311 @@
+//! Reparsing after edits. Parsing restarts at the first element an edit could change (including
+//! the start of the list it is in) and stops once an element after the edit lines up with the
+//! previous parse, after which the previous elements are reused
+//!
+//! ```
+//! use simple_markdown_parser::incremental::{ParsedElements, TextEdit};
+//! use simple_markdown_parser::ParseOptions;
+//!
+//! let source = "# Title\nSome text\n\nMore text";
+//! let parsed = ParsedElements::parse(source, &ParseOptions::default()).unwrap();
+//!
+//! let edit = TextEdit { range: 13..17, new_text: "words".to_owned() };
+//! let new_source = edit.apply(source);
+//! let (reparsed, change) = parsed.reparse(&new_source, &edit).unwrap();
+//! assert_eq!(change.new, 1..2);
+//! assert_eq!(reparsed.elements()[1].1, 8..18);
+//! ```
+
+use std::ops::{ControlFlow, Range};
+
+use crate::{
+    extensions::{CustomBlock, CustomData},
+    parse_from, parse_with_positions, restarts_parsing, CommandBlock, MarkdownElement, ParseError,
+    ParseErrorReason, ParseOptions, RawCode, RawMarkdown, RawText, Table,
+};
+
+/// Replacement of the bytes in `range` with `new_text`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: Range<usize>,
+    pub new_text: String,
+}
+
+impl TextEdit {
+    /// `source` with the edit applied
+    #[must_use]
+    pub fn apply(&self, source: &str) -> String {
+        let mut result = source.to_owned();
+        result.replace_range(self.range.clone(), &self.new_text);
+        result
+    }
+}
+
+/// Indices of the `old` elements that were replaced by the `new` elements
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElementsChange {
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+}
+
+/// Elements of a source with their byte ranges, as given by [`parse_with_positions`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedElements<'a> {
+    source: &'a str,
+    options: ParseOptions,
+    elements: Vec<(MarkdownElement<'a>, Range<usize>)>,
+}
+
+impl<'a> ParsedElements<'a> {
+    /// # Errors
+    /// errors for unclosed blocks and mismatched command tags
+    pub fn parse(source: &'a str, options: &ParseOptions) -> Result<Self, ParseError> {
+        let mut elements = Vec::new();
+        parse_with_positions(source, options, |element, position| {
+            elements.push((element, position));
+        })?;
+        Ok(Self {
+            source,
+            options: options.clone(),
+            elements,
+        })
+    }
+
+    #[must_use]
+    pub fn source(&self) -> &'a str {
+        self.source
+    }
+
+    #[must_use]
+    pub fn elements(&self) -> &[(MarkdownElement<'a>, Range<usize>)] {
+        &self.elements
+    }
+
+    /// Parses `source`, which is the previous source with `edit` applied, reusing the elements
+    /// the edit cannot have changed. Gives the same elements as parsing `source` from scratch
+    /// # Errors
+    /// errors for unclosed blocks, mismatched command tags and exceeded limits
+    #[allow(clippy::too_many_lines)]
+    pub fn reparse<'b>(
+        &self,
+        source: &'b str,
+        edit: &TextEdit,
+    ) -> Result<(ParsedElements<'b>, ElementsChange), ParseError> {
+        let TextEdit { range, new_text } = edit;
+        let edit_end = range.start + new_text.len();
+        let matches_edit = range.start <= range.end
+            && range.end <= self.source.len()
+            && self.source.len() - range.len() + new_text.len() == source.len()
+            && source.is_char_boundary(range.start)
+            && source.is_char_boundary(edit_end);
+        if !matches_edit {
+            let parsed = ParsedElements::parse(source, &self.options)?;
+            let change = ElementsChange {
+                old: 0..self.elements.len(),
+                new: 0..parsed.elements.len(),
+            };
+            return Ok((parsed, change));
+        }
+
+        // Byte offset in `source` of an offset in the previous source outside of the edit
+        let shift = |offset: usize| {
+            if offset >= range.end {
+                offset - range.len() + new_text.len()
+            } else {
+                offset
+            }
+        };
+        let reuse = |(element, position): &(MarkdownElement<'a>, Range<usize>)| {
+            let element = map_slices(element.clone(), |slice| {
+                // Slices from extensions may not be of the source
+                let Some(offset) = (slice.as_ptr() as usize)
+                    .checked_sub(self.source.as_ptr() as usize)
+                    .filter(|offset| {
+                        !slice.is_empty() && offset + slice.len() <= self.source.len()
+                    })
+                else {
+                    return "";
+                };
+                let start = shift(offset);
+                source.get(start..start + slice.len()).unwrap_or_default()
+            });
+            (element, shift(position.start)..shift(position.end))
+        };
+
+        let first = self.first_affected(range.start);
+        let from = if first == 0 {
+            0
+        } else {
+            self.elements[first].1.start
+        };
+
+        let mut elements: Vec<_> = self.elements[..first].iter().map(reuse).collect();
+        let mut old_index = first;
+        let mut resynchronised = None;
+        // Reused elements count towards the limit
+        let options = self.options.clone().max_elements(
+            self.options
+                .max_elements
+                .map(|max| max.saturating_sub(first)),
+        );
+        parse_from(source, from, &options, |element, position| {
+            // Parsing after a (non list item) element that starts at the same place after the edit
+            // gives the same elements as before
+            if position.start >= edit_end && restarts_parsing(&element) {
+                let old_start = position.start - new_text.len() + range.len();
+                while self
+                    .elements
+                    .get(old_index)
+                    .is_some_and(|(_, position)| position.start < old_start)
+                {
+                    old_index += 1;
+                }
+                if let Some((old_element, old_position)) = self.elements.get(old_index) {
+                    if old_position.start == old_start
+                        && old_position.len() == position.len()
+                        && restarts_parsing(old_element)
+                    {
+                        resynchronised = Some(old_index);
+                        return ControlFlow::Break(());
+                    }
+                }
+            }
+            elements.push((element, position));
+            ControlFlow::Continue(())
+        })
+        .map_err(|error| error.with_limits(&self.options))?;
+
+        let old_end = resynchronised.unwrap_or(self.elements.len());
+        let mut change = ElementsChange {
+            old: first..old_end,
+            new: first..elements.len(),
+        };
+        elements.extend(self.elements[old_end..].iter().map(reuse));
+        if let Some(limit) = self.options.max_elements {
+            if let Some((_, position)) = elements.get(limit) {
+                return Err(ParseError {
+                    reason: ParseErrorReason::TooManyElements { limit },
+                    position: position.clone(),
+                });
+            }
+        }
+
+        // Reparsed elements that did not change
+        let unchanged = |old: usize, new: usize| {
+            let (old_element, old_position) = &self.elements[old];
+            same_element(old_element, &elements[new].0)
+                && (shift(old_position.start)..shift(old_position.end)) == elements[new].1
+        };
+        while !change.old.is_empty()
+            && !change.new.is_empty()
+            && self.elements[change.old.start].1.end < range.start
+            && unchanged(change.old.start, change.new.start)
+        {
+            change.old.start += 1;
+            change.new.start += 1;
+        }
+        while !change.old.is_empty()
+            && !change.new.is_empty()
+            && self.elements[change.old.end - 1].1.start >= range.end
+            && unchanged(change.old.end - 1, change.new.end - 1)
+        {
+            change.old.end -= 1;
+            change.new.end -= 1;
+        }
+
+        let parsed = ParsedElements {
+            source,
+            options: self.options.clone(),
+            elements,
+        };
+        Ok((parsed, change))
+    }
+
+    /// Index of the first element that an edit starting at `edit_start` can change. The element
+    /// before the one containing the edit may extend into it (list item children and tables).
+    /// Whether a list is loose depends on all of its items and the indented lines after them, so
+    /// those are reparsed from the start of the list
+    fn first_affected(&self, edit_start: usize) -> usize {
+        let in_list = |index: usize| {
+            let (element, position) = &self.elements[index];
+            matches!(element, MarkdownElement::ListItem { .. })
+                || self.source[position.start..].starts_with([' ', '\t'])
+        };
+        let mut first = self
+            .elements
+            .partition_point(|(_, position)| position.start <= edit_start)
+            .saturating_sub(2);
+        while first > 0 && in_list(first) && in_list(first - 1) {
+            first -= 1;
+        }
+        first
+    }
+}
+
+fn same_element<'c>(old: &MarkdownElement<'c>, new: &MarkdownElement<'c>) -> bool {
+    old == new
+}
+
+fn map_slices<'a, 'b>(
+    element: MarkdownElement<'a>,
+    mut map: impl FnMut(&'a str) -> &'b str,
+) -> MarkdownElement<'b> {
+    match element {
+        MarkdownElement::Heading { level, text } => MarkdownElement::Heading {
+            level,
+            text: RawText(map(text.0)),
+        },
+        MarkdownElement::Quote(inner) => MarkdownElement::Quote(RawMarkdown(map(inner.0))),
+        MarkdownElement::Callout { kind, title } => MarkdownElement::Callout {
+            kind: map(kind),
+            title: RawText(map(title.0)),
+        },
+        MarkdownElement::Paragraph(text) => MarkdownElement::Paragraph(RawText(map(text.0))),
+        MarkdownElement::ListItem {
+            level,
+            number,
+            text,
+            children,
+            loose,
+            continues,
+        } => MarkdownElement::ListItem {
+            level,
+            number,
+            text: RawText(map(text.0)),
+            children: RawMarkdown(map(children.0)),
+            loose,
+            continues,
+        },
+        MarkdownElement::Table(table) => MarkdownElement::Table(Table {
+            source: map(table.source),
+            ..table
+        }),
+        MarkdownElement::CodeBlock { language, code } => MarkdownElement::CodeBlock {
+            language: map(language),
+            code: RawCode(map(code.0)),
+        },
+        MarkdownElement::LaTeXBlock { script } => MarkdownElement::LaTeXBlock {
+            script: map(script),
+        },
+        MarkdownElement::CommandBlock(command) => MarkdownElement::CommandBlock(CommandBlock {
+            name: map(command.name),
+            arguments: map(command.arguments),
+            inner: RawMarkdown(map(command.inner.0)),
+        }),
+        MarkdownElement::CommentBlock(comment) => MarkdownElement::CommentBlock(map(comment)),
+        MarkdownElement::HTMLElement(element) => MarkdownElement::HTMLElement(map(element)),
+        MarkdownElement::Frontmatter(frontmatter) => MarkdownElement::Frontmatter(map(frontmatter)),
+        MarkdownElement::HorizontalRule => MarkdownElement::HorizontalRule,
+        MarkdownElement::Custom(block) => MarkdownElement::Custom(CustomBlock {
+            name: block.name,
+            source: map(block.source),
+            data: CustomData {
+                arguments: map(block.data.arguments),
+                content: map(block.data.content),
+                value: block.data.value,
+            },
+        }),
+        MarkdownElement::Footnote => MarkdownElement::Footnote,
+        MarkdownElement::Empty => MarkdownElement::Empty,
+    }
+}
@@ -1,31 +1,62 @@
 #![doc = include_str!("../README.md")]
 
+pub mod arguments;
+pub mod cst;
+pub mod document;
+pub mod emoji;
+pub mod extensions;
 pub mod extras;
+pub mod fmt;
+pub mod incremental;
+pub mod streaming;
 pub mod utilities;
+pub mod visitor;
 
 /// Markdown block element
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum MarkdownElement<'a> {
     Heading {
         level: u8,
         text: RawText<'a>,
     },
     Quote(RawMarkdown<'a>),
+    /// `> [!kind] title` (from Obsidian), when [`ParseOptions::callouts`] is enabled. The
+    /// following lines of the quote are separate [`MarkdownElement::Quote`]s. A `+` or `-` folding
+    /// marker after the kind is skipped
+    Callout {
+        kind: &'a str,
+        title: RawText<'a>,
+    },
     Paragraph(RawText<'a>),
     ListItem {
+        /// Nesting depth, starting at 0. From indentation relative to the content of the previous items
         level: u8,
+        /// `Some` for ordered items (`1.` or `1)`), with their number
+        number: Option<u32>,
+        /// With [`ParseOptions::multiline_paragraphs`], includes following lines of text
         text: RawText<'a>,
+        /// Following lines indented to the content of the item (continuation paragraphs, code blocks,
+        /// quotes etc), with their indentation. Nested list items are separate elements
+        children: RawMarkdown<'a>,
+        /// Whether the list of the item has blank lines between items or their children (not
+        /// counting lists nested in it). Items of loose lists are wrapped in paragraphs
+        loose: bool,
+        /// Whether these are more `children` of the previous item at `level`, after items nested
+        /// in it. Has no marker, so `number` is `None` and `text` is empty
+        continues: bool,
     },
     // TODO
     Table(Table<'a>),
     // TODO modifiers
     CodeBlock {
         language: &'a str,
-        code: &'a str,
+        code: RawCode<'a>,
     },
     LaTeXBlock {
         script: &'a str,
     },
+    /// `{% name arguments %}` ... `{% /name %}` or self closing `{% name arguments /%}` (with empty `inner`)
     CommandBlock(CommandBlock<'a>),
     /// Inside `%%` (from Obsidan)
     CommentBlock(&'a str),
@@ -41,39 +72,88 @@ pub enum MarkdownElement<'a> {
     //     source: &'a str,
     // },
     Footnote,
+    /// Block of a [`extensions::BlockExtension`] registered on [`ParseOptions`]
+    Custom(extensions::CustomBlock<'a>),
     Empty,
 }
 
-impl MarkdownElement<'_> {
+impl<'a> MarkdownElement<'a> {
     #[must_use]
     pub fn as_markdown(&self) -> String {
         match self {
             Self::Heading { level, text } => {
                 let mut s = "#".repeat(*level as usize);
-                s.push_str(text.0);
                 s.push(' ');
+                s.push_str(text.0);
                 s
             }
-            Self::ListItem { level, text } => {
+            Self::ListItem {
+                children,
+                continues: true,
+                ..
+            } => children.0.trim_end().to_owned(),
+            Self::ListItem {
+                level,
+                number,
+                text,
+                children,
+                ..
+            } => {
                 let mut s = "\t".repeat(*level as usize);
-                s.push_str("- ");
+                if let Some(number) = number {
+                    s.push_str(&number.to_string());
+                    s.push_str(". ");
+                } else {
+                    s.push_str("- ");
+                }
                 s.push_str(text.0);
+                if !children.0.is_empty() {
+                    s.push('\n');
+                    s.push_str(children.0.trim_end());
+                }
                 s
             }
-            Self::CodeBlock { language, code } => {
-                format!("```{language}\n{code}```")
-                // let mut s = "```".to_owned();
-                // s.push_str(language);
-                // s.push_str("\n");
-                // s.push_str("```");
-                // s
-            }
+            Self::CodeBlock { language, code } => format!("```{language}\n{code}```"),
             Self::Paragraph(text) => text.0.to_owned(),
-            Self::Quote(text) => {
-                format!("> {text}", text = text.0)
+            Self::Quote(text) => format!(">{text}", text = text.0),
+            Self::Callout { kind, title } => format!("> [!{kind}] {title}", title = title.0)
+                .trim_end()
+                .to_owned(),
+            Self::Table(table) => table.source.trim_end().to_owned(),
+            Self::LaTeXBlock { script } => format!("$$\n{script}\n$$"),
+            Self::CommandBlock(CommandBlock {
+                name,
+                arguments,
+                inner,
+            }) => {
+                let mut s = format!("{{% {name}");
+                if !arguments.is_empty() {
+                    s.push(' ');
+                    s.push_str(arguments);
+                }
+                if inner.0.is_empty() {
+                    s.push_str(" /%}");
+                } else {
+                    s.push_str(" %}\n");
+                    s.push_str(inner.0);
+                    s.push_str("{% /");
+                    s.push_str(name);
+                    s.push_str(" %}");
+                }
+                s
+            }
+            Self::CommentBlock(comment) => {
+                if comment.contains('\n') {
+                    format!("%%\n{comment}\n%%")
+                } else {
+                    format!("%% {comment} %%")
+                }
             }
-            Self::Empty => String::new(),
-            item => format!("TODO {item:?}"),
+            Self::HTMLElement(element) => (*element).to_owned(),
+            Self::Frontmatter(frontmatter) => format!("---\n{frontmatter}---"),
+            Self::HorizontalRule => "---".to_owned(),
+            Self::Custom(block) => block.source.trim_end().to_owned(),
+            Self::Footnote | Self::Empty => String::new(),
         }
     }
 
@@ -91,10 +171,11 @@ impl MarkdownElement<'_> {
     }
 
     #[must_use]
-    pub fn parts_like(&self) -> Option<RawText> {
+    pub fn parts_like(&self) -> Option<RawText<'a>> {
         if let MarkdownElement::Heading { text, .. }
+        | MarkdownElement::Callout { title: text, .. }
         | MarkdownElement::Paragraph(text)
-        | MarkdownElement::ListItem { level: _, text } = self
+        | MarkdownElement::ListItem { text, .. } = self
         {
             Some(*text)
         } else if let MarkdownElement::Quote(text) = self {
@@ -113,8 +194,9 @@ impl MarkdownElement<'_> {
                 format!("Heading {{ level: {level} }}")
             }
             MarkdownElement::Quote(_) => "Quote".to_owned(),
+            MarkdownElement::Callout { kind, title: _ } => format!("Callout ({kind})"),
             MarkdownElement::Paragraph(_) => "Paragraph".to_owned(),
-            MarkdownElement::ListItem { level, text: _ } => {
+            MarkdownElement::ListItem { level, .. } => {
                 format!("ListItem {{ level: {level} }}")
             }
             MarkdownElement::Table(_table) => "Table".to_owned(),
@@ -126,6 +208,7 @@ impl MarkdownElement<'_> {
             MarkdownElement::Frontmatter(_) => "Frontmatter".to_owned(),
             MarkdownElement::HorizontalRule => "HorizontalRule".to_owned(),
             MarkdownElement::Footnote => "Footnote".to_owned(),
+            MarkdownElement::Custom(block) => format!("Custom ({name})", name = block.name),
             MarkdownElement::Empty => "Empty".to_owned(),
         }
     }
@@ -133,6 +216,7 @@ impl MarkdownElement<'_> {
 
 /// (unsplit) Text inside markdown item
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct RawText<'a>(pub &'a str);
 
 impl<'a> RawText<'a> {
@@ -141,6 +225,12 @@ impl<'a> RawText<'a> {
         PartsIterator::new(self.0)
     }
 
+    /// [`RawText::parts`] with only the inline syntax enabled in `options`
+    #[must_use]
+    pub fn parts_with_options(&self, options: &ParseOptions) -> PartsIterator<'a> {
+        PartsIterator::with_options(self.0, options)
+    }
+
     #[must_use]
     pub fn no_decoration(&self) -> String {
         let mut s = String::new();
@@ -151,24 +241,52 @@ impl<'a> RawText<'a> {
     }
 }
 
+/// Contents of a code block. A slice of the source, so lines end with its line endings (`\r\n` or
+/// `\r` as well as `\n`). [`RawCode::lines`] and the [`std::fmt::Display`] output do not depend on
+/// them
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RawCode<'a>(pub &'a str);
+
+impl<'a> RawCode<'a> {
+    /// Without their line endings
+    pub fn lines(&self) -> impl Iterator<Item = &'a str> {
+        let on = self.0;
+        lines(on, 0).map(move |(start, end, _)| &on[start..end])
+    }
+}
+
+/// Lines each ending with `\n`
+impl std::fmt::Display for RawCode<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for line in self.lines() {
+            writeln!(f, "{line}")?;
+        }
+        Ok(())
+    }
+}
+
 /// Some are prefixes, some are wrapped
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum MarkdownTextElement<'a> {
     Plain(&'a str),
     /// `*hi*` or `_hi_`
     Italic(&'a str),
     /// `**hi**` or `__hi__`
     Bold(&'a str),
-    /// **_hi_**
+    /// `***hi***` or `___hi___`
     BoldAndItalic(&'a str),
     /// `` `code` ``
     Code(&'a str),
     /// `~~gone~~`
     StrikeThrough(&'a str),
-    /// `:emoji:`
+    /// `:emoji:`. Only known shortcodes (see [`emoji::shortcode_to_unicode`]), holds the name
     Emoji(&'a str),
-    /// `$\sin$`
+    /// `$\sin$` or `\(\sin\)`
     Latex(&'a str),
+    /// `$$\sin$$` or `\[\sin\]` inside text
+    DisplayLatex(&'a str),
     /// `{something}` TODO WIP
     Expression(&'a str),
     /// `==hightlighted==`
@@ -177,7 +295,7 @@ pub enum MarkdownTextElement<'a> {
     Superscript(&'a str),
     /// `~subscript~` (unfortuantly not _)
     Subscript(&'a str),
-    /// `#item`
+    /// `#item` or nested `#item/sub-item` (see [`MarkdownTextElement::tag_segments`])
     Tag(&'a str),
     /// `[on](to)`
     Link {
@@ -190,9 +308,24 @@ pub enum MarkdownTextElement<'a> {
         alt: &'a str,
         source: &'a str,
     },
+    /// `{% name arguments /%}`. Always has empty `inner`
+    Command(CommandBlock<'a>),
+    /// Syntax of an [`extensions::InlineExtension`] registered on [`ParseOptions`]
+    Custom(extensions::CustomInline<'a>),
 }
 
 impl<'a> MarkdownTextElement<'a> {
+    /// For [`MarkdownTextElement::Tag`], the `/` separated parts of a nested tag.
+    /// `#project/alpha` gives `project` then `alpha`
+    #[must_use]
+    pub fn tag_segments(&self) -> Option<std::str::Split<'a, char>> {
+        if let MarkdownTextElement::Tag(tag) = self {
+            Some(tag.split('/'))
+        } else {
+            None
+        }
+    }
+
     #[must_use]
     pub fn no_decoration(&self) -> &'a str {
         match self {
@@ -202,41 +335,51 @@ impl<'a> MarkdownTextElement<'a> {
             | MarkdownTextElement::BoldAndItalic(i)
             | MarkdownTextElement::Code(i)
             | MarkdownTextElement::StrikeThrough(i)
-            | MarkdownTextElement::Emoji(i)
             | MarkdownTextElement::Latex(i)
+            | MarkdownTextElement::DisplayLatex(i)
             | MarkdownTextElement::Highlight(i)
             | MarkdownTextElement::Subscript(i)
             | MarkdownTextElement::Superscript(i)
-            | MarkdownTextElement::Tag(i) => i,
-            MarkdownTextElement::Expression(_) | MarkdownTextElement::Media { .. } => "",
-            MarkdownTextElement::Link { on: _, to: _ } => {
-                eprintln!("TODO no decoration link");
-                ""
-            }
+            | MarkdownTextElement::Tag(i)
+            | MarkdownTextElement::Custom(extensions::CustomInline { content: i, .. }) => i,
+            MarkdownTextElement::Emoji(i) => emoji::shortcode_to_unicode(i).unwrap_or(i),
+            MarkdownTextElement::Expression(_)
+            | MarkdownTextElement::Media { .. }
+            | MarkdownTextElement::Command(_) => "",
+            MarkdownTextElement::Link { on, to: _ } => on.0,
         }
     }
 }
 
 // TODO want to do in main loop
-#[allow(clippy::needless_lifetimes)]
-fn decide<'a>(item: &'a str) -> MarkdownElement<'a> {
+fn decide(item: &str) -> MarkdownElement<'_> {
     let item = item.trim();
-    if item.starts_with('#') {
-        let level = item.chars().take_while(|c| *c == '#').count();
+    let heading_level = item.chars().take_while(|c| *c == '#').count();
+    // `#tag` at the start of a line is not a heading. Following `CommonMark`, neither is `#######`
+    if (1..=6).contains(&heading_level)
+        && item[heading_level..]
+            .chars()
+            .next()
+            .is_none_or(char::is_whitespace)
+    {
+        let level = heading_level;
         MarkdownElement::Heading {
-            level: level.try_into().expect("deep header"),
-            text: RawText(item[level..].trim()),
+            level: u8::try_from(level).unwrap_or(6),
+            text: RawText(without_closing_sequence(item[level..].trim())),
         }
     } else if let Some(item) = item.strip_prefix('>') {
         MarkdownElement::Quote(RawMarkdown(item))
     } else if let "---" = item {
         MarkdownElement::HorizontalRule
-    } else if let Some(item) = item.trim_start().strip_prefix('-') {
-        // TODO one or the other
-        let level = item.chars().take_while(|c| *c == '\t' || *c == ' ').count();
+    } else if let Some((number, marker_length)) = list_marker(item) {
+        // `level` is set by the parser, as it depends on previous items
         MarkdownElement::ListItem {
-            level: level.try_into().expect("deep list item"),
-            text: RawText(item.trim()),
+            level: 0,
+            number,
+            text: RawText(item[marker_length..].trim()),
+            children: RawMarkdown(""),
+            loose: false,
+            continues: false,
         }
     } else if item.is_empty() {
         MarkdownElement::Empty
@@ -245,17 +388,571 @@ fn decide<'a>(item: &'a str) -> MarkdownElement<'a> {
     }
 }
 
-#[derive(Default, Copy, Clone)]
+/// For the text after `>`, the callout it starts
+fn callout(quote: &str) -> Option<MarkdownElement<'_>> {
+    let rest = quote.trim_start().strip_prefix("[!")?;
+    let (kind, title) = rest.split_once(']')?;
+    if kind.is_empty() || kind.contains(char::is_whitespace) {
+        return None;
+    }
+    let title = title.strip_prefix(['+', '-']).unwrap_or(title);
+    Some(MarkdownElement::Callout {
+        kind,
+        title: RawText(title.trim()),
+    })
+}
+
+/// Number of `>` markers (which can be separated by whitespace) at the start of `line`
+fn quote_depth(line: &str) -> usize {
+    let mut rest = line.trim_start();
+    let mut depth = 0;
+    while let Some(inner) = rest.strip_prefix('>') {
+        depth += 1;
+        rest = inner.trim_start();
+    }
+    depth
+}
+
+/// Heading text without an optional closing sequence of `#`s (which must follow a space)
+fn without_closing_sequence(text: &str) -> &str {
+    let without = text.trim_end_matches('#');
+    if without.is_empty() || without.ends_with([' ', '\t']) {
+        without.trim_end()
+    } else {
+        text
+    }
+}
+
+/// `-`, `*`, `+`, or up to nine digits followed by `.` or `)`, when followed by whitespace or the
+/// end of `item`. Returns the number of ordered markers and the length of the marker
+fn list_marker(item: &str) -> Option<(Option<u32>, usize)> {
+    let (number, length) = if item.starts_with(['-', '*', '+']) {
+        (None, 1)
+    } else {
+        let digits = item
+            .find(|chr: char| !chr.is_ascii_digit())
+            .unwrap_or(item.len());
+        if !(1..=9).contains(&digits) || !item[digits..].starts_with(['.', ')']) {
+            return None;
+        }
+        (Some(item[..digits].parse().ok()?), digits + 1)
+    };
+    let rest = &item[length..];
+    (rest.is_empty() || rest.starts_with(char::is_whitespace)).then_some((number, length))
+}
+
+/// Columns of leading whitespace. Tabs advance to the next multiple of `tab_width`
+pub(crate) fn indentation_columns(line: &str, tab_width: usize) -> usize {
+    whitespace_end_column(line, 0, tab_width)
+}
+
+/// Column after the leading whitespace of `text`, which starts at `column`
+fn whitespace_end_column(text: &str, column: usize, tab_width: usize) -> usize {
+    let tab_width = tab_width.max(1);
+    text.chars()
+        .map_while(|chr| match chr {
+            ' ' => Some(1),
+            '\t' => Some(tab_width),
+            _ => None,
+        })
+        .fold(column, |column, width| {
+            if width == 1 {
+                column + 1
+            } else {
+                column + width - column % width
+            }
+        })
+}
+
+/// Lines after a list item (from `from`) that are indented to at least `content_column`, and blank
+/// lines between them. Stops at list items. Returns the end of the last line and the start of the
+/// line after it
+fn list_item_children(
+    on: &str,
+    from: usize,
+    content_column: usize,
+    tab_width: usize,
+) -> Option<(usize, usize)> {
+    let mut in_code = None;
+    let mut last = None;
+    for (line_start, line_end, next_line) in lines(on, from) {
+        let line = &on[line_start..line_end];
+        if line.trim().is_empty() {
+            continue;
+        }
+        let indented = indentation_columns(line, tab_width) >= content_column;
+        if !indented || (in_code.is_none() && list_item_columns(line, tab_width).is_some()) {
+            break;
+        }
+        track_fence(&mut in_code, line);
+        last = Some((line_end, next_line));
+    }
+    last
+}
+
+/// Lines after a paragraph line (from `from`) that continue the paragraph: up to a blank line or
+/// a line that starts another element. Returns the end of the last line and the start of the line
+/// after it
+fn paragraph_lines(on: &str, from: usize, options: &ParseOptions) -> Option<(usize, usize)> {
+    let mut last = None;
+    for (line_start, line_end, next_line) in lines(on, from) {
+        if !continues_paragraph(&on[line_start..line_end], options) {
+            break;
+        }
+        last = Some((line_end, next_line));
+    }
+    last
+}
+
+/// Whether `line` continues a paragraph (with [`ParseOptions::multiline_paragraphs`]): it is text
+/// that does not start another element
+pub(crate) fn continues_paragraph(line: &str, options: &ParseOptions) -> bool {
+    let trimmed = line.trim();
+    let starts_block = code_fence(line).is_some()
+        || (options.latex && (trimmed.starts_with("$$") || trimmed.starts_with("\\[")))
+        || (options.comments && trimmed.starts_with("%%"))
+        || (options.tables && trimmed.starts_with('|'))
+        || (options.commands && trimmed.starts_with("{%"))
+        || options
+            .block_extensions
+            .0
+            .iter()
+            .any(|extension| extension.starts(line));
+    !starts_block && matches!(decide(line), MarkdownElement::Paragraph(_))
+}
+
+/// Whether the list starting at `from` has a blank line between its items or lines in them (not
+/// counting lines in lists nested in it). Lines belong to the list if they are list items not
+/// before `parent_content` (the content column of the item the list is nested in), are indented to
+/// at least the content of an item or (with [`ParseOptions::multiline_paragraphs`]) continue the
+/// text of an item
+fn list_is_loose(on: &str, from: usize, parent_content: usize, options: &ParseOptions) -> bool {
+    let tab_width = options.tab_width;
+    let mut in_code = None;
+    let mut blank_before = false;
+    // Items of the list and those nested in it
+    let mut items = ListState::default();
+    // End of the text of the last item
+    let mut item_text_end = from;
+    for (line_start, line_end, next_line) in lines(on, from) {
+        let line = &on[line_start..line_end];
+        if line.trim().is_empty() {
+            blank_before = in_code.is_none();
+            continue;
+        }
+        let item_columns = list_item_columns(line, tab_width).filter(|_| in_code.is_none());
+        // Level of the list the line separates blocks of, if it is in the list
+        let in_list = if let Some((marker, content)) = item_columns {
+            if marker < parent_content {
+                return false;
+            }
+            if options.multiline_paragraphs {
+                item_text_end =
+                    paragraph_lines(on, next_line, options).map_or(next_line, |(_, after)| after);
+            }
+            let open = items.content_columns.len();
+            let level = items.item(marker, content);
+            // The first item of a nested list is in the item before
+            if level == open {
+                level.checked_sub(1)
+            } else {
+                Some(level)
+            }
+        } else if line_start < item_text_end {
+            Some(items.content_columns.len().saturating_sub(1))
+        } else {
+            let indentation = indentation_columns(line, tab_width);
+            items
+                .content_columns
+                .iter()
+                .rposition(|content| indentation >= *content)
+        };
+        match in_list {
+            None if item_columns.is_none() => return false,
+            Some(0) if blank_before => return true,
+            _ => blank_before = false,
+        }
+        track_fence(&mut in_code, line);
+    }
+    false
+}
+
+/// For a line opening a fenced code block (three or more backticks), the length of the fence and
+/// the text after it
+fn code_fence(line: &str) -> Option<(usize, &str)> {
+    let trimmed = line.trim();
+    let length = trimmed.bytes().take_while(|byte| *byte == b'`').count();
+    (length >= 3).then(|| (length, trimmed[length..].trim_end()))
+}
+
+/// Whether `line` closes a code block opened with a fence of `length` backticks: a line of at
+/// least as many backticks
+fn closes_fence(line: &str, length: usize) -> bool {
+    let trimmed = line.trim();
+    trimmed.len() >= length && trimmed.bytes().all(|byte| byte == b'`')
+}
+
+/// Updates `open`, the fence length of the code block lines are in, for `line`. Returns whether
+/// the line is a fence
+pub(crate) fn track_fence(open: &mut Option<usize>, line: &str) -> bool {
+    if let Some(length) = *open {
+        let closes = closes_fence(line, length);
+        if closes {
+            *open = None;
+        }
+        closes
+    } else {
+        *open = code_fence(line).map(|(length, _)| length);
+        open.is_some()
+    }
+}
+
+/// For a list item line, the columns of the marker and of the content. Tabs advance to the next
+/// multiple of `tab_width`
+pub(crate) fn list_item_columns(line: &str, tab_width: usize) -> Option<(usize, usize)> {
+    let item = line.trim_start_matches([' ', '\t']);
+    let (_, marker_length) = list_marker(item)?;
+    let marker = indentation_columns(line, tab_width);
+    let after = &item[marker_length..];
+    if after.trim().is_empty() {
+        Some((marker, marker + marker_length + 1))
+    } else {
+        Some((
+            marker,
+            whitespace_end_column(after, marker + marker_length, tab_width),
+        ))
+    }
+}
+
+/// Which syntax to parse. The default is all of the syntax this crate supports apart from
+/// wikilinks and callouts, with a line of text per paragraph. Syntax that is turned off is parsed as
+/// text (so `{` in prose does not start an expression)
+///
+/// ```
+/// use simple_markdown_parser::ParseOptions;
+///
+/// let options = ParseOptions::gfm().emoji(false).tab_width(2);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct ParseOptions {
     include_new_lines: bool,
+    tab_width: usize,
+    multiline_paragraphs: bool,
+    frontmatter: bool,
+    tables: bool,
+    latex: bool,
+    comments: bool,
+    commands: bool,
+    expressions: bool,
+    tags: bool,
+    emoji: bool,
+    wikilinks: bool,
+    callouts: bool,
+    max_nesting_depth: Option<usize>,
+    max_line_length: Option<usize>,
+    max_elements: Option<usize>,
+    block_extensions: extensions::Extensions<dyn extensions::BlockExtension>,
+    inline_extensions: extensions::Extensions<dyn extensions::InlineExtension>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            include_new_lines: false,
+            tab_width: 4,
+            multiline_paragraphs: false,
+            frontmatter: true,
+            tables: true,
+            latex: true,
+            comments: true,
+            commands: true,
+            expressions: true,
+            tags: true,
+            emoji: true,
+            wikilinks: false,
+            callouts: false,
+            max_nesting_depth: Some(128),
+            max_line_length: None,
+            max_elements: None,
+            block_extensions: extensions::Extensions::default(),
+            inline_extensions: extensions::Extensions::default(),
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Only syntax in the `CommonMark` specification: consecutive lines of text form a single
+    /// paragraph and everything else that can be turned off is. See `tests/commonmark.rs` for how
+    /// closely output matches the specification examples
+    #[must_use]
+    pub fn commonmark() -> Self {
+        Self {
+            multiline_paragraphs: true,
+            frontmatter: false,
+            tables: false,
+            latex: false,
+            comments: false,
+            commands: false,
+            expressions: false,
+            tags: false,
+            emoji: false,
+            wikilinks: false,
+            callouts: false,
+            ..Self::default()
+        }
+    }
+
+    /// `CommonMark` with the GitHub extensions this crate supports: tables and (as rendered on
+    /// GitHub) emoji shortcodes
+    #[must_use]
+    pub fn gfm() -> Self {
+        Self::commonmark().tables(true).emoji(true)
+    }
+
+    /// `CommonMark` with the syntax of Obsidian notes: frontmatter, tables, LaTeX, `%%` comments,
+    /// tags, wikilinks and callouts
+    #[must_use]
+    pub fn obsidian() -> Self {
+        Self::commonmark()
+            .frontmatter(true)
+            .tables(true)
+            .latex(true)
+            .comments(true)
+            .tags(true)
+            .wikilinks(true)
+            .callouts(true)
+    }
+
+    /// Columns a tab counts as when deciding list item nesting. Defaults to 4
+    #[must_use]
+    pub fn tab_width(mut self, tab_width: usize) -> Self {
+        self.tab_width = tab_width;
+        self
+    }
+
+    /// Consecutive lines of text form one paragraph, rather than a paragraph each
+    #[must_use]
+    pub fn multiline_paragraphs(mut self, enabled: bool) -> Self {
+        self.multiline_paragraphs = enabled;
+        self
+    }
+
+    /// `---` delimited block at the start of the source
+    #[must_use]
+    pub fn frontmatter(mut self, enabled: bool) -> Self {
+        self.frontmatter = enabled;
+        self
+    }
+
+    /// Blocks of lines starting with `|`
+    #[must_use]
+    pub fn tables(mut self, enabled: bool) -> Self {
+        self.tables = enabled;
+        self
+    }
+
+    /// `$$` and `\[` blocks and inline `$`, `$$`, `\(` and `\[`
+    #[must_use]
+    pub fn latex(mut self, enabled: bool) -> Self {
+        self.latex = enabled;
+        self
+    }
+
+    /// `%%` blocks
+    #[must_use]
+    pub fn comments(mut self, enabled: bool) -> Self {
+        self.comments = enabled;
+        self
+    }
+
+    /// `{% name %}` blocks and inline `{% name /%}`
+    #[must_use]
+    pub fn commands(mut self, enabled: bool) -> Self {
+        self.commands = enabled;
+        self
+    }
+
+    /// Inline `{expression}`
+    #[must_use]
+    pub fn expressions(mut self, enabled: bool) -> Self {
+        self.expressions = enabled;
+        self
+    }
+
+    /// Inline `#tag`
+    #[must_use]
+    pub fn tags(mut self, enabled: bool) -> Self {
+        self.tags = enabled;
+        self
+    }
+
+    /// Inline `:shortcode:`
+    #[must_use]
+    pub fn emoji(mut self, enabled: bool) -> Self {
+        self.emoji = enabled;
+        self
+    }
+
+    /// `[[target]]` and `[[target|text]]` as [`MarkdownTextElement::Link`]s and `![[target]]` as
+    /// [`MarkdownTextElement::Media`]
+    #[must_use]
+    pub fn wikilinks(mut self, enabled: bool) -> Self {
+        self.wikilinks = enabled;
+        self
+    }
+
+    /// `> [!kind] title` as [`MarkdownElement::Callout`]
+    #[must_use]
+    pub fn callouts(mut self, enabled: bool) -> Self {
+        self.callouts = enabled;
+        self
+    }
+
+    /// Deepest nesting of quotes (`>` markers on a line), list items and commands before
+    /// [`ParseErrorReason::NestingTooDeep`]. Nested content is parsed again by the document tree
+    /// and the HTML emitter, so this bounds their recursion. Defaults to 128
+    #[must_use]
+    pub fn max_nesting_depth(mut self, limit: Option<usize>) -> Self {
+        self.max_nesting_depth = limit;
+        self
+    }
+
+    /// Longest line (in bytes) before [`ParseErrorReason::LineTooLong`]. Defaults to no limit
+    #[must_use]
+    pub fn max_line_length(mut self, limit: Option<usize>) -> Self {
+        self.max_line_length = limit;
+        self
+    }
+
+    /// Most elements a parse gives before [`ParseErrorReason::TooManyElements`]. Nested content
+    /// (such as the inside of a quote) is counted when it is parsed. Defaults to no limit
+    #[must_use]
+    pub fn max_elements(mut self, limit: Option<usize>) -> Self {
+        self.max_elements = limit;
+        self
+    }
+
+    /// Block syntax to parse as [`MarkdownElement::Custom`]. Extensions are tried in the order they
+    /// are added, before the built-in syntax. Defaults to none
+    #[must_use]
+    pub fn block_extension(self, extension: impl extensions::BlockExtension + 'static) -> Self {
+        self.shared_block_extension(std::sync::Arc::new(extension))
+    }
+
+    /// [`ParseOptions::block_extension`] with an instance that other options can also use
+    #[must_use]
+    pub fn shared_block_extension(
+        mut self,
+        extension: std::sync::Arc<dyn extensions::BlockExtension>,
+    ) -> Self {
+        self.block_extensions = self.block_extensions.with(extension);
+        self
+    }
+
+    /// Inline syntax to parse as [`MarkdownTextElement::Custom`]. Extensions are tried in the order
+    /// they are added, before the built-in syntax. Defaults to none
+    #[must_use]
+    pub fn inline_extension(self, extension: impl extensions::InlineExtension + 'static) -> Self {
+        self.shared_inline_extension(std::sync::Arc::new(extension))
+    }
+
+    /// [`ParseOptions::inline_extension`] with an instance that other options can also use
+    #[must_use]
+    pub fn shared_inline_extension(
+        mut self,
+        extension: std::sync::Arc<dyn extensions::InlineExtension>,
+    ) -> Self {
+        self.inline_extensions = self.inline_extensions.with(extension);
+        self
+    }
 }
 
 /// # Errors
 /// errors for unclosed blocks
-pub fn parse<'a>(on: &'a str, cb: impl FnMut(MarkdownElement<'a>)) -> Result<(), ()> {
+pub fn parse<'a>(on: &'a str, cb: impl FnMut(MarkdownElement<'a>)) -> Result<(), ParseError> {
     parse_with_options(on, &ParseOptions::default(), cb)
 }
 
+/// Structural error in the source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub reason: ParseErrorReason,
+    /// Byte range in the source
+    pub position: std::ops::Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorReason {
+    /// `{% name %}` without a `{% /name %}`. Position of the opening tag
+    UnclosedCommand { name: String },
+    /// `{% /found %}` when the innermost open command is `expected`
+    MismatchedCommandClose { expected: String, found: String },
+    /// `{% /name %}` without any open command
+    UnexpectedCommandClose { name: String },
+    /// Quotes, list items or commands nested deeper than [`ParseOptions::max_nesting_depth`].
+    /// Position of the line
+    NestingTooDeep { limit: usize },
+    /// Line longer than [`ParseOptions::max_line_length`]
+    LineTooLong { limit: usize },
+    /// More elements than [`ParseOptions::max_elements`]. Position of the first element over
+    TooManyElements { limit: usize },
+}
+
+impl ParseError {
+    /// For errors from parsing part of a source with a reduced [`ParseOptions::max_elements`],
+    /// the limit of `options`
+    pub(crate) fn with_limits(mut self, options: &ParseOptions) -> Self {
+        if let (ParseErrorReason::TooManyElements { limit }, Some(max)) =
+            (&mut self.reason, options.max_elements)
+        {
+            *limit = max;
+        }
+        self
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.reason {
+            ParseErrorReason::UnclosedCommand { name } => {
+                write!(f, "command '{name}' is not closed")?;
+            }
+            ParseErrorReason::MismatchedCommandClose { expected, found } => {
+                write!(
+                    f,
+                    "expected '{{% /{expected} %}}', found '{{% /{found} %}}'"
+                )?;
+            }
+            ParseErrorReason::UnexpectedCommandClose { name } => {
+                write!(f, "'{{% /{name} %}}' does not close any command")?;
+            }
+            ParseErrorReason::NestingTooDeep { limit } => {
+                write!(f, "nested deeper than {limit} levels")?;
+            }
+            ParseErrorReason::LineTooLong { limit } => {
+                write!(f, "line longer than {limit} bytes")?;
+            }
+            ParseErrorReason::TooManyElements { limit } => {
+                write!(f, "more than {limit} elements")?;
+            }
+        }
+        write!(
+            f,
+            " (at {start}..{end})",
+            start = self.position.start,
+            end = self.position.end
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Splits `name arguments` from inside `{% ... %}`
+fn split_command(command_line: &str) -> (&str, &str) {
+    command_line.split_once(' ').unwrap_or((command_line, ""))
+}
+
 pub fn strip_surrounds<'a>(on: &'a str, left: &str, right: &str) -> Option<&'a str> {
     on.trim()
         .strip_prefix(left)
@@ -265,163 +962,616 @@ pub fn strip_surrounds<'a>(on: &'a str, left: &str, right: &str) -> Option<&'a s
 
 /// Parse source using callback
 /// # Errors
-/// errors for unclosed blocks
-#[allow(clippy::result_unit_err, clippy::too_many_lines)]
+/// errors for unclosed blocks and mismatched command tags
 pub fn parse_with_options<'a>(
     on: &'a str,
     options: &ParseOptions,
     mut cb: impl FnMut(MarkdownElement<'a>),
-) -> Result<(), ()> {
-    let mut since_new_line = 0;
-    let mut start = 0;
+) -> Result<(), ParseError> {
+    parse_with_positions(on, options, |element, _| cb(element))
+}
+
+/// Lines as (start, end before the line ending, start of next line). Lines end with `\n`, `\r\n`
+/// or a lone `\r`
+pub(crate) fn lines(on: &str, from: usize) -> impl Iterator<Item = (usize, usize, usize)> + '_ {
+    let mut start = from;
+    std::iter::from_fn(move || {
+        if start >= on.len() {
+            return None;
+        }
+        let line_start = start;
+        let line_end = on[line_start..]
+            .find(['\n', '\r'])
+            .map_or(on.len(), |offset| line_start + offset);
+        let line_ending = if on[line_end..].starts_with("\r\n") {
+            "\r\n".len()
+        } else {
+            usize::from(line_end < on.len())
+        };
+        start = line_end + line_ending;
+        Some((line_start, line_end, start))
+    })
+}
 
-    // Some => in_code
-    let mut current_code_language = None;
+/// [`parse_with_options`] that also gives the byte range of each element in `on` (from the start
+/// of its first line to the end of its last line, excluding the line ending). A leading byte order
+/// mark is skipped and `\r\n` or `\r` line endings are accepted. Single line elements never include
+/// the `\r`, multiline contents are slices of `on` and so keep the original line endings (see
+/// [`RawCode`] for code)
+/// # Errors
+/// errors for unclosed blocks and mismatched command tags
+pub fn parse_with_positions<'a>(
+    on: &'a str,
+    options: &ParseOptions,
+    mut cb: impl FnMut(MarkdownElement<'a>, std::ops::Range<usize>),
+) -> Result<(), ParseError> {
+    parse_from(on, 0, options, |element, position| {
+        cb(element, position);
+        std::ops::ControlFlow::Continue(())
+    })
+}
+
+/// Whether parsing from the start of `element` does not depend on previous elements
+pub(crate) fn restarts_parsing(element: &MarkdownElement) -> bool {
+    !matches!(
+        element,
+        MarkdownElement::ListItem { .. } | MarkdownElement::Empty
+    )
+}
+
+/// [`parse_with_positions`] starting at the line beginning at `from`, which must not be inside a
+/// multiline block or list. Stops (without checking for unclosed blocks) when `cb` breaks
+pub(crate) fn parse_from<'a>(
+    on: &'a str,
+    from: usize,
+    options: &ParseOptions,
+    cb: impl FnMut(MarkdownElement<'a>, std::ops::Range<usize>) -> std::ops::ControlFlow<()>,
+) -> Result<(), ParseError> {
+    parse_lines(on, from, ListState::default(), options, cb).map(|_| ())
+}
+
+/// Items of the list that parsing starts inside of
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ListState {
+    /// Content columns of the items, outermost first
+    pub(crate) content_columns: Vec<usize>,
+    /// Whether the lists of the items are loose, outermost first. Missing for a list that has
+    /// just started
+    pub(crate) loose: Vec<bool>,
+}
+
+impl ListState {
+    /// Updates the columns for an item with its marker and content at these columns. Returns the
+    /// level of the item
+    pub(crate) fn item(&mut self, marker: usize, content: usize) -> usize {
+        // Nested if indented to at least the content of the parent item
+        while self
+            .content_columns
+            .last()
+            .is_some_and(|parent_content| marker < *parent_content)
+        {
+            self.content_columns.pop();
+        }
+        self.content_columns.push(content);
+        let level = self.content_columns.len() - 1;
+        self.loose.truncate(level + 1);
+        level
+    }
+
+    /// Closes the items nested deeper than `level`
+    pub(crate) fn close_nested(&mut self, level: usize) {
+        self.content_columns.truncate(level + 1);
+        self.loose.truncate(level + 1);
+    }
+}
+
+/// Multiline block without its closing line at the end of the source
+pub(crate) enum Unclosed {
+    /// With the length of the fence
+    Code(usize),
+    /// With the closing delimiter
+    LaTeX(&'static str),
+    Comment,
+    Frontmatter,
+    /// With the opening line
+    Custom(std::sync::Arc<dyn extensions::BlockExtension>, String),
+}
+
+impl Unclosed {
+    /// Whether `line` would end the block
+    pub(crate) fn ended_by(&self, line: &str) -> bool {
+        match self {
+            Self::Code(fence_length) => closes_fence(line, *fence_length),
+            Self::LaTeX(end) => line.trim() == *end,
+            Self::Comment => line.trim().ends_with("%%"),
+            Self::Frontmatter => line.trim() == "---",
+            Self::Custom(extension, opening) => !matches!(
+                extension.next_line(opening, line),
+                extensions::BlockLine::Continue
+            ),
+        }
+    }
+}
+
+/// [`parse_from`] inside the items of `list`. Also returns the block left open at the end of `on`
+/// with the start of its opening line
+#[allow(clippy::too_many_lines)]
+pub(crate) fn parse_lines<'a>(
+    on: &'a str,
+    from: usize,
+    mut list: ListState,
+    options: &ParseOptions,
+    mut cb: impl FnMut(MarkdownElement<'a>, std::ops::Range<usize>) -> std::ops::ControlFlow<()>,
+) -> Result<Option<(Unclosed, usize)>, ParseError> {
+    let content_start = if on.starts_with('\u{feff}') {
+        '\u{feff}'.len_utf8()
+    } else {
+        0
+    };
+    let from = from.max(content_start);
+
+    let stopped = std::cell::Cell::new(false);
+    let elements = std::cell::Cell::new(0);
+    // Position of the element over `max_elements`
+    let too_many = std::cell::Cell::new(None);
+    let mut cb = |element, position: std::ops::Range<usize>| {
+        if stopped.get() {
+            return;
+        }
+        if options
+            .max_elements
+            .is_some_and(|max| elements.get() >= max)
+        {
+            too_many.set(Some(position));
+            stopped.set(true);
+            return;
+        }
+        elements.set(elements.get() + 1);
+        stopped.set(cb(element, position).is_break());
+    };
+    let on_stopped = || match too_many.take() {
+        Some(position) => Err(ParseError {
+            reason: ParseErrorReason::TooManyElements {
+                limit: options.max_elements.unwrap_or_default(),
+            },
+            position,
+        }),
+        None => Ok(None),
+    };
+    let too_deep = |depth: usize, position| match options.max_nesting_depth {
+        Some(limit) if depth > limit => Err(ParseError {
+            reason: ParseErrorReason::NestingTooDeep { limit },
+            position,
+        }),
+        _ => Ok(()),
+    };
+
+    // Start of the opening line of the current multiline block
+    let mut block_start = from;
+    // Start of the content of the current multiline block
+    let mut start = from;
+    // End of the previous line
+    let mut last_line_end = from;
+
+    // Some => in_code, with the length of the fence
+    let mut current_code_fence: Option<(usize, &str)> = None;
 
     let mut current_command_and_arguments: Option<(&str, &str)> = None;
+    // Position of the opening tag of the current command
+    let mut current_command_position = 0..0;
+    // Commands opened inside the current command (name and position), for nesting
+    let mut inner_commands: Vec<(&str, std::ops::Range<usize>)> = Vec::new();
+    // Command tags in code blocks of a command body are ignored
+    let mut in_command_code = None;
 
     let mut in_frontmatter = false;
     let mut in_table = false;
-    let mut in_latex_block = false;
+    // Some => in_latex_block, with the closing delimiter (`$$` or `\]`)
+    let mut latex_block_end: Option<&'static str> = None;
     let mut in_markdown_comment = false;
+    // Some => in a block of the extension, with its opening line
+    let mut custom_block: Option<(&std::sync::Arc<dyn extensions::BlockExtension>, &str)> = None;
 
-    for (idx, chr) in on.char_indices() {
-        if let '\n' = chr {
-            let line = &on[since_new_line..idx];
+    // Lines before this are part of the previous element (children of a list item or lines of a
+    // paragraph)
+    let mut element_lines_end = from;
 
-            if current_code_language.is_some() {
-                if let "```" = line.trim() {
-                    cb(MarkdownElement::CodeBlock {
-                        language: current_code_language.take().unwrap(),
-                        code: &on[start..since_new_line],
-                    });
-                    start = idx + 1;
-                }
-                since_new_line = idx + 1;
-                continue;
+    for (line_start, line_end, next_line) in lines(on, from) {
+        if stopped.get() {
+            return on_stopped();
+        }
+        if let Some(limit) = options
+            .max_line_length
+            .filter(|limit| line_end - line_start > *limit)
+        {
+            return Err(ParseError {
+                reason: ParseErrorReason::LineTooLong { limit },
+                position: line_start..line_end,
+            });
+        }
+        if line_start < element_lines_end {
+            continue;
+        }
+
+        let line = &on[line_start..line_end];
+        let line_position = line_start..line_end;
+        let block_position = block_start..line_end;
+
+        if let Some((fence_length, language)) = current_code_fence {
+            if closes_fence(line, fence_length) {
+                cb(
+                    MarkdownElement::CodeBlock {
+                        language,
+                        code: RawCode(&on[start..line_start]),
+                    },
+                    block_position,
+                );
+                current_code_fence = None;
             }
+            last_line_end = line_end;
+            continue;
+        }
 
-            if let Some((current_command, arguments)) = current_command_and_arguments {
-                if let Some(command_line) = strip_surrounds(line, "{%", "%}") {
-                    if command_line
-                        .trim()
-                        .strip_prefix('/')
-                        .is_some_and(|command| current_command == command)
-                    {
-                        cb(MarkdownElement::CommandBlock(CommandBlock {
-                            name: current_command,
-                            arguments,
-                            inner: RawMarkdown(&on[start..since_new_line]),
-                        }));
+        if let Some((current_command, arguments)) = current_command_and_arguments {
+            let command_line = strip_surrounds(line, "{%", "%}")
+                .filter(|inner| !(in_command_code.is_some() || inner.contains("%}")));
+            if track_fence(&mut in_command_code, line) {
+                // Commands in code are not parsed
+            } else if let Some(command_line) = command_line {
+                if let Some(closing) = command_line.strip_prefix('/') {
+                    let closing = closing.trim();
+                    let inner = inner_commands.pop();
+                    let expected = inner.as_ref().map_or(current_command, |(name, _)| name);
+                    if expected != closing {
+                        return Err(ParseError {
+                            reason: ParseErrorReason::MismatchedCommandClose {
+                                expected: expected.to_owned(),
+                                found: closing.to_owned(),
+                            },
+                            position: line_position,
+                        });
+                    }
+                    if inner.is_none() {
+                        cb(
+                            MarkdownElement::CommandBlock(CommandBlock {
+                                name: current_command,
+                                arguments,
+                                inner: RawMarkdown(&on[start..line_start]),
+                            }),
+                            block_position,
+                        );
                         current_command_and_arguments = None;
-                        start = idx + 1;
                     }
+                } else if !command_line.ends_with('/') {
+                    too_deep(inner_commands.len() + 2, line_position.clone())?;
+                    inner_commands.push((split_command(command_line).0, line_position));
                 }
-                since_new_line = idx + 1;
-                continue;
             }
+            last_line_end = line_end;
+            continue;
+        }
 
-            if in_latex_block {
-                if let "$$" = line.trim() {
-                    cb(MarkdownElement::LaTeXBlock {
-                        script: on[start..since_new_line].trim(),
-                    });
-                    in_latex_block = false;
-                    start = idx + 1;
-                }
-                since_new_line = idx + 1;
-                continue;
+        if let Some(end) = latex_block_end {
+            if end == line.trim() {
+                cb(
+                    MarkdownElement::LaTeXBlock {
+                        script: on[start..line_start].trim(),
+                    },
+                    block_position,
+                );
+                latex_block_end = None;
             }
+            last_line_end = line_end;
+            continue;
+        }
 
-            if in_markdown_comment {
-                if line.trim().ends_with("%%") {
-                    cb(MarkdownElement::CommentBlock(
-                        on[start..since_new_line].trim(),
-                    ));
-                    in_markdown_comment = false;
-                    start = idx + 1;
-                }
-                since_new_line = idx + 1;
+        if in_markdown_comment {
+            if line.trim().ends_with("%%") {
+                cb(
+                    MarkdownElement::CommentBlock(on[start..line_start].trim()),
+                    block_position,
+                );
+                in_markdown_comment = false;
+            }
+            last_line_end = line_end;
+            continue;
+        }
+
+        if let Some((extension, opening)) = custom_block {
+            let next_line = extension.next_line(opening, line);
+            if let extensions::BlockLine::Continue = next_line {
+                last_line_end = line_end;
+                continue;
+            }
+            let end = if let extensions::BlockLine::Close = next_line {
+                line_end
+            } else {
+                last_line_end
+            };
+            cb(
+                custom_element(
+                    &**extension,
+                    opening,
+                    &on[start..line_start],
+                    &on[block_start..end],
+                ),
+                block_start..end,
+            );
+            custom_block = None;
+            if let extensions::BlockLine::Close = next_line {
+                last_line_end = line_end;
                 continue;
             }
+        }
 
-            if in_table {
-                if !line.ends_with('|') {
-                    cb(MarkdownElement::Table(Table(&on[start..since_new_line])));
-                    in_table = false;
-                    start = idx + 1;
-                }
-                since_new_line = idx + 1;
+        if in_table {
+            if line.trim().starts_with('|') {
+                last_line_end = line_end;
                 continue;
             }
+            // Table ends and the line is parsed as normal
+            cb(
+                MarkdownElement::Table(Table::new(&on[start..line_start])),
+                block_start..last_line_end,
+            );
+            in_table = false;
+        }
 
-            let is_horizontal_rule = "---" == line.trim();
+        let is_horizontal_rule = "---" == line.trim();
 
-            if in_frontmatter {
-                if is_horizontal_rule {
-                    cb(MarkdownElement::Frontmatter(&on[start..since_new_line]));
-                    in_frontmatter = false;
-                }
-                since_new_line = idx + 1;
-                continue;
+        if in_frontmatter {
+            if is_horizontal_rule {
+                cb(
+                    MarkdownElement::Frontmatter(&on[start..line_start]),
+                    block_position,
+                );
+                in_frontmatter = false;
             }
+            last_line_end = line_end;
+            continue;
+        }
 
-            since_new_line = idx + 1;
+        last_line_end = line_end;
+        block_start = line_start;
+        start = next_line;
 
-            if let Some(rest) = line.trim().strip_prefix("```") {
-                // TODO other motifiers here
-                let language = rest.trim_end();
-                current_code_language = Some(language);
-            } else if let "$$" = line.trim() {
-                in_latex_block = true;
-            } else if let Some(line) = line.trim_start().strip_prefix("%%") {
-                if let Some(out) = line.trim_end().strip_suffix("%%") {
-                    cb(MarkdownElement::CommentBlock(out.trim()));
-                } else {
-                    in_markdown_comment = true;
-                }
-            } else if start == 0 && is_horizontal_rule {
-                in_frontmatter = true;
-            } else if let Some(command_line) = strip_surrounds(line, "{%", "%}") {
-                current_command_and_arguments =
-                    Some(command_line.split_once(' ').unwrap_or((command_line, "")));
+        let list_item_columns = list_item_columns(line, options.tab_width);
+        if list_item_columns.is_none() && !line.trim().is_empty() {
+            // Lines indented to the content of an item after items nested in it are more of its
+            // children
+            let indentation = indentation_columns(line, options.tab_width);
+            if let Some(level) = list
+                .content_columns
+                .iter()
+                .rposition(|content| indentation >= *content)
+            {
+                list.close_nested(level);
+                let (end, after) = list_item_children(
+                    on,
+                    line_start,
+                    list.content_columns[level],
+                    options.tab_width,
+                )
+                .unwrap_or((line_end, next_line));
+                cb(
+                    MarkdownElement::ListItem {
+                        level: u8::try_from(level).unwrap_or(u8::MAX),
+                        number: None,
+                        text: RawText(&on[line_start..line_start]),
+                        children: RawMarkdown(&on[line_start..after]),
+                        loose: list.loose.get(level).copied().unwrap_or_default(),
+                        continues: true,
+                    },
+                    line_start..end,
+                );
+                element_lines_end = after;
+                last_line_end = end;
+                continue;
+            }
+            list = ListState::default();
+        }
+
+        if let Some(extension) = options
+            .block_extensions
+            .0
+            .iter()
+            .find(|extension| extension.starts(line))
+        {
+            custom_block = Some((extension, line));
+        } else if let Some(fence) = code_fence(line) {
+            // TODO other motifiers here
+            current_code_fence = Some(fence);
+        } else if let (true, "$$") = (options.latex, line.trim()) {
+            latex_block_end = Some("$$");
+        } else if let (true, "\\[") = (options.latex, line.trim()) {
+            latex_block_end = Some("\\]");
+        } else if let Some(script) = strip_surrounds(line, "$$", "$$")
+            .filter(|script| !script.contains("$$"))
+            .or_else(|| {
+                strip_surrounds(line, "\\[", "\\]").filter(|script| !script.contains("\\]"))
+            })
+            .filter(|script| options.latex && !script.is_empty())
+        {
+            cb(MarkdownElement::LaTeXBlock { script }, line_position);
+        } else if let Some(line) = line
+            .trim_start()
+            .strip_prefix("%%")
+            .filter(|_| options.comments)
+        {
+            if let Some(out) = line.trim_end().strip_suffix("%%") {
+                cb(MarkdownElement::CommentBlock(out.trim()), line_position);
             } else {
-                let result = decide(line);
-                let to_add = !matches!(
-                    (options.include_new_lines, result),
-                    (false, MarkdownElement::Empty)
+                in_markdown_comment = true;
+            }
+        } else if options.frontmatter && line_start == content_start && is_horizontal_rule {
+            in_frontmatter = true;
+        } else if options.tables && line.trim_start().starts_with('|') {
+            in_table = true;
+            start = line_start;
+        } else if let Some(command_line) = strip_surrounds(line, "{%", "%}")
+            .filter(|inner| options.commands && !inner.contains("%}"))
+        {
+            if let Some(closing) = command_line.strip_prefix('/') {
+                return Err(ParseError {
+                    reason: ParseErrorReason::UnexpectedCommandClose {
+                        name: closing.trim().to_owned(),
+                    },
+                    position: line_position,
+                });
+            } else if let Some(command_line) = command_line.strip_suffix('/') {
+                let (name, arguments) = split_command(command_line.trim_end());
+                cb(
+                    MarkdownElement::CommandBlock(CommandBlock {
+                        name,
+                        arguments,
+                        inner: RawMarkdown(""),
+                    }),
+                    line_position,
                 );
-                if to_add {
-                    cb(result);
-                }
+            } else {
+                current_command_and_arguments = Some(split_command(command_line));
+                current_command_position = line_position;
             }
+        } else {
+            let mut result = decide(line);
+            if let MarkdownElement::Quote(_) = result {
+                too_deep(quote_depth(line), line_position.clone())?;
+            }
+            if let (true, MarkdownElement::Quote(RawMarkdown(quote))) = (options.callouts, &result)
+            {
+                result = callout(quote).unwrap_or(result);
+            }
+            let mut position = line_position;
+            if let (
+                MarkdownElement::ListItem {
+                    level,
+                    text,
+                    children,
+                    loose,
+                    ..
+                },
+                Some((marker, content)),
+            ) = (&mut result, list_item_columns)
+            {
+                let item_level = list.item(marker, content);
+                too_deep(item_level + 1, position.clone())?;
+                if list.loose.len() == item_level {
+                    let parent_content = item_level
+                        .checked_sub(1)
+                        .map_or(0, |parent| list.content_columns[parent]);
+                    list.loose
+                        .push(list_is_loose(on, line_start, parent_content, options));
+                }
+                *level = u8::try_from(item_level).unwrap_or(u8::MAX);
+                *loose = list.loose[item_level];
 
-            start = since_new_line;
+                // Following lines of text continue the item's paragraph
+                let mut children_start = next_line;
+                if let (true, false) = (options.multiline_paragraphs, text.0.is_empty()) {
+                    if let Some((end, after)) = paragraph_lines(on, next_line, options) {
+                        let text_start = line_start + line.trim_end().len() - text.0.len();
+                        *text = RawText(on[text_start..end].trim_end());
+                        position.end = end;
+                        element_lines_end = after;
+                        last_line_end = end;
+                        children_start = after;
+                    }
+                }
+                if let Some((end, after)) =
+                    list_item_children(on, children_start, content, options.tab_width)
+                {
+                    *children = RawMarkdown(&on[children_start..after]);
+                    position.end = end;
+                    element_lines_end = after;
+                    last_line_end = end;
+                }
+            } else if let (true, MarkdownElement::Paragraph(text)) =
+                (options.multiline_paragraphs, &mut result)
+            {
+                if let Some((end, after)) = paragraph_lines(on, next_line, options) {
+                    let text_start = line_start + (line.len() - line.trim_start().len());
+                    *text = RawText(on[text_start..end].trim_end());
+                    position.end = end;
+                    element_lines_end = after;
+                    last_line_end = end;
+                }
+            }
+            let to_add = !matches!(
+                (options.include_new_lines, &result),
+                (false, MarkdownElement::Empty)
+            );
+            if to_add {
+                cb(result, position);
+            }
         }
     }
 
-    if current_code_language.is_some() {
-        eprintln!("TODO error {current_code_language:?}");
-        // todo!("error here");
-    } else if in_latex_block {
-        eprintln!("TODO unclosed latex block");
+    if stopped.get() {
+        return on_stopped();
     }
 
-    if in_table {
-        cb(MarkdownElement::Table(Table(&on[start..since_new_line])));
+    if let Some((name, _)) = current_command_and_arguments {
+        let (name, position) = inner_commands
+            .pop()
+            .unwrap_or((name, current_command_position));
+        return Err(ParseError {
+            reason: ParseErrorReason::UnclosedCommand {
+                name: name.to_owned(),
+            },
+            position,
+        });
+    }
+
+    // TODO error here. Unclosed code and LaTeX blocks are currently skipped (not printed, as
+    // streaming parses incomplete sources)
+    let unclosed = if let Some((fence_length, _)) = current_code_fence {
+        Some(Unclosed::Code(fence_length))
+    } else if let Some(end) = latex_block_end {
+        Some(Unclosed::LaTeX(end))
+    } else if in_markdown_comment {
+        Some(Unclosed::Comment)
+    } else if in_frontmatter {
+        Some(Unclosed::Frontmatter)
     } else {
-        let line = &on[start..];
-        let result = decide(line);
-        let to_add = !matches!(
-            (options.include_new_lines, result),
-            (false, MarkdownElement::Empty)
+        custom_block.map(|(extension, opening)| {
+            Unclosed::Custom(std::sync::Arc::clone(extension), opening.to_owned())
+        })
+    };
+
+    if in_table {
+        cb(
+            MarkdownElement::Table(Table::new(&on[start..])),
+            block_start..last_line_end,
         );
-        if to_add {
-            cb(result);
-        }
     }
 
-    Ok(())
+    // Blocks without a closing line end with the source
+    if let Some((extension, opening)) = custom_block {
+        cb(
+            custom_element(
+                &**extension,
+                opening,
+                &on[start..],
+                &on[block_start..last_line_end],
+            ),
+            block_start..last_line_end,
+        );
+    }
+
+    on_stopped().map(|_| unclosed.map(|unclosed| (unclosed, block_start)))
+}
+
+fn custom_element<'a>(
+    extension: &dyn extensions::BlockExtension,
+    opening: &'a str,
+    body: &'a str,
+    source: &'a str,
+) -> MarkdownElement<'a> {
+    MarkdownElement::Custom(extensions::CustomBlock {
+        name: extension.name(),
+        source,
+        data: extension.element(opening, body),
+    })
 }
 
 /// Work in progress abstraction for iterating over markdown text sections giving decoration (bold, links, etc) information
@@ -430,36 +1580,69 @@ pub fn parse_with_options<'a>(
 pub struct PartsIterator<'a> {
     on: &'a str,
     last: usize,
-    in_tag: bool,
     pub in_bold: bool,
     pub in_italic: bool,
-    in_code: bool,
-    in_latex: bool,
-    in_emoji: bool,
     in_link: bool,
     in_chevron_link: bool,
     in_media: bool,
     in_expression: bool,
+    options: ParseOptions,
+    /// Built on the first `*` or `_`
+    emphasis_runs: Option<DelimiterRuns>,
+    math_closers: MathClosers,
+    /// For the `%}` of inline commands
+    command_closer: Option<Closer>,
 }
 
 impl<'a> PartsIterator<'a> {
     #[must_use]
     pub fn new(on: &'a str) -> Self {
+        Self::with_options(on, &ParseOptions::default())
+    }
+
+    #[must_use]
+    pub fn with_options(on: &'a str, options: &ParseOptions) -> Self {
         Self {
             on,
             last: 0,
-            in_tag: false,
             in_bold: false,
             in_italic: false,
-            in_emoji: false,
-            in_code: false,
-            in_latex: false,
             in_link: false,
             in_chevron_link: false,
             in_media: false,
             in_expression: false,
+            options: options.clone(),
+            emphasis_runs: None,
+            math_closers: MathClosers::default(),
+            command_closer: None,
         }
     }
+
+    /// Syntax of the first inline extension triggered by `chr` at `idx` in `range`
+    fn custom_inline(
+        &self,
+        range: &'a str,
+        idx: usize,
+        chr: char,
+    ) -> Option<extensions::CustomInline<'a>> {
+        let rest = &range[idx..];
+        let preceding = self.on[..(self.last + idx)].chars().next_back();
+        self.options
+            .inline_extensions
+            .0
+            .iter()
+            .filter(|extension| extension.triggers().contains(&chr))
+            .find_map(|extension| {
+                let (content, length) = extension.parse(rest, preceding)?;
+                // Lengths that are not of `rest` are ignored
+                let source = rest.get(..length).filter(|source| !source.is_empty())?;
+                Some(extensions::CustomInline {
+                    name: extension.name(),
+                    source,
+                    content,
+                })
+            })
+    }
 }
 
 impl<'a> Iterator for PartsIterator<'a> {
@@ -489,7 +1672,13 @@ impl<'a> Iterator for PartsIterator<'a> {
                                         to: "",
                                     });
                                 }
-                                panic!("media parsing broken {chr}");
+                                // `![alt]` without a source is text
+                                self.last += idx;
+                                self.in_media = false;
+                                let start = self.last - idx - "![".len();
+                                return Some(MarkdownTextElement::Plain(
+                                    &self.on[start..self.last],
+                                ));
                             }
                         } else if let ')' = chr {
                             let in_brackets = &range[..link_text_end];
@@ -519,32 +1708,25 @@ impl<'a> Iterator for PartsIterator<'a> {
                         }
                     } else if let '[' = chr {
                         bracket_depth += 1;
+                        // Text nested deeper than the limit is not a link, so that consumers
+                        // recursing into link text are bounded
+                        if self
+                            .options
+                            .max_nesting_depth
+                            .is_some_and(|limit| bracket_depth >= limit)
+                        {
+                            let opening = if self.in_link { "[" } else { "![" };
+                            self.in_link = false;
+                            self.in_media = false;
+                            return Some(MarkdownTextElement::Plain(
+                                &self.on[(self.last - opening.len())..self.last],
+                            ));
+                        }
                     }
 
                     continue;
                 }
 
-                // TODO escaped stuff etc
-                if self.in_code {
-                    if let '`' = chr {
-                        self.last += idx + 1;
-                        self.in_code = false;
-                        return Some(MarkdownTextElement::Code(&range[..idx]));
-                    }
-                    continue;
-                }
-                // TODO escaped stuff etc
-                if let (true, '$') = (self.in_latex, chr) {
-                    self.last += idx + 1;
-                    self.in_latex = false;
-                    return Some(MarkdownTextElement::Latex(&range[..idx]));
-                }
-                // TODO escaped stuff etc
-                if let (true, ':') = (self.in_emoji, chr) {
-                    self.last += idx + 1;
-                    self.in_emoji = false;
-                    return Some(MarkdownTextElement::Emoji(&range[..idx]));
-                }
                 // TODO escaped stuff etc
                 if let (true, '}') = (self.in_expression, chr) {
                     self.last += idx + 1;
@@ -563,10 +1745,14 @@ impl<'a> Iterator for PartsIterator<'a> {
                     });
                 }
 
-                if self.in_tag && chr.is_whitespace() {
-                    self.last += idx + 1;
-                    self.in_tag = false;
-                    return Some(MarkdownTextElement::Tag(&range[..idx]));
+                if let Some(custom) = self.custom_inline(range, idx, chr) {
+                    if idx > 0 {
+                        // Picked up on the next call
+                        self.last += idx;
+                        return Some(MarkdownTextElement::Plain(&range[..idx]));
+                    }
+                    self.last += custom.source.len();
+                    return Some(MarkdownTextElement::Custom(custom));
                 }
 
                 macro_rules! yield_current {
@@ -583,34 +1769,90 @@ impl<'a> Iterator for PartsIterator<'a> {
 
                 match chr {
                     '`' => {
-                        self.last += idx + 1;
-                        self.in_code = true;
-                        yield_current!();
+                        let opening = range[idx..].bytes().take_while(|b| *b == b'`').count();
+                        let after = &range[(idx + opening)..];
+                        if let Some(closing) = find_closing_backticks(after, opening) {
+                            if idx > 0 {
+                                // Code span is picked up on the next call
+                                self.last += idx;
+                                return Some(MarkdownTextElement::Plain(&range[..idx]));
+                            }
+                            self.last += opening + closing + opening;
+                            return Some(MarkdownTextElement::Code(strip_code_span_spaces(
+                                &after[..closing],
+                            )));
+                        }
+                        // No matching closing run, so the backticks are literal
+                        for _ in 1..opening {
+                            iterator.next();
+                        }
                     }
-                    '$' => {
-                        self.last += idx + 1;
-                        self.in_latex = true;
-                        yield_current!();
+                    '$' | '\\' if self.options.latex => {
+                        if let Some((math, length)) =
+                            inline_math(self.on, self.last + idx, &mut self.math_closers)
+                        {
+                            if idx > 0 {
+                                // Math is picked up on the next call
+                                self.last += idx;
+                                return Some(MarkdownTextElement::Plain(&range[..idx]));
+                            }
+                            self.last += length;
+                            return Some(math);
+                        } else if range[idx..].starts_with("$$") {
+                            // Unclosed `$$` is literal
+                            iterator.next();
+                        }
                     }
-                    '{' => {
+                    '{' if self.options.commands || self.options.expressions => {
+                        if let Some((command, length)) =
+                            inline_command(self.on, self.last + idx, &mut self.command_closer)
+                                .filter(|_| self.options.commands)
+                        {
+                            if idx > 0 {
+                                // Command is picked up on the next call
+                                self.last += idx;
+                                return Some(MarkdownTextElement::Plain(&range[..idx]));
+                            }
+                            self.last += length;
+                            return Some(MarkdownTextElement::Command(command));
+                        }
+                        if !self.options.expressions {
+                            continue;
+                        }
                         self.last += idx + 1;
                         self.in_expression = true;
                         yield_current!();
                     }
-                    ':' if range[(idx + 1)..]
-                        .chars()
-                        .next()
-                        .is_some_and(char::is_alphanumeric) =>
-                    {
-                        // TODO check next is not whitespace etc
-                        self.last += idx + 1;
-                        self.in_emoji = true;
-                        yield_current!();
+                    ':' if self.options.emoji => {
+                        // Unknown shortcodes are left as plain text
+                        if let Some(shortcode) = emoji_shortcode(&range[(idx + 1)..]) {
+                            if idx > 0 {
+                                // Emoji is picked up on the next call
+                                self.last += idx;
+                                return Some(MarkdownTextElement::Plain(&range[..idx]));
+                            }
+                            self.last += shortcode.len() + ":".len() * 2;
+                            return Some(MarkdownTextElement::Emoji(shortcode));
+                        }
                     }
-                    '#' => {
-                        self.last += idx + 1;
-                        self.in_tag = true;
-                        yield_current!();
+                    '#' if self.options.tags => {
+                        // Tags must start a word (not `C#` or `example.com/#fragment`)
+                        let at_word_start = self.on[..(self.last + idx)]
+                            .chars()
+                            .next_back()
+                            .is_none_or(char::is_whitespace);
+                        if let Some(tag) = at_word_start
+                            .then(|| tag_name(&range[(idx + 1)..]))
+                            .flatten()
+                        {
+                            if idx > 0 {
+                                // Tag is picked up on the next call
+                                self.last += idx;
+                                return Some(MarkdownTextElement::Plain(&range[..idx]));
+                            }
+                            self.last += "#".len() + tag.len();
+                            return Some(MarkdownTextElement::Tag(tag));
+                        }
                     }
                     '<' if range[idx..]
                         .chars()
@@ -622,18 +1864,94 @@ impl<'a> Iterator for PartsIterator<'a> {
                         yield_current!();
                     }
                     '!' if range[idx..].starts_with("![") => {
+                        if let Some((target, alias, length)) = self
+                            .options
+                            .wikilinks
+                            .then(|| wikilink(&range[(idx + 1)..]))
+                            .flatten()
+                        {
+                            if idx > 0 {
+                                // Embed is picked up on the next call
+                                self.last += idx;
+                                return Some(MarkdownTextElement::Plain(&range[..idx]));
+                            }
+                            self.last += "!".len() + length;
+                            return Some(MarkdownTextElement::Media {
+                                alt: alias.unwrap_or_default(),
+                                source: target,
+                            });
+                        }
                         self.last += idx + "![".len();
                         self.in_media = true;
                         yield_current!();
                     }
                     '[' => {
+                        if let Some((target, alias, length)) = self
+                            .options
+                            .wikilinks
+                            .then(|| wikilink(&range[idx..]))
+                            .flatten()
+                        {
+                            if idx > 0 {
+                                // Link is picked up on the next call
+                                self.last += idx;
+                                return Some(MarkdownTextElement::Plain(&range[..idx]));
+                            }
+                            self.last += length;
+                            return Some(MarkdownTextElement::Link {
+                                on: RawText(alias.unwrap_or(target)),
+                                to: target,
+                            });
+                        }
                         self.last += idx + '['.len_utf8();
                         self.in_link = true;
                         yield_current!();
                     }
                     '*' | '_' => {
                         let start = &range[idx..];
-                        if start.starts_with("**") || start.starts_with("__") {
+                        // Not a slice as the delimiter may be followed by a multibyte character
+                        let triple = start.get(..3).unwrap_or_default();
+                        let content_end = (!self.in_bold
+                            && !self.in_italic
+                            && (triple == "***" || triple == "___"))
+                            .then(|| start[3..].find(triple))
+                            .flatten()
+                            .filter(|end| *end > 0);
+                        if let Some(content_end) = content_end {
+                            if idx > 0 {
+                                // Picked up on the next call
+                                self.last += idx;
+                                return Some(MarkdownTextElement::Plain(&range[..idx]));
+                            }
+                            self.last += 3 + content_end + 3;
+                            return Some(MarkdownTextElement::BoldAndItalic(
+                                &start[3..(3 + content_end)],
+                            ));
+                        }
+                        let delimiter = if start.starts_with("**") || start.starts_with("__") {
+                            &start[..2]
+                        } else {
+                            &start[..1]
+                        };
+                        let opening = if delimiter.len() == 2 {
+                            !self.in_bold
+                        } else {
+                            !self.in_italic
+                        };
+                        // Delimiters without a closing one are literal
+                        let after = self.last + idx + delimiter.len();
+                        let on = self.on;
+                        let closed = self
+                            .emphasis_runs
+                            .get_or_insert_with(|| DelimiterRuns::new(on))
+                            .has_closing(after, delimiter);
+                        if opening && !closed {
+                            for _ in 1..delimiter.len() {
+                                iterator.next();
+                            }
+                            continue;
+                        }
+                        if delimiter.len() == 2 {
                             self.last += idx + 2;
                             self.in_bold = !self.in_bold;
                             if self.in_bold {
@@ -656,7 +1974,22 @@ impl<'a> Iterator for PartsIterator<'a> {
             }
 
             self.last = self.on.len();
-            if range.is_empty() {
+            if self.in_link || self.in_media {
+                // Brackets at the end of the text, which are not closed by a source
+                let opening = if self.in_link { "[" } else { "![" };
+                let start = self.on.len() - range.len() - opening.len();
+                let link_text = link_text_end.filter(|end| end + 1 == range.len());
+                let in_link = self.in_link;
+                self.in_link = false;
+                self.in_media = false;
+                match link_text {
+                    Some(end) if in_link => Some(MarkdownTextElement::Link {
+                        on: RawText(&range[..end]),
+                        to: "",
+                    }),
+                    _ => Some(MarkdownTextElement::Plain(&self.on[start..])),
+                }
+            } else if range.is_empty() {
                 None
             } else {
                 // TODO errors left overs. But also others such as tags etc
@@ -666,7 +1999,263 @@ impl<'a> Iterator for PartsIterator<'a> {
     }
 }
 
+/// Runs of `*` and `_` in text, so whether an emphasis delimiter is closed is found without
+/// scanning the rest of the text for each one
+struct DelimiterRuns {
+    /// Start, length and character of each run, in order
+    runs: Vec<(usize, usize, char)>,
+    /// For each run, the index of the first run from it with each of the delimiters `*`, `**`,
+    /// `_` and `__`
+    next: Vec<[Option<usize>; 4]>,
+}
+
+impl DelimiterRuns {
+    fn new(on: &str) -> Self {
+        let mut runs: Vec<(usize, usize, char)> = Vec::new();
+        for (idx, chr) in on
+            .char_indices()
+            .filter(|(_, chr)| matches!(chr, '*' | '_'))
+        {
+            match runs.last_mut() {
+                Some((start, length, run_chr)) if *run_chr == chr && *start + *length == idx => {
+                    *length += 1;
+                }
+                _ => runs.push((idx, 1, chr)),
+            }
+        }
+        let mut next = vec![[None; 4]; runs.len()];
+        let mut found = [None; 4];
+        for (idx, (_, length, chr)) in runs.iter().enumerate().rev() {
+            if let Some(slot) = Self::slot(*chr, *length) {
+                found[slot] = Some(idx);
+            }
+            next[idx] = found;
+        }
+        Self { runs, next }
+    }
+
+    fn slot(chr: char, length: usize) -> Option<usize> {
+        match (chr, length) {
+            ('*', 1) => Some(0),
+            ('*', 2) => Some(1),
+            ('_', 1) => Some(2),
+            ('_', 2) => Some(3),
+            _ => None,
+        }
+    }
+
+    /// Whether the text from `from` has a run of the delimiter character of the same length as
+    /// `delimiter` (so `*` is not closed by `**`). A run that `from` is inside of counts from `from`
+    fn has_closing(&self, from: usize, delimiter: &str) -> bool {
+        let Some(chr) = delimiter.chars().next() else {
+            return false;
+        };
+        let Some(slot) = Self::slot(chr, delimiter.len()) else {
+            return false;
+        };
+        let mut idx = self
+            .runs
+            .partition_point(|(start, length, _)| start + length <= from);
+        if let Some((start, length, run_chr)) = self.runs.get(idx) {
+            if *start < from {
+                if *run_chr == chr && start + length - from == delimiter.len() {
+                    return true;
+                }
+                idx += 1;
+            }
+        }
+        self.next.get(idx).is_some_and(|next| next[slot].is_some())
+    }
+}
+
+/// The first match of a closing delimiter at or after `from`
+#[derive(Debug, Clone, Copy)]
+struct Closer {
+    from: usize,
+    at: Option<usize>,
+}
+
+impl Closer {
+    /// Reuses the `last` search if it also applies from `from`, so that openers without a closer
+    /// do not each search the rest of the text
+    fn find(
+        last: &mut Option<Self>,
+        from: usize,
+        search: impl FnOnce(usize) -> Option<usize>,
+    ) -> Option<usize> {
+        if let Some(Closer { from: searched, at }) = *last {
+            if searched <= from && at.is_none_or(|at| at >= from) {
+                return at;
+            }
+        }
+        let at = search(from);
+        *last = Some(Closer { from, at });
+        at
+    }
+}
+
+/// Searches for the closing delimiters of math in a text
+#[derive(Debug, Default)]
+struct MathClosers {
+    dollar: Option<Closer>,
+    dollars: Option<Closer>,
+    bracket: Option<Closer>,
+    parenthesis: Option<Closer>,
+}
+
+/// For math at `at` in `on`, returns the element and its length.
+/// `$$display$$`, `\[display\]`, `\(inline\)` and `$inline$`. Following Pandoc, the opening `$` must
+/// be followed by non-whitespace and not preceded by a digit and the closing `$` must be preceded
+/// by non-whitespace and not followed by a digit (so `$5 and $10` is not math)
+fn inline_math<'a>(
+    on: &'a str,
+    at: usize,
+    closers: &mut MathClosers,
+) -> Option<(MarkdownTextElement<'a>, usize)> {
+    fn delimited<'a>(
+        on: &'a str,
+        at: usize,
+        (open, close): (&str, &str),
+        closer: &mut Option<Closer>,
+    ) -> Option<(&'a str, usize)> {
+        let from = at + open.len();
+        let end = Closer::find(closer, from, |from| {
+            on[from..].find(close).map(|idx| from + idx)
+        })?;
+        let script = &on[from..end];
+        (!script.trim().is_empty()).then_some((script, end + close.len() - at))
+    }
+
+    let rest = &on[at..];
+    if rest.starts_with("$$") {
+        let (script, length) = delimited(on, at, ("$$", "$$"), &mut closers.dollars)?;
+        Some((MarkdownTextElement::DisplayLatex(script), length))
+    } else if rest.starts_with("\\[") {
+        let (script, length) = delimited(on, at, ("\\[", "\\]"), &mut closers.bracket)?;
+        Some((MarkdownTextElement::DisplayLatex(script), length))
+    } else if rest.starts_with("\\(") {
+        let (script, length) = delimited(on, at, ("\\(", "\\)"), &mut closers.parenthesis)?;
+        Some((MarkdownTextElement::Latex(script), length))
+    } else if let Some(after) = rest.strip_prefix('$') {
+        let first = after.chars().next().filter(|chr| !chr.is_whitespace())?;
+        if on[..at].ends_with(|chr: char| chr.is_ascii_digit()) {
+            return None;
+        }
+        // Whether a `$` closes only depends on its neighbours once it is after the first character
+        let from = at + "$".len() + first.len_utf8();
+        let end = Closer::find(&mut closers.dollar, from, |from| {
+            on[from..]
+                .match_indices('$')
+                .map(|(idx, _)| from + idx)
+                .find(|idx| {
+                    let after_text = on[..*idx]
+                        .chars()
+                        .next_back()
+                        .is_some_and(|chr| !chr.is_whitespace());
+                    after_text && !on[(idx + 1)..].starts_with(|chr: char| chr.is_ascii_digit())
+                })
+        })?;
+        Some((
+            MarkdownTextElement::Latex(&on[(at + "$".len())..end]),
+            end + "$".len() - at,
+        ))
+    } else {
+        None
+    }
+}
+
+/// For `[[target]]` or `[[target|alias]]` at the start of `on`, returns the target, the alias and
+/// the length
+fn wikilink(on: &str) -> Option<(&str, Option<&str>, usize)> {
+    let rest = on.strip_prefix("[[")?;
+    let end = rest.find("]]")?;
+    let inner = &rest[..end];
+    if inner.trim().is_empty() || inner.contains(['[', ']', '\n', '\r']) {
+        return None;
+    }
+    let (target, alias) = match inner.split_once('|') {
+        Some((target, alias)) => (target, Some(alias)),
+        None => (inner, None),
+    };
+    Some((target, alias, "[[".len() + end + "]]".len()))
+}
+
+/// For `{% name arguments /%}` at `at` in `on`, returns the command and its length
+fn inline_command<'a>(
+    on: &'a str,
+    at: usize,
+    closer: &mut Option<Closer>,
+) -> Option<(CommandBlock<'a>, usize)> {
+    if !on[at..].starts_with("{%") {
+        return None;
+    }
+    let from = at + "{%".len();
+    let end = Closer::find(closer, from, |from| {
+        on[from..].find("%}").map(|idx| from + idx)
+    })?;
+    let command_line = on[from..end].trim_end().strip_suffix('/')?;
+    let (name, arguments) = split_command(command_line.trim());
+    let command = CommandBlock {
+        name,
+        arguments,
+        inner: RawMarkdown(""),
+    };
+    Some((command, end + "%}".len() - at))
+}
+
+/// Returns the tag name if `on` starts with one. Following Obsidian, tags are made of letters, digits,
+/// `_`, `-` and `/` (for nesting), end at any other character and cannot be only digits (`#12`)
+fn tag_name(on: &str) -> Option<&str> {
+    let length = on
+        .char_indices()
+        .find(|(_, chr)| !(chr.is_alphanumeric() || matches!(chr, '_' | '-' | '/')))
+        .map_or(on.len(), |(idx, _)| idx);
+    let tag = &on[..length];
+    tag.chars().any(|chr| !chr.is_ascii_digit()).then_some(tag)
+}
+
+/// Returns the shortcode if `on` starts with a known `shortcode:`
+fn emoji_shortcode(on: &str) -> Option<&str> {
+    let length = on
+        .bytes()
+        .take_while(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b"_+-".contains(b))
+        .count();
+    let shortcode = &on[..length];
+    let closed = on[length..].starts_with(':');
+    (closed && emoji::shortcode_to_unicode(shortcode).is_some()).then_some(shortcode)
+}
+
+/// Finds the start of a backtick run of exactly `length` in `on`
+fn find_closing_backticks(on: &str, length: usize) -> Option<usize> {
+    let bytes = on.as_bytes();
+    let mut idx = 0;
+    while idx < bytes.len() {
+        if bytes[idx] == b'`' {
+            let run = bytes[idx..].iter().take_while(|b| **b == b'`').count();
+            if run == length {
+                return Some(idx);
+            }
+            idx += run;
+        } else {
+            idx += 1;
+        }
+    }
+    None
+}
+
+/// From `CommonMark`: a single leading and trailing space is removed if both are present
+/// (so that spans can start or end with backticks), unless the content is only spaces
+fn strip_code_span_spaces(content: &str) -> &str {
+    let surrounded = content.len() > 2 && content.starts_with(' ') && content.ends_with(' ');
+    if surrounded && !content.bytes().all(|b| b == b' ') {
+        &content[1..(content.len() - 1)]
+    } else {
+        content
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct RawMarkdown<'a>(pub &'a str);
 
 // #[cfg(target_family = "wasm")]
@@ -681,14 +2270,65 @@ pub struct RawMarkdown<'a>(pub &'a str);
 // }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
-pub struct Table<'a>(pub(crate) &'a str);
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(transparent))]
+pub struct Table<'a> {
+    pub(crate) source: &'a str,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) continues: bool,
+}
 
 impl<'a> Table<'a> {
+    pub(crate) fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            continues: false,
+        }
+    }
+
+    fn lines(&self) -> impl Iterator<Item = &'a str> {
+        let source = self.source;
+        lines(source, 0).map(move |(start, end, _)| &source[start..end])
+    }
+
+    /// Whether the rows follow on from the previous table. [`crate::streaming`] gives long tables
+    /// in parts, each with the header and separator rows of the first
+    #[must_use]
+    pub fn continues(&self) -> bool {
+        self.continues
+    }
+
     pub fn rows(&self) -> impl Iterator<Item = TableRow<'a>> {
-        let mut lines = self.0.lines();
-        let header = lines.next().expect("no heading (empty table)");
-        std::iter::once(TableRow(header)).chain(lines.skip(1).map(TableRow))
+        // The second line is the separator row
+        let mut lines = self.lines();
+        let header = lines.next();
+        header.into_iter().chain(lines.skip(1)).map(TableRow)
     }
+
+    /// Of each column, from the `:`s in the separator row. `None` for columns without any
+    pub fn alignments(&self) -> impl Iterator<Item = Option<Alignment>> + 'a {
+        let separator = self.lines().nth(1).unwrap_or_default();
+        TableRow(separator).cells().map(|cell| {
+            let cell = cell.0.trim();
+            match (cell.starts_with(':'), cell.len() > 1 && cell.ends_with(':')) {
+                (true, true) => Some(Alignment::Center),
+                (true, false) => Some(Alignment::Left),
+                (false, true) => Some(Alignment::Right),
+                (false, false) => None,
+            }
+        })
+    }
+}
+
+/// [`Table::alignments`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Alignment {
+    /// `:--`
+    Left,
+    /// `:-:`
+    Center,
+    /// `--:`
+    Right,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -696,11 +2336,14 @@ pub struct TableRow<'a>(pub(crate) &'a str);
 
 impl<'a> TableRow<'a> {
     pub fn cells(&self) -> impl Iterator<Item = RawText<'a>> {
-        let inner = &self.0[1..(self.0.len() - 1)];
+        let inner = self.0.trim();
+        let inner = inner.strip_prefix('|').unwrap_or(inner);
+        let inner = inner.strip_suffix('|').unwrap_or(inner);
         inner.split('|').map(RawText)
     }
 }
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct CommandBlock<'a> {
     pub name: &'a str,
     pub arguments: &'a str,
@@ -708,40 +2351,19 @@ pub struct CommandBlock<'a> {
 }
 
 impl<'a> CommandBlock<'a> {
-    #[must_use]
-    #[allow(clippy::collapsible_else_if)]
-    pub fn arguments(&self) -> Vec<(&'a str, &'a str)> {
-        let mut arguments = Vec::new();
-        let mut key: Option<&str> = None;
-        let mut start = 0;
-        let mut in_string = false;
-
-        for (idx, chr) in self.arguments.char_indices() {
-            if let Some(current_key) = key {
-                let value = self.arguments[start..idx].trim();
-                if let (' ', false, false) = (chr, in_string, value.is_empty()) {
-                    arguments.push((current_key, value));
-                    start = idx;
-                    key = None;
-                } else if let '"' = chr {
-                    in_string = !in_string;
-                }
-            } else {
-                if let '=' = chr {
-                    let key_acc = &self.arguments[start..idx];
-                    key = Some(key_acc.trim());
-                    start = idx + 1;
-                }
-            }
-        }
-        if let Some(current_key) = key {
-            if in_string {
-                eprintln!("missing '\"'");
-            }
-            let value = self.arguments[start..].trim();
-            arguments.push((current_key, value));
-        }
+    /// # Errors
+    /// for malformed argument lists
+    pub fn arguments(&self) -> Result<arguments::CommandArguments<'a>, arguments::ArgumentsError> {
+        arguments::CommandArguments::parse(self.arguments)
+    }
 
-        arguments
+    /// [`CommandBlock::arguments`] with the limits of `options`
+    /// # Errors
+    /// for malformed arguments
+    pub fn arguments_with_options(
+        &self,
+        options: &ParseOptions,
+    ) -> Result<arguments::CommandArguments<'a>, arguments::ArgumentsError> {
+        arguments::CommandArguments::parse_with_options(self.arguments, options)
     }
 }
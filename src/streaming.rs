@@ -0,0 +1,552 @@
+//! Parsing of sources that arrive in chunks (such as from a [`BufRead`]). Elements are given as
+//! soon as they are complete and only the source of incomplete elements is kept, so memory is
+//! bounded by the largest block rather than the whole source (long lists and tables are given in
+//! parts, see [`StreamingParser::split_after`])
+//!
+//! ```
+//! use simple_markdown_parser::streaming::StreamingParser;
+//! use simple_markdown_parser::ParseOptions;
+//!
+//! let mut parser = StreamingParser::new(&ParseOptions::default());
+//! let mut elements = Vec::new();
+//! for chunk in ["# Fi", "rst\nSome text\n# Sec", "ond\n"] {
+//!     parser
+//!         .push(chunk, |element| elements.push(format!("{element:?}")))
+//!         .unwrap();
+//! }
+//! parser.finish(|element| elements.push(format!("{element:?}"))).unwrap();
+//! assert_eq!(elements.len(), 3);
+//! ```
+
+use std::io::BufRead;
+
+use crate::{
+    continues_paragraph, indentation_columns, lines, list_item_columns, parse_lines,
+    restarts_parsing, strip_surrounds, track_fence, ListState, MarkdownElement, ParseError,
+    ParseErrorReason, ParseOptions, Unclosed,
+};
+
+pub struct StreamingParser {
+    options: ParseOptions,
+    /// Source not yet given as elements. After the first elements, starts with the line ending
+    /// before the next element so that it is not parsed as the start of a document (frontmatter)
+    buffer: String,
+    /// Offset in `buffer` to parse from
+    from: usize,
+    /// End of the lines in `buffer` that have been parsed or could not change `pending`
+    parsed: usize,
+    pending: Pending,
+    /// Items of the list that `from` is inside of
+    list: ListState,
+    /// Whether the table at `from` continues the last one given, whose header and separator rows
+    /// are kept
+    table_continues: bool,
+    /// Lists and tables with more source than this are given in parts
+    split_after: usize,
+    /// Length of source from `from` at which to parse again, even if lines could not change
+    /// `pending`
+    parse_at: usize,
+    /// Offset of `buffer` in the whole source, for error positions
+    offset: usize,
+    /// Offset in `buffer` and length of the rows removed from a table given in parts
+    removed_rows: Option<(usize, usize)>,
+    /// Elements given so far, for [`ParseOptions::max_elements`]
+    emitted: usize,
+}
+
+/// What later lines can change, from the elements after the ones given
+enum Pending {
+    /// Any line can add elements
+    Any,
+    /// Only the line ending the block
+    Block(Unclosed),
+    /// Only closing tags
+    Command,
+    /// Lines that are not rows
+    Table,
+    /// Lines that do not continue the paragraph
+    Paragraph,
+    List(ListLines),
+}
+
+impl Pending {
+    fn changed_by(&mut self, line: &str, options: &ParseOptions) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Block(block) => block.ended_by(line),
+            Self::Command => {
+                strip_surrounds(line, "{%", "%}").is_some_and(|inner| inner.starts_with('/'))
+            }
+            Self::Table => !line.trim().starts_with('|'),
+            Self::Paragraph => !continues_paragraph(line, options),
+            Self::List(list) => list.changed_by(line, options.tab_width),
+        }
+    }
+}
+
+/// Lines after the last item of a list
+struct ListLines {
+    /// Of the last item
+    content_column: usize,
+    /// Fence length of a code block in the children of the last item
+    in_code: Option<usize>,
+    blank_before: bool,
+    /// Whether items are given as they complete (otherwise the list could still become loose)
+    loose: bool,
+}
+
+impl ListLines {
+    /// From the item starting at `item` and the lines after it
+    fn new(on: &str, item: usize, loose: bool, tab_width: usize) -> Self {
+        let mut item_lines = lines(on, item);
+        // Continued items have no marker, so their children are from their indentation
+        let content_column = item_lines.next().map_or(0, |(start, end, _)| {
+            let line = &on[start..end];
+            list_item_columns(line, tab_width).map_or_else(
+                || indentation_columns(line, tab_width),
+                |(_, content)| content,
+            )
+        });
+        let mut list = Self {
+            content_column,
+            in_code: None,
+            blank_before: false,
+            loose,
+        };
+        for (start, end, _) in item_lines {
+            list.changed_by(&on[start..end], tab_width);
+        }
+        list
+    }
+
+    fn changed_by(&mut self, line: &str, tab_width: usize) -> bool {
+        if line.trim().is_empty() {
+            self.blank_before = self.in_code.is_none();
+            return false;
+        }
+        // Can make the list loose
+        if std::mem::take(&mut self.blank_before) {
+            return true;
+        }
+        if let Some((_, content)) =
+            list_item_columns(line, tab_width).filter(|_| self.in_code.is_none())
+        {
+            // Completes the previous item
+            self.content_column = content;
+            return self.loose;
+        }
+        // Ends the list (or continues the text of the item)
+        if indentation_columns(line, tab_width) < self.content_column {
+            return true;
+        }
+        track_fence(&mut self.in_code, line);
+        false
+    }
+}
+
+impl StreamingParser {
+    #[must_use]
+    pub fn new(options: &ParseOptions) -> Self {
+        let split_after = 64 * 1024;
+        Self {
+            options: options.clone(),
+            buffer: String::new(),
+            from: 0,
+            parsed: 0,
+            pending: Pending::Any,
+            list: ListState::default(),
+            table_continues: false,
+            split_after,
+            parse_at: split_after,
+            offset: 0,
+            removed_rows: None,
+            emitted: 0,
+        }
+    }
+
+    /// Lists and tables with more than `bytes` of source are given in parts, so that memory stays
+    /// bounded. Items of a list are then given once the next item starts, as loose if the list was
+    /// loose before the split. Tables are given as parts that each [`crate::Table::continues`] the
+    /// previous one. Defaults to 64 KiB
+    #[must_use]
+    pub fn split_after(mut self, bytes: usize) -> Self {
+        self.split_after = bytes;
+        self.parse_at = bytes;
+        self
+    }
+
+    /// Bytes of source kept for elements that are not yet complete
+    #[must_use]
+    pub fn buffered(&self) -> usize {
+        self.buffer.len() - self.from
+    }
+
+    /// Adds the next part of the source, calling `cb` with the elements that are now complete.
+    /// Elements borrow from the parser and so only live for the callback. Lines are only parsed
+    /// again if they could change the elements after the given ones
+    /// # Errors
+    /// errors for mismatched command tags and exceeded limits. A line longer than
+    /// [`ParseOptions::max_line_length`] errors before it is complete, so it is not buffered
+    pub fn push(
+        &mut self,
+        chunk: &str,
+        mut cb: impl FnMut(MarkdownElement<'_>),
+    ) -> Result<(), ParseError> {
+        self.buffer.push_str(chunk);
+
+        // Only whole lines are parsed. A `\r` at the end could be the start of `\r\n`
+        let without_return = self.buffer.strip_suffix('\r').unwrap_or(&self.buffer);
+        let lines_end = without_return.rfind(['\n', '\r']).map_or(0, |end| end + 1);
+        let last_line = lines_end.max(self.parsed)..without_return.len();
+        if let Some(limit) = self
+            .options
+            .max_line_length
+            .filter(|limit| last_line.len() > *limit)
+        {
+            return Err(self.offset_error(ParseError {
+                reason: ParseErrorReason::LineTooLong { limit },
+                position: last_line,
+            }));
+        }
+        if lines_end <= self.parsed {
+            return Ok(());
+        }
+
+        let mut changed = lines_end - self.from >= self.parse_at;
+        for (line_start, line_end, _) in lines(&self.buffer[..lines_end], self.parsed) {
+            if let Some(limit) = self
+                .options
+                .max_line_length
+                .filter(|limit| line_end - line_start > *limit)
+            {
+                return Err(self.offset_error(ParseError {
+                    reason: ParseErrorReason::LineTooLong { limit },
+                    position: line_start..line_end,
+                }));
+            }
+            changed = changed
+                || self
+                    .pending
+                    .changed_by(&self.buffer[line_start..line_end], &self.options);
+        }
+        self.parsed = lines_end;
+        if changed {
+            self.parse(lines_end, &mut cb)?;
+        }
+        Ok(())
+    }
+
+    /// Parses the lines up to `lines_end` and gives the elements that later lines cannot change
+    #[allow(clippy::too_many_lines)]
+    fn parse(
+        &mut self,
+        lines_end: usize,
+        cb: &mut impl FnMut(MarkdownElement<'_>),
+    ) -> Result<(), ParseError> {
+        // Elements after the complete ones can change with later chunks, so are only counted once
+        // they are given
+        let options = self.options.clone().max_elements(None);
+        let mut elements = Vec::new();
+        let result = parse_lines(
+            &self.buffer[..lines_end],
+            self.from,
+            self.list.clone(),
+            &options,
+            |element, position| {
+                elements.push((element, position));
+                std::ops::ControlFlow::Continue(())
+            },
+        );
+        let (unclosed, in_command) = match result {
+            Ok(unclosed) => (unclosed, false),
+            // Can be closed by later chunks
+            Err(ParseError {
+                reason: ParseErrorReason::UnclosedCommand { .. },
+                ..
+            }) => (None, true),
+            Err(error) => return Err(self.offset_error(error)),
+        };
+
+        // Later lines cannot change elements before a list item, or before a line that could be
+        // part of a list (an indented line can make the list loose)
+        let complete = elements
+            .iter()
+            .rposition(|(element, position)| {
+                restarts_parsing(element) && !self.buffer[position.start..].starts_with([' ', '\t'])
+            })
+            .unwrap_or(0);
+        let resume = elements
+            .get(complete)
+            .map_or(self.from, |(_, position)| position.start);
+        // Elements from `keep` are parsed again, from `resume`
+        let (keep, resume, pending, split_rows) = if in_command {
+            (complete, resume, Pending::Command, None)
+        } else if let Some((block, start)) = unclosed {
+            // Elements before the block are complete
+            let keep = elements.partition_point(|(_, position)| position.start < start);
+            (keep, start, Pending::Block(block), None)
+        } else {
+            let last = elements
+                .iter()
+                .rposition(|(element, _)| !matches!(element, MarkdownElement::Empty));
+            let split = lines_end - self.from >= self.split_after;
+            match last.map(|last| (last, &elements[last])) {
+                Some((last, (MarkdownElement::ListItem { loose, .. }, position))) => {
+                    let item_lines = ListLines::new(
+                        &self.buffer[..lines_end],
+                        position.start,
+                        *loose,
+                        self.options.tab_width,
+                    );
+                    if *loose || split {
+                        (last, position.start, Pending::List(item_lines), None)
+                    } else {
+                        (complete, resume, Pending::List(item_lines), None)
+                    }
+                }
+                Some((last, (MarkdownElement::Table(_), position)))
+                    if matches!(&self.buffer[position.end..lines_end], "\n" | "\r\n" | "\r") =>
+                {
+                    let rows_start = lines(&self.buffer, position.start)
+                        .nth(1)
+                        .map_or(lines_end, |(_, _, next_line)| next_line);
+                    if split && rows_start < lines_end {
+                        (last + 1, position.start, Pending::Table, Some(rows_start))
+                    } else {
+                        (complete, resume, Pending::Table, None)
+                    }
+                }
+                Some((_, (MarkdownElement::Paragraph(_), position)))
+                    if self.options.multiline_paragraphs
+                        && matches!(
+                            &self.buffer[position.end..lines_end],
+                            "\n" | "\r\n" | "\r"
+                        ) =>
+                {
+                    (complete, resume, Pending::Paragraph, None)
+                }
+                _ => (complete, resume, Pending::Any, None),
+            }
+        };
+        self.pending = pending;
+
+        if keep > 0 {
+            if let Some(limit) = self.options.max_elements {
+                if let Some((_, position)) = elements[..keep].get(limit - self.emitted) {
+                    return Err(self.offset_error(ParseError {
+                        reason: ParseErrorReason::TooManyElements { limit },
+                        position: position.clone(),
+                    }));
+                }
+            }
+
+            // Parsing resumes inside the list if it is split
+            let mut list = self.list.clone();
+            for (element, position) in &elements[..keep] {
+                match element {
+                    MarkdownElement::ListItem {
+                        level,
+                        loose,
+                        continues,
+                        ..
+                    } => {
+                        let level = usize::from(*level);
+                        let line = lines(&self.buffer, position.start)
+                            .next()
+                            .map_or("", |(start, end, _)| &self.buffer[start..end]);
+                        if let (false, Some((marker, content))) =
+                            (*continues, list_item_columns(line, self.options.tab_width))
+                        {
+                            list.item(marker, content);
+                        } else {
+                            list.close_nested(level);
+                        }
+                        list.loose.resize(level, false);
+                        list.loose.push(*loose);
+                    }
+                    MarkdownElement::Empty => {}
+                    _ => list = ListState::default(),
+                }
+            }
+            let in_list = matches!(
+                elements.get(keep),
+                Some((MarkdownElement::ListItem { .. }, _))
+            );
+            self.list = if in_list { list } else { ListState::default() };
+
+            self.emitted += keep;
+            for (mut element, position) in elements.drain(..keep) {
+                if let (MarkdownElement::Table(table), true) = (
+                    &mut element,
+                    self.table_continues && position.start == self.from,
+                ) {
+                    table.continues = true;
+                }
+                cb(element);
+            }
+            self.table_continues = false;
+            drop(elements);
+
+            self.drain_to(resume);
+            if let Some(rows_start) = split_rows {
+                self.remove_rows(rows_start - (resume - self.from));
+            }
+        }
+        let pending = self.parsed - self.from;
+        self.parse_at = if pending < self.split_after {
+            self.split_after
+        } else {
+            2 * pending
+        };
+        Ok(())
+    }
+
+    /// Removes the source before `resume`, apart from the line ending before it
+    fn drain_to(&mut self, resume: usize) {
+        let drained = resume
+            .checked_sub(1)
+            .filter(|end| matches!(self.buffer.as_bytes()[*end], b'\n' | b'\r'))
+            .unwrap_or(0);
+        self.buffer.drain(..drained);
+        self.from = resume - drained;
+        self.parsed -= drained;
+        self.offset += drained;
+        self.removed_rows = match self.removed_rows {
+            Some((at, length)) if at > drained => Some((at - drained, length)),
+            Some((_, length)) => {
+                self.offset += length;
+                None
+            }
+            None => None,
+        };
+    }
+
+    /// Removes the given rows of the table at `from`, keeping its header and separator rows for
+    /// the next part
+    fn remove_rows(&mut self, rows_start: usize) {
+        let length = self.parsed - rows_start;
+        self.buffer.replace_range(rows_start..self.parsed, "");
+        self.parsed = rows_start;
+        // Rows of the same table are always removed from after its separator row
+        let removed = self.removed_rows.map_or(0, |(_, removed)| removed);
+        self.removed_rows = Some((rows_start, removed + length));
+        self.table_continues = true;
+    }
+
+    /// Parses the rest of the source
+    /// # Errors
+    /// errors for unclosed blocks, mismatched command tags and exceeded limits
+    pub fn finish(self, mut cb: impl FnMut(MarkdownElement<'_>)) -> Result<(), ParseError> {
+        let options = self
+            .options
+            .clone()
+            .max_elements(self.options.max_elements.map(|max| max - self.emitted));
+        parse_lines(
+            &self.buffer,
+            self.from,
+            self.list.clone(),
+            &options,
+            |mut element, position| {
+                if let (MarkdownElement::Table(table), true) = (
+                    &mut element,
+                    self.table_continues && position.start == self.from,
+                ) {
+                    table.continues = true;
+                }
+                cb(element);
+                std::ops::ControlFlow::Continue(())
+            },
+        )
+        .map(|_| ())
+        .map_err(|error| self.offset_error(error).with_limits(&self.options))
+    }
+
+    fn offset_error(&self, error: ParseError) -> ParseError {
+        let offset = |position: usize| {
+            let removed = match self.removed_rows {
+                Some((at, length)) if position >= at => length,
+                _ => 0,
+            };
+            position + self.offset + removed
+        };
+        ParseError {
+            position: offset(error.position.start)..offset(error.position.end),
+            ..error
+        }
+    }
+}
+
+/// Parses everything from `reader`, calling `cb` with each element once it is complete
+/// # Errors
+/// errors from reading (including invalid UTF-8) and parsing
+pub fn parse_reader(
+    mut reader: impl BufRead,
+    options: &ParseOptions,
+    mut cb: impl FnMut(MarkdownElement<'_>),
+) -> Result<(), StreamError> {
+    let mut parser = StreamingParser::new(options);
+    // Bytes read but not yet parsed, as a character can be split between reads
+    let mut bytes = Vec::new();
+    loop {
+        let read = reader.fill_buf()?;
+        if read.is_empty() {
+            break;
+        }
+        let length = read.len();
+        bytes.extend_from_slice(read);
+        reader.consume(length);
+
+        let valid = match std::str::from_utf8(&bytes) {
+            Ok(chunk) => chunk.len(),
+            Err(error) if error.error_len().is_none() => error.valid_up_to(),
+            Err(error) => {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, error).into())
+            }
+        };
+        let chunk = std::str::from_utf8(&bytes[..valid])
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))?;
+        parser.push(chunk, &mut cb)?;
+        bytes.drain(..valid);
+    }
+    if let Err(error) = std::str::from_utf8(&bytes) {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, error).into());
+    }
+    parser.finish(cb)?;
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum StreamError {
+    Io(std::io::Error),
+    Parse(ParseError),
+}
+
+impl From<std::io::Error> for StreamError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<ParseError> for StreamError {
+    fn from(error: ParseError) -> Self {
+        Self::Parse(error)
+    }
+}
+
+impl std::fmt::Display for StreamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StreamError::Io(error) => write!(f, "could not read source: {error}"),
+            StreamError::Parse(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for StreamError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StreamError::Io(error) => Some(error),
+            StreamError::Parse(error) => Some(error),
+        }
+    }
+}
@@ -1,15 +1,14 @@
-use super::{parse, MarkdownElement, RawText};
+use super::{parse, MarkdownElement, MarkdownTextElement, ParseError, ParseOptions, RawText};
 
 #[cfg(target_family = "wasm")]
 use wasm_bindgen::prelude::*;
 
 /// # Errors
 /// errors from markdown parsing
-#[allow(clippy::result_unit_err)]
 pub fn parse_with_header_information<'a>(
     on: &'a str,
     mut cb: impl for<'b> FnMut(&'b Vec<RawText<'a>>, MarkdownElement<'a>),
-) -> Result<(), ()> {
+) -> Result<(), ParseError> {
     let mut header_chain = Vec::new();
     parse(on, |element| {
         if let MarkdownElement::Heading { level, text } = element {
@@ -29,11 +28,10 @@ pub fn parse_with_header_information<'a>(
 
 /// # Errors
 /// errors from markdown parsing
-#[allow(clippy::result_unit_err)]
 pub fn parse_blocks<'a>(
     on: &'a str,
     mut cb: impl for<'b> FnMut(&'b Vec<RawText<'a>>, &'b [MarkdownElement<'a>]),
-) -> Result<(), ()> {
+) -> Result<(), ParseError> {
     let mut header_chain = Vec::new();
     let mut inner = Vec::new();
 
@@ -120,12 +118,17 @@ pub fn extract_code_blocks(on: &str) -> VecCodeBlock {
             header_chain.push(text);
         } else if let MarkdownElement::CodeBlock { language, code } = element {
             language.clone_into(&mut current_block.language);
-            code.clone_into(&mut current_block.code);
+            current_block.code = code.to_string();
         } else if let MarkdownElement::Paragraph(content) = element {
             current_block.information.push_str(content.0);
         } else if let MarkdownElement::Quote(content) = element {
             current_block.information.push_str(content.0);
-        } else if let MarkdownElement::ListItem { level: _, text } = element {
+        } else if let MarkdownElement::ListItem {
+            text,
+            continues: false,
+            ..
+        } = element
+        {
             current_block.items.push(text.0.to_owned());
         }
     });
@@ -234,6 +237,124 @@ pub fn extract_slides(on: &str) -> Vec<Slide> {
     slides
 }
 
+/// A `#tag` and the byte range of it (including the `#`) in the source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagOccurrence<'a> {
+    pub tag: &'a str,
+    pub position: std::ops::Range<usize>,
+}
+
+/// All tags in text elements (including in link text), in source order
+#[must_use]
+pub fn collect_tags(on: &str) -> Vec<TagOccurrence<'_>> {
+    collect_tags_with_options(on, &ParseOptions::default())
+}
+
+/// [`collect_tags`] with the syntax of `options`. Tags are found in the text of elements, list item
+/// children, command bodies, table cells and emphasis and link text
+#[must_use]
+pub fn collect_tags_with_options<'a>(
+    on: &'a str,
+    options: &ParseOptions,
+) -> Vec<TagOccurrence<'a>> {
+    let mut collector = TagCollector {
+        on,
+        options,
+        tags: Vec::new(),
+    };
+    collector.markdown(on, &|idx| idx);
+    collector.tags
+}
+
+struct TagCollector<'a, 'o> {
+    on: &'a str,
+    options: &'o ParseOptions,
+    tags: Vec<TagOccurrence<'a>>,
+}
+
+impl TagCollector<'_, '_> {
+    /// Tags in `buffer`, where `to_source` maps a byte offset in `buffer` to one in the source
+    fn markdown(&mut self, buffer: &str, to_source: &dyn Fn(usize) -> usize) {
+        let offset = |slice: &str| slice.as_ptr() as usize - buffer.as_ptr() as usize;
+        let _result = crate::parse_with_options(buffer, self.options, |element| {
+            if let Some(text) = element.parts_like() {
+                self.text(text.0, &|idx| to_source(offset(text.0) + idx), 0);
+            }
+            match element {
+                MarkdownElement::ListItem { children, .. } if !children.0.is_empty() => {
+                    // Parsed without their indentation, so lines are mapped back
+                    let mut dedented = String::new();
+                    let mut pieces = Vec::new();
+                    let mut line_start = 0;
+                    let lines = children.0.split_inclusive('\n');
+                    for (line, dedented_line) in
+                        lines.zip(crate::document::dedented_lines(children.0))
+                    {
+                        let removed = line.len() - dedented_line.len();
+                        pieces.push((dedented.len(), line_start + removed));
+                        dedented.push_str(dedented_line);
+                        line_start += line.len();
+                    }
+                    let children_start = offset(children.0);
+                    self.markdown(&dedented, &|idx| {
+                        let piece = pieces.partition_point(|(start, _)| *start <= idx) - 1;
+                        let (dedented_start, start) = pieces[piece];
+                        to_source(children_start + start + (idx - dedented_start))
+                    });
+                }
+                MarkdownElement::CommandBlock(command) => {
+                    let inner = command.inner.0;
+                    self.markdown(inner, &|idx| to_source(offset(inner) + idx));
+                }
+                MarkdownElement::Table(table) => {
+                    for row in table.rows() {
+                        for cell in row.cells() {
+                            self.text(cell.0, &|idx| to_source(offset(cell.0) + idx), 0);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        });
+    }
+
+    /// Tags in `text` at `depth` in emphasis and links, where `to_source` maps a byte offset in
+    /// `text` to one in the source
+    fn text(&mut self, text: &str, to_source: &dyn Fn(usize) -> usize, depth: usize) {
+        let offset = |slice: &str| slice.as_ptr() as usize - text.as_ptr() as usize;
+        for part in RawText(text).parts_with_options(self.options) {
+            let inner = match part {
+                MarkdownTextElement::Tag(tag) => {
+                    let start = to_source(offset(tag)) - "#".len();
+                    let end = start + "#".len() + tag.len();
+                    self.tags.push(TagOccurrence {
+                        tag: &self.on[(start + "#".len())..end],
+                        position: start..end,
+                    });
+                    continue;
+                }
+                MarkdownTextElement::Link { on, .. } => on.0,
+                MarkdownTextElement::Bold(inner)
+                | MarkdownTextElement::Italic(inner)
+                | MarkdownTextElement::BoldAndItalic(inner)
+                | MarkdownTextElement::StrikeThrough(inner)
+                | MarkdownTextElement::Highlight(inner)
+                | MarkdownTextElement::Superscript(inner)
+                | MarkdownTextElement::Subscript(inner) => inner,
+                _ => continue,
+            };
+            // Bounded like nested link text
+            if self
+                .options
+                .max_nesting_depth
+                .is_none_or(|limit| depth < limit)
+            {
+                self.text(inner, &|idx| to_source(offset(inner) + idx), depth + 1);
+            }
+        }
+    }
+}
+
 pub mod lexical_analysis {
     use super::parse;
 
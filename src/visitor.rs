@@ -0,0 +1,361 @@
+//! Traversal of [`Document`] trees. Methods default to walking their children (with the `walk_*`
+//! functions), so implementations only override the kinds they are interested in
+//!
+//! ```
+//! use simple_markdown_parser::document::{Document, Inline};
+//! use simple_markdown_parser::visitor::VisitorMut;
+//!
+//! struct MarkdownLinksToHtml;
+//!
+//! impl VisitorMut for MarkdownLinksToHtml {
+//!     fn visit_link(&mut self, _content: &mut Vec<Inline>, to: &mut String) {
+//!         if let Some(stem) = to.strip_suffix(".md") {
+//!             *to = format!("{stem}.html");
+//!         }
+//!     }
+//! }
+//!
+//! let mut document = Document::parse("See [other](./other.md)").unwrap();
+//! MarkdownLinksToHtml.visit_document(&mut document);
+//! ```
+
+use crate::document::{Block, Command, Document, Heading, Inline, List, ListItem, Section, Table};
+
+pub trait Visitor {
+    fn visit_document(&mut self, document: &Document) {
+        walk_document(self, document);
+    }
+
+    /// Every sequence of blocks (document, sections, quotes, list items and commands)
+    fn visit_blocks(&mut self, blocks: &[Block]) {
+        walk_blocks(self, blocks);
+    }
+
+    fn visit_block(&mut self, block: &Block) {
+        walk_block(self, block);
+    }
+
+    fn visit_section(&mut self, section: &Section) {
+        walk_section(self, section);
+    }
+
+    fn visit_heading(&mut self, heading: &Heading) {
+        self.visit_inlines(&heading.content);
+    }
+
+    fn visit_paragraph(&mut self, content: &[Inline]) {
+        self.visit_inlines(content);
+    }
+
+    fn visit_quote(&mut self, children: &[Block]) {
+        self.visit_blocks(children);
+    }
+
+    fn visit_callout(&mut self, _kind: &str, title: &[Inline]) {
+        self.visit_inlines(title);
+    }
+
+    fn visit_list(&mut self, list: &List) {
+        for item in &list.items {
+            self.visit_list_item(item);
+        }
+    }
+
+    fn visit_list_item(&mut self, item: &ListItem) {
+        self.visit_inlines(&item.content);
+        self.visit_blocks(&item.children);
+    }
+
+    fn visit_table(&mut self, table: &Table) {
+        for cell in table.header.iter().chain(table.rows.iter().flatten()) {
+            self.visit_inlines(cell);
+        }
+    }
+
+    fn visit_code_block(&mut self, _language: &str, _code: &str) {}
+
+    fn visit_latex_block(&mut self, _script: &str) {}
+
+    /// Block and inline commands
+    fn visit_command(&mut self, command: &Command) {
+        self.visit_blocks(&command.children);
+    }
+
+    fn visit_comment(&mut self, _comment: &str) {}
+
+    fn visit_html_element(&mut self, _element: &str) {}
+
+    fn visit_frontmatter(&mut self, _frontmatter: &str) {}
+
+    fn visit_horizontal_rule(&mut self) {}
+
+    fn visit_footnote(&mut self) {}
+
+    /// Blocks of [`crate::extensions::BlockExtension`]s
+    fn visit_custom_block(&mut self, _name: &str, _arguments: &str, _content: &str) {}
+
+    fn visit_inlines(&mut self, inlines: &[Inline]) {
+        for inline in inlines {
+            self.visit_inline(inline);
+        }
+    }
+
+    fn visit_inline(&mut self, inline: &Inline) {
+        walk_inline(self, inline);
+    }
+
+    /// Plain and decorated (bold, italic, highlighted etc) text
+    fn visit_text(&mut self, _text: &str) {}
+
+    fn visit_code(&mut self, _code: &str) {}
+
+    fn visit_emoji(&mut self, _name: &str) {}
+
+    fn visit_latex(&mut self, _script: &str, _display: bool) {}
+
+    fn visit_expression(&mut self, _expression: &str) {}
+
+    fn visit_tag(&mut self, _tag: &str) {}
+
+    fn visit_link(&mut self, content: &[Inline], _to: &str) {
+        self.visit_inlines(content);
+    }
+
+    fn visit_media(&mut self, _alt: &str, _source: &str) {}
+
+    /// Elements of [`crate::extensions::InlineExtension`]s
+    fn visit_custom_inline(&mut self, _name: &str, _content: &str) {}
+}
+
+pub fn walk_document<V: Visitor + ?Sized>(visitor: &mut V, document: &Document) {
+    visitor.visit_blocks(&document.children);
+}
+
+pub fn walk_blocks<V: Visitor + ?Sized>(visitor: &mut V, blocks: &[Block]) {
+    for block in blocks {
+        visitor.visit_block(block);
+    }
+}
+
+pub fn walk_section<V: Visitor + ?Sized>(visitor: &mut V, section: &Section) {
+    visitor.visit_heading(&section.heading);
+    visitor.visit_blocks(&section.children);
+}
+
+pub fn walk_block<V: Visitor + ?Sized>(visitor: &mut V, block: &Block) {
+    match block {
+        Block::Section(section) => visitor.visit_section(section),
+        Block::Paragraph(content) => visitor.visit_paragraph(content),
+        Block::Quote(children) => visitor.visit_quote(children),
+        Block::Callout { kind, title } => visitor.visit_callout(kind, title),
+        Block::List(list) => visitor.visit_list(list),
+        Block::Table(table) => visitor.visit_table(table),
+        Block::CodeBlock { language, code } => visitor.visit_code_block(language, code),
+        Block::LaTeXBlock { script } => visitor.visit_latex_block(script),
+        Block::Command(command) => visitor.visit_command(command),
+        Block::Comment(comment) => visitor.visit_comment(comment),
+        Block::HTMLElement(element) => visitor.visit_html_element(element),
+        Block::Frontmatter(frontmatter) => visitor.visit_frontmatter(frontmatter),
+        Block::HorizontalRule => visitor.visit_horizontal_rule(),
+        Block::Footnote => visitor.visit_footnote(),
+        Block::Custom {
+            name,
+            arguments,
+            content,
+            ..
+        } => visitor.visit_custom_block(name, arguments, content),
+    }
+}
+
+pub fn walk_inline<V: Visitor + ?Sized>(visitor: &mut V, inline: &Inline) {
+    match inline {
+        Inline::Plain(text)
+        | Inline::Italic(text)
+        | Inline::Bold(text)
+        | Inline::BoldAndItalic(text)
+        | Inline::StrikeThrough(text)
+        | Inline::Highlight(text)
+        | Inline::Superscript(text)
+        | Inline::Subscript(text) => visitor.visit_text(text),
+        Inline::Code(code) => visitor.visit_code(code),
+        Inline::Emoji(name) => visitor.visit_emoji(name),
+        Inline::Latex(script) => visitor.visit_latex(script, false),
+        Inline::DisplayLatex(script) => visitor.visit_latex(script, true),
+        Inline::Expression(expression) => visitor.visit_expression(expression),
+        Inline::Tag(tag) => visitor.visit_tag(tag),
+        Inline::Link { content, to } => visitor.visit_link(content, to),
+        Inline::Media { alt, source } => visitor.visit_media(alt, source),
+        Inline::Command(command) => visitor.visit_command(command),
+        Inline::Custom { name, content, .. } => visitor.visit_custom_inline(name, content),
+    }
+}
+
+/// As [`Visitor`], but can modify nodes in place. [`VisitorMut::visit_blocks`] and
+/// [`VisitorMut::visit_inlines`] can also add or remove nodes
+pub trait VisitorMut {
+    fn visit_document(&mut self, document: &mut Document) {
+        walk_document_mut(self, document);
+    }
+
+    /// Every sequence of blocks (document, sections, quotes, list items and commands)
+    fn visit_blocks(&mut self, blocks: &mut Vec<Block>) {
+        walk_blocks_mut(self, blocks);
+    }
+
+    fn visit_block(&mut self, block: &mut Block) {
+        walk_block_mut(self, block);
+    }
+
+    fn visit_section(&mut self, section: &mut Section) {
+        walk_section_mut(self, section);
+    }
+
+    fn visit_heading(&mut self, heading: &mut Heading) {
+        self.visit_inlines(&mut heading.content);
+    }
+
+    fn visit_paragraph(&mut self, content: &mut Vec<Inline>) {
+        self.visit_inlines(content);
+    }
+
+    fn visit_quote(&mut self, children: &mut Vec<Block>) {
+        self.visit_blocks(children);
+    }
+
+    fn visit_callout(&mut self, _kind: &mut String, title: &mut Vec<Inline>) {
+        self.visit_inlines(title);
+    }
+
+    fn visit_list(&mut self, list: &mut List) {
+        for item in &mut list.items {
+            self.visit_list_item(item);
+        }
+    }
+
+    fn visit_list_item(&mut self, item: &mut ListItem) {
+        self.visit_inlines(&mut item.content);
+        self.visit_blocks(&mut item.children);
+    }
+
+    fn visit_table(&mut self, table: &mut Table) {
+        for cell in table
+            .header
+            .iter_mut()
+            .chain(table.rows.iter_mut().flatten())
+        {
+            self.visit_inlines(cell);
+        }
+    }
+
+    fn visit_code_block(&mut self, _language: &mut String, _code: &mut String) {}
+
+    fn visit_latex_block(&mut self, _script: &mut String) {}
+
+    /// Block and inline commands
+    fn visit_command(&mut self, command: &mut Command) {
+        self.visit_blocks(&mut command.children);
+    }
+
+    fn visit_comment(&mut self, _comment: &mut String) {}
+
+    fn visit_html_element(&mut self, _element: &mut String) {}
+
+    fn visit_frontmatter(&mut self, _frontmatter: &mut String) {}
+
+    fn visit_horizontal_rule(&mut self) {}
+
+    fn visit_footnote(&mut self) {}
+
+    /// Blocks of [`crate::extensions::BlockExtension`]s. The source is what is formatted
+    fn visit_custom_block(&mut self, _name: &str, _source: &mut String) {}
+
+    fn visit_inlines(&mut self, inlines: &mut Vec<Inline>) {
+        for inline in inlines {
+            self.visit_inline(inline);
+        }
+    }
+
+    fn visit_inline(&mut self, inline: &mut Inline) {
+        walk_inline_mut(self, inline);
+    }
+
+    /// Plain and decorated (bold, italic, highlighted etc) text
+    fn visit_text(&mut self, _text: &mut String) {}
+
+    fn visit_code(&mut self, _code: &mut String) {}
+
+    fn visit_emoji(&mut self, _name: &mut String) {}
+
+    fn visit_latex(&mut self, _script: &mut String, _display: bool) {}
+
+    fn visit_expression(&mut self, _expression: &mut String) {}
+
+    fn visit_tag(&mut self, _tag: &mut String) {}
+
+    fn visit_link(&mut self, content: &mut Vec<Inline>, _to: &mut String) {
+        self.visit_inlines(content);
+    }
+
+    fn visit_media(&mut self, _alt: &mut String, _source: &mut String) {}
+
+    /// Elements of [`crate::extensions::InlineExtension`]s. The source is what is formatted
+    fn visit_custom_inline(&mut self, _name: &str, _source: &mut String) {}
+}
+
+pub fn walk_document_mut<V: VisitorMut + ?Sized>(visitor: &mut V, document: &mut Document) {
+    visitor.visit_blocks(&mut document.children);
+}
+
+pub fn walk_blocks_mut<V: VisitorMut + ?Sized>(visitor: &mut V, blocks: &mut [Block]) {
+    for block in blocks {
+        visitor.visit_block(block);
+    }
+}
+
+pub fn walk_section_mut<V: VisitorMut + ?Sized>(visitor: &mut V, section: &mut Section) {
+    visitor.visit_heading(&mut section.heading);
+    visitor.visit_blocks(&mut section.children);
+}
+
+pub fn walk_block_mut<V: VisitorMut + ?Sized>(visitor: &mut V, block: &mut Block) {
+    match block {
+        Block::Section(section) => visitor.visit_section(section),
+        Block::Paragraph(content) => visitor.visit_paragraph(content),
+        Block::Quote(children) => visitor.visit_quote(children),
+        Block::Callout { kind, title } => visitor.visit_callout(kind, title),
+        Block::List(list) => visitor.visit_list(list),
+        Block::Table(table) => visitor.visit_table(table),
+        Block::CodeBlock { language, code } => visitor.visit_code_block(language, code),
+        Block::LaTeXBlock { script } => visitor.visit_latex_block(script),
+        Block::Command(command) => visitor.visit_command(command),
+        Block::Comment(comment) => visitor.visit_comment(comment),
+        Block::HTMLElement(element) => visitor.visit_html_element(element),
+        Block::Frontmatter(frontmatter) => visitor.visit_frontmatter(frontmatter),
+        Block::HorizontalRule => visitor.visit_horizontal_rule(),
+        Block::Footnote => visitor.visit_footnote(),
+        Block::Custom { name, source, .. } => visitor.visit_custom_block(name, source),
+    }
+}
+
+pub fn walk_inline_mut<V: VisitorMut + ?Sized>(visitor: &mut V, inline: &mut Inline) {
+    match inline {
+        Inline::Plain(text)
+        | Inline::Italic(text)
+        | Inline::Bold(text)
+        | Inline::BoldAndItalic(text)
+        | Inline::StrikeThrough(text)
+        | Inline::Highlight(text)
+        | Inline::Superscript(text)
+        | Inline::Subscript(text) => visitor.visit_text(text),
+        Inline::Code(code) => visitor.visit_code(code),
+        Inline::Emoji(name) => visitor.visit_emoji(name),
+        Inline::Latex(script) => visitor.visit_latex(script, false),
+        Inline::DisplayLatex(script) => visitor.visit_latex(script, true),
+        Inline::Expression(expression) => visitor.visit_expression(expression),
+        Inline::Tag(tag) => visitor.visit_tag(tag),
+        Inline::Link { content, to } => visitor.visit_link(content, to),
+        Inline::Media { alt, source } => visitor.visit_media(alt, source),
+        Inline::Command(command) => visitor.visit_command(command),
+        Inline::Custom { name, source, .. } => visitor.visit_custom_inline(name, source),
+    }
+}
@@ -1,6 +1,7 @@
-use simple_markdown_parser::{MarkdownElement, RawMarkdown, RawText};
+use simple_markdown_parser::{MarkdownElement, RawCode, RawMarkdown, RawText};
 
 #[test]
+#[allow(clippy::too_many_lines)]
 fn scan() {
     let source: &str = r"
 # Hello world
@@ -67,21 +68,29 @@ this is another comment
             vec![RawText("Hello world"), RawText("Under heading")],
             MarkdownElement::CodeBlock {
                 language: "ts",
-                code: "this is some code\n",
+                code: RawCode("this is some code\n"),
             },
         ),
         (
             vec![RawText("Hello world"), RawText("Under heading")],
             MarkdownElement::ListItem {
-                level: 1,
+                level: 0,
+                number: None,
                 text: RawText("List item 1"),
+                children: RawMarkdown(""),
+                loose: false,
+                continues: false,
             },
         ),
         (
             vec![RawText("Hello world"), RawText("Under heading")],
             MarkdownElement::ListItem {
-                level: 1,
+                level: 0,
+                number: None,
                 text: RawText("List item 2"),
+                children: RawMarkdown(""),
+                loose: false,
+                continues: false,
             },
         ),
         (
@@ -136,3 +145,618 @@ this is another comment
 
     pretty_assertions::assert_eq!(output, expected);
 }
+
+#[test]
+fn commands() {
+    use simple_markdown_parser::{CommandBlock, MarkdownTextElement};
+
+    let source: &str = r#"{% badge text="new" /%}
+
+Release {% badge text="beta" /%} is out
+
+{% note type="info" %}
+Inner
+{% /note %}
+"#;
+
+    let mut output = Vec::new();
+    let _ = simple_markdown_parser::parse(source, |item| output.push(item));
+
+    let expected = vec![
+        MarkdownElement::CommandBlock(CommandBlock {
+            name: "badge",
+            arguments: r#"text="new""#,
+            inner: RawMarkdown(""),
+        }),
+        MarkdownElement::Paragraph(RawText(r#"Release {% badge text="beta" /%} is out"#)),
+        MarkdownElement::CommandBlock(CommandBlock {
+            name: "note",
+            arguments: r#"type="info""#,
+            inner: RawMarkdown("Inner\n"),
+        }),
+    ];
+    pretty_assertions::assert_eq!(output, expected);
+
+    pretty_assertions::assert_eq!(
+        RawText(r#"Release {% badge text="beta" /%} is out"#)
+            .parts()
+            .collect::<Vec<_>>(),
+        vec![
+            MarkdownTextElement::Plain("Release "),
+            MarkdownTextElement::Command(CommandBlock {
+                name: "badge",
+                arguments: r#"text="beta""#,
+                inner: RawMarkdown(""),
+            }),
+            MarkdownTextElement::Plain(" is out"),
+        ]
+    );
+
+    // Openings without a `%}` do not each search the rest of the text
+    let start = std::time::Instant::now();
+    let source = "{% ".repeat(40_000);
+    let options = simple_markdown_parser::ParseOptions::default().expressions(false);
+    assert!(RawText(&source)
+        .parts_with_options(&options)
+        .all(|part| matches!(part, MarkdownTextElement::Plain(_))));
+    let source = format!("{}{{% a /%}}", "{% a %} ".repeat(20_000));
+    assert_eq!(
+        RawText(&source)
+            .parts_with_options(&options)
+            .filter(|part| matches!(part, MarkdownTextElement::Command(_)))
+            .count(),
+        1
+    );
+    assert!(start.elapsed() < std::time::Duration::from_secs(5));
+}
+
+#[test]
+fn nested_commands() {
+    use simple_markdown_parser::{CommandBlock, ParseError, ParseErrorReason};
+
+    let source: &str = r"{% tabs %}
+{% tabs %}
+{% note %}
+Inner
+{% /note %}
+{% /tabs %}
+```md
+{% /tabs %}
+```
+{% /tabs %}
+After";
+
+    let mut output = Vec::new();
+    let result = simple_markdown_parser::parse(source, |item| output.push(item));
+
+    assert!(result.is_ok());
+    pretty_assertions::assert_eq!(
+        output,
+        vec![
+            MarkdownElement::CommandBlock(CommandBlock {
+                name: "tabs",
+                arguments: "",
+                inner: RawMarkdown(
+                    "{% tabs %}\n{% note %}\nInner\n{% /note %}\n{% /tabs %}\n```md\n{% /tabs %}\n```\n"
+                ),
+            }),
+            MarkdownElement::Paragraph(RawText("After")),
+        ]
+    );
+
+    let mismatched = "{% columns %}\n{% note %}\n{% /columns %}\n";
+    pretty_assertions::assert_eq!(
+        simple_markdown_parser::parse(mismatched, |_| {}),
+        Err(ParseError {
+            reason: ParseErrorReason::MismatchedCommandClose {
+                expected: "note".to_owned(),
+                found: "columns".to_owned()
+            },
+            position: 25..39
+        })
+    );
+
+    let unclosed = "Text\n{% columns %}\n{% note %}\n{% /note %}\n";
+    pretty_assertions::assert_eq!(
+        simple_markdown_parser::parse(unclosed, |_| {}),
+        Err(ParseError {
+            reason: ParseErrorReason::UnclosedCommand {
+                name: "columns".to_owned()
+            },
+            position: 5..18
+        })
+    );
+
+    let unexpected = "Text\n{% /note %}";
+    pretty_assertions::assert_eq!(
+        simple_markdown_parser::parse(unexpected, |_| {}),
+        Err(ParseError {
+            reason: ParseErrorReason::UnexpectedCommandClose {
+                name: "note".to_owned()
+            },
+            position: 5..16
+        })
+    );
+}
+
+#[test]
+fn command_arguments() {
+    use simple_markdown_parser::arguments::{
+        ArgumentValue, ArgumentsError, ArgumentsErrorReason, CommandArguments,
+    };
+    use std::borrow::Cow;
+
+    let arguments = CommandArguments::parse(
+        r#""Intro" sync title="Say \"hi\"" size = 2 wide=true labels=['a', "b"] 3"#,
+    )
+    .unwrap();
+
+    pretty_assertions::assert_eq!(
+        arguments,
+        CommandArguments {
+            positional: vec![
+                ArgumentValue::String(Cow::Borrowed("Intro")),
+                ArgumentValue::Number(3.0)
+            ],
+            named: vec![
+                (
+                    "title",
+                    ArgumentValue::String(Cow::Owned("Say \"hi\"".to_owned()))
+                ),
+                ("size", ArgumentValue::Number(2.0)),
+                ("wide", ArgumentValue::Boolean(true)),
+                (
+                    "labels",
+                    ArgumentValue::List(vec![
+                        ArgumentValue::String(Cow::Borrowed("a")),
+                        ArgumentValue::String(Cow::Borrowed("b"))
+                    ])
+                ),
+            ],
+            flags: vec!["sync"],
+        }
+    );
+    assert!(arguments.has_flag("sync"));
+    assert_eq!(
+        arguments.get("title").and_then(ArgumentValue::as_str),
+        Some("Say \"hi\"")
+    );
+
+    pretty_assertions::assert_eq!(
+        CommandArguments::parse(r#"a=1 title="unclosed"#),
+        Err(ArgumentsError {
+            reason: ArgumentsErrorReason::UnclosedString,
+            position: 10..19
+        })
+    );
+    pretty_assertions::assert_eq!(
+        CommandArguments::parse("list=[1, 2"),
+        Err(ArgumentsError {
+            reason: ArgumentsErrorReason::UnclosedList,
+            position: 5..10
+        })
+    );
+    pretty_assertions::assert_eq!(
+        CommandArguments::parse("key= "),
+        Err(ArgumentsError {
+            reason: ArgumentsErrorReason::ExpectedValue,
+            position: 5..5
+        })
+    );
+}
+
+#[test]
+fn latex_blocks() {
+    let source: &str = r"$$x^2$$
+
+\[
+y
+\]
+
+\[z\]
+";
+
+    let mut output = Vec::new();
+    let _ = simple_markdown_parser::parse(source, |item| output.push(item));
+
+    pretty_assertions::assert_eq!(
+        output,
+        vec![
+            MarkdownElement::LaTeXBlock { script: "x^2" },
+            MarkdownElement::LaTeXBlock { script: "y" },
+            MarkdownElement::LaTeXBlock { script: "z" },
+        ]
+    );
+}
+
+#[test]
+fn crlf_and_byte_order_mark() {
+    use simple_markdown_parser::{parse_with_positions, ParseOptions};
+
+    let source = "\u{feff}---\r\ntitle: x\r\n---\r\n# Heading\r\n```rs\r\ncode\r\nmore\r\n```\r\n| a | b |\r\n|---|---|\r\n| 1 | 2 |\r\nAfter\rlast";
+
+    let mut output = Vec::new();
+    let _ = parse_with_positions(source, &ParseOptions::default(), |item, position| {
+        output.push((item, position));
+    });
+
+    let positions: Vec<_> = output
+        .iter()
+        .map(|(_, position)| position.clone())
+        .collect();
+    assert_eq!(positions, [3..21, 23..32, 34..56, 58..89, 91..96, 97..101]);
+    assert_eq!(output[0].0, MarkdownElement::Frontmatter("title: x\r\n"));
+    assert_eq!(
+        output[1].0,
+        MarkdownElement::Heading {
+            level: 1,
+            text: RawText("Heading")
+        }
+    );
+    assert_eq!(output[4].0, MarkdownElement::Paragraph(RawText("After")));
+    assert_eq!(output[5].0, MarkdownElement::Paragraph(RawText("last")));
+
+    // Code and table rows are split on each line ending, without the `\r`s
+    let MarkdownElement::CodeBlock { language, code } = output[2].0 else {
+        panic!("expected code block, found {:?}", output[2].0);
+    };
+    assert_eq!(language, "rs");
+    assert_eq!(code.lines().collect::<Vec<_>>(), ["code", "more"]);
+    assert_eq!(code.to_string(), "code\nmore\n");
+
+    // Tables can be indented
+    for source in [
+        &source[58..],
+        "| a | b |\r|:--|---|\r| 1 | 2 |\rAfter",
+        "  | a | b |\r\n  |:--|---|\r\n  | 1 | 2 |\r\nAfter",
+    ] {
+        let mut tables = Vec::new();
+        parse_with_positions(source, &ParseOptions::default(), |item, _| {
+            if let MarkdownElement::Table(table) = item {
+                tables.push(table);
+            }
+        })
+        .unwrap();
+        let rows = tables[0]
+            .rows()
+            .map(|row| row.cells().map(|cell| cell.0.trim()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        pretty_assertions::assert_eq!(rows, vec![vec!["a", "b"], vec!["1", "2"]]);
+        assert_eq!(tables[0].alignments().count(), 2);
+    }
+
+    let code = "```\rlone\r```\r";
+    let mut output = Vec::new();
+    parse_with_positions(code, &ParseOptions::default(), |item, _| output.push(item)).unwrap();
+    let [MarkdownElement::CodeBlock { code, .. }] = output[..] else {
+        panic!("expected code block, found {output:?}");
+    };
+    assert_eq!(code.to_string(), "lone\n");
+}
+
+#[test]
+fn list_levels() {
+    use simple_markdown_parser::{parse_with_options, ParseOptions};
+
+    let source = "- a\n  - b\n- c\n\t- d\n\t\t- e\n - f\n-not item\n- g\n\t- h\n";
+
+    let mut levels = Vec::new();
+    let _ = simple_markdown_parser::parse(source, |item| {
+        if let MarkdownElement::ListItem { level, text, .. } = item {
+            levels.push((level, text.0));
+        }
+    });
+    pretty_assertions::assert_eq!(
+        levels,
+        vec![
+            (0, "a"),
+            (1, "b"),
+            (0, "c"),
+            (1, "d"),
+            (2, "e"),
+            (0, "f"),
+            (0, "g"),
+            (1, "h")
+        ]
+    );
+
+    // With a tab of 1 column, tab indented items are not indented past the content of the previous item
+    let mut levels = Vec::new();
+    let _ = parse_with_options(source, &ParseOptions::default().tab_width(1), |item| {
+        if let MarkdownElement::ListItem { level, .. } = item {
+            levels.push(level);
+        }
+    });
+    pretty_assertions::assert_eq!(levels, vec![0, 1, 0, 0, 0, 0, 0, 0]);
+}
+
+#[test]
+fn ordered_lists() {
+    let source = "1. one\n2) two\n   - nested\n10. ten\n1.5 is a number\n2024.";
+
+    let mut items = Vec::new();
+    let _ = simple_markdown_parser::parse(source, |item| {
+        if let MarkdownElement::ListItem {
+            level,
+            number,
+            text,
+            ..
+        } = item
+        {
+            items.push((level, number, text.0));
+        }
+    });
+    pretty_assertions::assert_eq!(
+        items,
+        vec![
+            (0, Some(1), "one"),
+            (0, Some(2), "two"),
+            (1, None, "nested"),
+            (0, Some(10), "ten"),
+            (0, Some(2024), "")
+        ]
+    );
+}
+
+#[test]
+fn markers_and_fences() {
+    let source = "* a\n+ b\n## Closed ##\n# C#\n# #\n````md\n```\ncode\n```\n````";
+
+    let mut output = Vec::new();
+    let _ = simple_markdown_parser::parse(source, |item| output.push(item));
+    pretty_assertions::assert_eq!(
+        &output[2..],
+        [
+            MarkdownElement::Heading {
+                level: 2,
+                text: RawText("Closed")
+            },
+            MarkdownElement::Heading {
+                level: 1,
+                text: RawText("C#")
+            },
+            MarkdownElement::Heading {
+                level: 1,
+                text: RawText("")
+            },
+            MarkdownElement::CodeBlock {
+                language: "md",
+                code: RawCode("```\ncode\n```\n")
+            },
+        ]
+    );
+    assert!(matches!(
+        output[..2],
+        [
+            MarkdownElement::ListItem {
+                text: RawText("a"),
+                ..
+            },
+            MarkdownElement::ListItem {
+                text: RawText("b"),
+                ..
+            }
+        ]
+    ));
+}
+
+#[test]
+fn list_item_children() {
+    let source = r"- tight
+  continued
+
+  ```rs
+  code
+  ```
+  - nested
+- next
+
+Paragraph
+
+- loose
+
+- list
+";
+
+    let mut output = Vec::new();
+    let _ = simple_markdown_parser::parse(source, |item| output.push(item));
+
+    pretty_assertions::assert_eq!(
+        output,
+        vec![
+            MarkdownElement::ListItem {
+                level: 0,
+                number: None,
+                text: RawText("tight"),
+                children: RawMarkdown("  continued\n\n  ```rs\n  code\n  ```\n"),
+                loose: true,
+                continues: false,
+            },
+            MarkdownElement::ListItem {
+                level: 1,
+                number: None,
+                text: RawText("nested"),
+                children: RawMarkdown(""),
+                // Nested lists are loose or tight by their own lines
+                loose: false,
+                continues: false,
+            },
+            MarkdownElement::ListItem {
+                level: 0,
+                number: None,
+                text: RawText("next"),
+                children: RawMarkdown(""),
+                loose: true,
+                continues: false,
+            },
+            MarkdownElement::Paragraph(RawText("Paragraph")),
+            MarkdownElement::ListItem {
+                level: 0,
+                number: None,
+                text: RawText("loose"),
+                children: RawMarkdown(""),
+                loose: true,
+                continues: false,
+            },
+            MarkdownElement::ListItem {
+                level: 0,
+                number: None,
+                text: RawText("list"),
+                children: RawMarkdown(""),
+                loose: true,
+                continues: false,
+            },
+        ]
+    );
+
+    let mut output = Vec::new();
+    let _ = simple_markdown_parser::parse("- a\n  > quote\n- b\n\nAfter", |item| {
+        output.push(item);
+    });
+    pretty_assertions::assert_eq!(
+        output,
+        vec![
+            MarkdownElement::ListItem {
+                level: 0,
+                number: None,
+                text: RawText("a"),
+                children: RawMarkdown("  > quote\n"),
+                loose: false,
+                continues: false,
+            },
+            MarkdownElement::ListItem {
+                level: 0,
+                number: None,
+                text: RawText("b"),
+                children: RawMarkdown(""),
+                loose: false,
+                continues: false,
+            },
+            MarkdownElement::Paragraph(RawText("After")),
+        ]
+    );
+}
+
+#[test]
+fn list_item_continuations() {
+    // Lines after nested items continue the item they are indented to
+    let mut output = Vec::new();
+    let _ = simple_markdown_parser::parse("- a\n  - b\n\n    - c\n\n  para\nAfter", |item| {
+        output.push(item);
+    });
+    pretty_assertions::assert_eq!(
+        output,
+        vec![
+            MarkdownElement::ListItem {
+                level: 0,
+                number: None,
+                text: RawText("a"),
+                children: RawMarkdown(""),
+                loose: true,
+                continues: false,
+            },
+            MarkdownElement::ListItem {
+                level: 1,
+                number: None,
+                text: RawText("b"),
+                children: RawMarkdown(""),
+                loose: true,
+                continues: false,
+            },
+            MarkdownElement::ListItem {
+                level: 2,
+                number: None,
+                text: RawText("c"),
+                children: RawMarkdown(""),
+                loose: false,
+                continues: false,
+            },
+            MarkdownElement::ListItem {
+                level: 0,
+                number: None,
+                text: RawText(""),
+                children: RawMarkdown("  para\n"),
+                loose: true,
+                continues: true,
+            },
+            MarkdownElement::Paragraph(RawText("After")),
+        ]
+    );
+}
+
+#[test]
+fn commonmark_options() {
+    use simple_markdown_parser::{
+        parse_with_options, MarkdownTextElement, ParseOptions, RawMarkdown, RawText,
+    };
+
+    let source = "---\nfirst line\n  second line\n\n%% not a comment %%\n| not | table |\n# Heading\n$$\nx\n$$\n- item\ncontinued";
+
+    let mut items = Vec::new();
+    let _ = parse_with_options(source, &ParseOptions::commonmark(), |item| {
+        items.push(item);
+    });
+    pretty_assertions::assert_eq!(
+        items,
+        vec![
+            MarkdownElement::HorizontalRule,
+            MarkdownElement::Paragraph(RawText("first line\n  second line")),
+            MarkdownElement::Paragraph(RawText("%% not a comment %%\n| not | table |")),
+            MarkdownElement::Heading {
+                level: 1,
+                text: RawText("Heading")
+            },
+            MarkdownElement::Paragraph(RawText("$$\nx\n$$")),
+            // Lines of text continue items
+            MarkdownElement::ListItem {
+                level: 0,
+                number: None,
+                text: RawText("item\ncontinued"),
+                children: RawMarkdown(""),
+                loose: false,
+                continues: false,
+            },
+        ]
+    );
+
+    let text = RawText("#tag {expression} :smile: $x$ {% command /%}");
+    pretty_assertions::assert_eq!(
+        text.parts_with_options(&ParseOptions::commonmark())
+            .collect::<Vec<_>>(),
+        vec![MarkdownTextElement::Plain(text.0)]
+    );
+}
+
+#[test]
+fn presets() {
+    use simple_markdown_parser::{parse_with_options, ParseOptions, RawMarkdown, RawText};
+
+    let source = "> [!warning]- Careful\n> text\n{% note /%}\n| a |";
+    let elements = |options: &ParseOptions| {
+        let mut elements = Vec::new();
+        let _ = parse_with_options(source, options, |item| elements.push(item));
+        elements
+    };
+
+    let obsidian = elements(&ParseOptions::obsidian());
+    pretty_assertions::assert_eq!(
+        obsidian[..3],
+        [
+            MarkdownElement::Callout {
+                kind: "warning",
+                title: RawText("Careful")
+            },
+            MarkdownElement::Quote(RawMarkdown(" text")),
+            MarkdownElement::Paragraph(RawText("{% note /%}")),
+        ]
+    );
+    assert!(matches!(obsidian[3], MarkdownElement::Table(_)));
+
+    let gfm = elements(&ParseOptions::gfm());
+    assert!(matches!(gfm[0], MarkdownElement::Quote(_)));
+    assert!(matches!(gfm[3], MarkdownElement::Table(_)));
+
+    let default = elements(&ParseOptions::default());
+    assert!(matches!(default[0], MarkdownElement::Quote(_)));
+    assert!(matches!(default[2], MarkdownElement::CommandBlock(_)));
+}
@@ -0,0 +1,276 @@
+//! Runs the `CommonMark` 0.31.2 specification examples (`tests/commonmark/spec.json`, in the
+//! format of the specification's `spec_tests.py --dump-tests`) through [`markdown_to_html_with_options`]
+//! with [`ParseOptions::commonmark`]. HTML is compared after normalising whitespace around block
+//! tags. Run with `--nocapture` for the pass rate of each section
+
+use simple_markdown_parser::{
+    extras::emit::{markdown_to_html_with_options, FeatureEmitter},
+    ParseOptions,
+};
+
+/// Examples that currently render the same as the specification. A failing one is a regression,
+/// a passing one not listed here should be added
+const PASSING: &[usize] = &[
+    4, 9, 10, 13, 17, 42, 44, 45, 46, 56, 62, 63, 64, 67, 68, 71, 72, 73, 74, 75, 78, 79, 94, 97,
+    98, 104, 108, 109, 119, 122, 124, 129, 130, 135, 136, 140, 142, 144, 147, 168, 175, 187, 219,
+    220, 221, 224, 227, 239, 255, 256, 258, 261, 262, 265, 266, 267, 268, 269, 275, 276, 277, 279,
+    281, 282, 283, 284, 294, 295, 296, 297, 303, 305, 306, 307, 310, 311, 314, 316, 319, 322, 323,
+    325, 326, 327, 328, 329, 330, 331, 332, 333, 334, 338, 339, 340, 348, 349, 350, 355, 356, 357,
+    364, 365, 370, 377, 378, 381, 382, 390, 396, 403, 405, 412, 420, 423, 434, 436, 439, 441, 448,
+    451, 453, 460, 462, 483, 484, 485, 487, 501, 522, 611, 612, 613, 614, 615, 616, 617, 623, 625,
+    627, 628, 629, 630, 631, 642, 643, 644, 645, 646, 647, 648, 650, 651, 652,
+];
+
+struct SpecExample {
+    markdown: String,
+    html: String,
+    example: usize,
+    section: String,
+}
+
+/// Emits code blocks as `CommonMark` does. The other features are not enabled by the preset
+struct CommonMarkEmitter;
+
+impl FeatureEmitter for CommonMarkEmitter {
+    fn code_block(&self, language: &str, code: &str) -> String {
+        let code = escape(code);
+        match language.split_whitespace().next() {
+            Some(language) => format!("<code class=\"language-{language}\">{code}</code>"),
+            None => format!("<code>{code}</code>"),
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// `None` for parse errors
+fn render(markdown: &str) -> Option<String> {
+    let mut output = Vec::new();
+    markdown_to_html_with_options(
+        markdown,
+        &ParseOptions::commonmark(),
+        &mut output,
+        &mut CommonMarkEmitter,
+    )
+    .ok()?;
+    String::from_utf8(output).ok()
+}
+
+/// Removes whitespace next to block tags and makes void tags `<hr>` rather than `<hr />`
+fn normalise(html: &str) -> String {
+    const BLOCK_TAGS: &[&str] = &[
+        "blockquote",
+        "h1",
+        "h2",
+        "h3",
+        "h4",
+        "h5",
+        "h6",
+        "hr",
+        "li",
+        "ol",
+        "p",
+        "pre",
+        "table",
+        "tbody",
+        "td",
+        "th",
+        "thead",
+        "tr",
+        "ul",
+    ];
+    let is_block_tag = |tag: &str| {
+        let name = tag
+            .trim_start_matches(['<', '/'])
+            .split(|chr: char| chr.is_whitespace() || matches!(chr, '>' | '/'))
+            .next()
+            .unwrap_or_default();
+        BLOCK_TAGS.contains(&name)
+    };
+
+    let mut parts: Vec<String> = Vec::new();
+    let mut rest = html;
+    while !rest.is_empty() {
+        let length = if rest.starts_with('<') {
+            rest.find('>').map_or(rest.len(), |end| end + 1)
+        } else {
+            rest.find('<').unwrap_or(rest.len())
+        };
+        let part = &rest[..length];
+        parts.push(part.replace(" />", ">").replace("/>", ">"));
+        rest = &rest[length..];
+    }
+
+    let mut result = String::new();
+    for (idx, part) in parts.iter().enumerate() {
+        if part.starts_with('<') {
+            result.push_str(part);
+            continue;
+        }
+        let mut text = part.as_str();
+        if idx == 0 || parts.get(idx - 1).is_some_and(|tag| is_block_tag(tag)) {
+            text = text.trim_start();
+        }
+        if parts.get(idx + 1).is_none_or(|tag| is_block_tag(tag)) {
+            text = text.trim_end();
+        }
+        result.push_str(text);
+    }
+    result
+}
+
+#[test]
+fn specification_examples() {
+    let examples = read_examples(include_str!("commonmark/spec.json"));
+    assert_eq!(examples.len(), 652);
+
+    // Sections with their passed and total examples, in specification order
+    let mut sections: Vec<(&str, usize, usize)> = Vec::new();
+    let mut regressions = Vec::new();
+    let mut newly_passing = Vec::new();
+    for example in &examples {
+        let passed = render(&example.markdown)
+            .is_some_and(|html| normalise(&html) == normalise(&example.html));
+        if sections
+            .last()
+            .is_none_or(|(name, ..)| *name != example.section)
+        {
+            sections.push((&example.section, 0, 0));
+        }
+        let section = sections.last_mut().unwrap();
+        section.1 += usize::from(passed);
+        section.2 += 1;
+
+        match (passed, PASSING.contains(&example.example)) {
+            (false, true) => regressions.push(example.example),
+            (true, false) => newly_passing.push(example.example),
+            _ => {}
+        }
+    }
+
+    for (name, passed, total) in &sections {
+        println!(
+            "{name:<42} {passed:>3}/{total:<3} ({percent}%)",
+            percent = passed * 100 / total
+        );
+    }
+    let passed: usize = sections.iter().map(|(_, passed, _)| passed).sum();
+    println!("{:<42} {passed:>3}/{}", "Total", examples.len());
+
+    assert!(
+        regressions.is_empty(),
+        "examples no longer passing: {regressions:?}"
+    );
+    assert!(
+        newly_passing.is_empty(),
+        "examples now passing (add to `PASSING`): {newly_passing:?}"
+    );
+}
+
+/// Reads the array of flat objects in the specification JSON
+fn read_examples(json: &str) -> Vec<SpecExample> {
+    let mut reader = JsonReader(json.trim());
+    let mut examples = Vec::new();
+    reader.expect('[');
+    while !reader.eat(']') {
+        reader.eat(',');
+        reader.expect('{');
+        let mut example = SpecExample {
+            markdown: String::new(),
+            html: String::new(),
+            example: 0,
+            section: String::new(),
+        };
+        while !reader.eat('}') {
+            reader.eat(',');
+            let key = reader.string();
+            reader.expect(':');
+            match key.as_str() {
+                "markdown" => example.markdown = reader.string(),
+                "html" => example.html = reader.string(),
+                "section" => example.section = reader.string(),
+                "example" => example.example = reader.number(),
+                _ => {
+                    if reader.0.starts_with('"') {
+                        reader.string();
+                    } else {
+                        reader.number();
+                    }
+                }
+            }
+        }
+        examples.push(example);
+    }
+    examples
+}
+
+struct JsonReader<'a>(&'a str);
+
+impl JsonReader<'_> {
+    fn eat(&mut self, chr: char) -> bool {
+        self.0 = self.0.trim_start();
+        if let Some(rest) = self.0.strip_prefix(chr) {
+            self.0 = rest;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, chr: char) {
+        assert!(self.eat(chr), "expected {chr:?} at {:?}", &self.0[..20]);
+    }
+
+    fn number(&mut self) -> usize {
+        self.0 = self.0.trim_start();
+        let length = self
+            .0
+            .find(|chr: char| !chr.is_ascii_digit())
+            .unwrap_or(self.0.len());
+        let number = self.0[..length].parse().unwrap();
+        self.0 = &self.0[length..];
+        number
+    }
+
+    fn string(&mut self) -> String {
+        self.expect('"');
+        let mut string = String::new();
+        let mut chars = self.0.char_indices();
+        while let Some((idx, chr)) = chars.next() {
+            match chr {
+                '"' => {
+                    self.0 = &self.0[idx + 1..];
+                    return string;
+                }
+                '\\' => match chars.next().unwrap().1 {
+                    'n' => string.push('\n'),
+                    't' => string.push('\t'),
+                    'r' => string.push('\r'),
+                    'b' => string.push('\u{8}'),
+                    'f' => string.push('\u{c}'),
+                    'u' => {
+                        let code_unit = |chars: &mut std::str::CharIndices| {
+                            let hex: String = (0..4).map(|_| chars.next().unwrap().1).collect();
+                            u16::from_str_radix(&hex, 16).unwrap()
+                        };
+                        let mut units = vec![code_unit(&mut chars)];
+                        // High surrogate followed by `\u` and the low surrogate
+                        if (0xD800..0xDC00).contains(&units[0]) {
+                            chars.nth(1);
+                            units.push(code_unit(&mut chars));
+                        }
+                        string.push_str(&String::from_utf16(&units).unwrap());
+                    }
+                    other => string.push(other),
+                },
+                chr => string.push(chr),
+            }
+        }
+        panic!("unterminated string");
+    }
+}
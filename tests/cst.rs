@@ -0,0 +1,224 @@
+use simple_markdown_parser::{
+    cst::{SyntaxTree, TokenKind},
+    parse_with_positions, ParseOptions,
+};
+
+const SOURCE: &str = "\u{feff}---
+title: a
+---
+
+#  Heading  \r
+Paragraph with *text*\t
+
+- [ ] task
+  continued
+
+  1. nested
+> quote
+| a | b |
+|---|---|
+| c |   |
+```rs
+let a = 2;
+```
+{% note kind=\"info\" %}
+  Inside
+{% /note %}
+%% comment %%
+```
+unclosed";
+
+#[test]
+fn lossless() {
+    let tree = SyntaxTree::parse(SOURCE).unwrap();
+    let tokens = tree.root().descendant_tokens();
+
+    let text: String = tokens.iter().map(|token| token.text).collect();
+    assert_eq!(text, SOURCE);
+
+    let mut last = 0;
+    for token in &tokens {
+        assert_eq!(token.range.start, last, "{token:?}");
+        assert!(!token.text.is_empty(), "{token:?}");
+        last = token.range.end;
+    }
+
+    let unparsed: Vec<_> = tokens
+        .iter()
+        .filter(|token| token.kind == TokenKind::Unparsed)
+        .map(|token| token.text)
+        .collect();
+    assert_eq!(unparsed, vec!["```", "unclosed"]);
+}
+
+#[test]
+fn elements() {
+    let tree = SyntaxTree::parse(SOURCE).unwrap();
+
+    let mut expected = Vec::new();
+    let _ = parse_with_positions(SOURCE, &ParseOptions::default(), |element, position| {
+        expected.push((element, position));
+    });
+
+    let found: Vec<_> = tree
+        .root()
+        .nodes()
+        .map(|node| (node.element().unwrap(), node.range.clone()))
+        .collect();
+    pretty_assertions::assert_eq!(found, expected);
+}
+
+#[test]
+fn tokens() {
+    let source = "  ## Heading  ";
+    let tree = SyntaxTree::parse(source).unwrap();
+    let heading = tree.root().nodes().next().unwrap();
+
+    let tokens: Vec<_> = heading
+        .tokens()
+        .map(|token| (token.kind, token.text))
+        .collect();
+    assert_eq!(
+        tokens,
+        vec![
+            (TokenKind::Whitespace, "  "),
+            (TokenKind::Marker, "##"),
+            (TokenKind::Whitespace, " "),
+            (TokenKind::Text, "Heading"),
+            (TokenKind::Whitespace, "  "),
+        ]
+    );
+
+    let source = "{% note kind=1 %}\n- a\n{% /note %}";
+    let tree = SyntaxTree::parse(source).unwrap();
+    let command = tree.root().nodes().next().unwrap();
+    assert_eq!(
+        command
+            .token(TokenKind::CommandName)
+            .map(|token| token.text),
+        Some("note")
+    );
+    assert_eq!(
+        command
+            .token(TokenKind::CommandArguments)
+            .map(|token| token.text),
+        Some("kind=1")
+    );
+    let inner = command.nodes().next().unwrap();
+    assert_eq!(inner.range, 18..21);
+    assert_eq!(
+        tree.root()
+            .token_at(20)
+            .map(|token| (token.kind, token.text)),
+        Some((TokenKind::Text, "a"))
+    );
+}
+
+#[test]
+fn surgical_edits() {
+    let source = "# Title with   spacing\n\n- [ ] first\n  - [x]  second\n1) one";
+    let tree = SyntaxTree::parse(source).unwrap();
+    let mut nodes = tree.root().nodes();
+
+    let heading = nodes.next().unwrap();
+    let title = heading.token(TokenKind::Text).unwrap();
+    let mut edited = source.to_owned();
+    edited.replace_range(title.range.clone(), "Renamed");
+    assert_eq!(edited, "# Renamed\n\n- [ ] first\n  - [x]  second\n1) one");
+
+    let first = nodes.next().unwrap();
+    let checkbox = first.token(TokenKind::TaskMarker).unwrap();
+    let mut edited = source.to_owned();
+    edited.replace_range(checkbox.range.clone(), "[x]");
+    assert_eq!(
+        edited,
+        "# Title with   spacing\n\n- [x] first\n  - [x]  second\n1) one"
+    );
+
+    let second = nodes.next().unwrap();
+    assert_eq!(second.token(TokenKind::TaskMarker).unwrap().text, "[x]");
+    assert_eq!(second.token(TokenKind::Text).unwrap().text, "second");
+
+    let ordered = nodes.next().unwrap();
+    let marker = ordered.token(TokenKind::Marker).unwrap();
+    let mut edited = source.to_owned();
+    edited.replace_range(marker.range.clone(), "2.");
+    assert_eq!(
+        edited,
+        "# Title with   spacing\n\n- [ ] first\n  - [x]  second\n2. one"
+    );
+}
+
+#[test]
+fn inline_tokens() {
+    let source = "Some *text* and [a `link`](to.md) #tag";
+    let tree = SyntaxTree::parse(source).unwrap();
+    let paragraph = tree.root().nodes().next().unwrap();
+    let tokens: Vec<_> = paragraph
+        .tokens()
+        .map(|token| (token.kind, token.text))
+        .collect();
+    assert_eq!(
+        tokens,
+        vec![
+            (TokenKind::Text, "Some "),
+            (TokenKind::Delimiter, "*"),
+            (TokenKind::Text, "text"),
+            (TokenKind::Delimiter, "*"),
+            (TokenKind::Text, " and "),
+            (TokenKind::Delimiter, "["),
+            (TokenKind::Text, "a "),
+            (TokenKind::Delimiter, "`"),
+            (TokenKind::Content, "link"),
+            (TokenKind::Delimiter, "`"),
+            (TokenKind::Delimiter, "]("),
+            (TokenKind::Destination, "to.md"),
+            (TokenKind::Delimiter, ")"),
+            (TokenKind::Text, " "),
+            (TokenKind::Delimiter, "#"),
+            (TokenKind::Text, "tag"),
+        ]
+    );
+
+    // Changing a link destination leaves the rest of the line
+    let destination = paragraph.token(TokenKind::Destination).unwrap();
+    let mut edited = source.to_owned();
+    edited.replace_range(destination.range.clone(), "other.md");
+    assert_eq!(edited, "Some *text* and [a `link`](other.md) #tag");
+
+    // Destinations before text
+    let source = "[[Page|alias]]";
+    let tree = SyntaxTree::parse_with_options(source, &ParseOptions::obsidian()).unwrap();
+    let tokens: Vec<_> = tree
+        .root()
+        .descendant_tokens()
+        .iter()
+        .map(|token| (token.kind, token.text))
+        .collect();
+    assert_eq!(
+        tokens,
+        vec![
+            (TokenKind::Delimiter, "[["),
+            (TokenKind::Destination, "Page"),
+            (TokenKind::Delimiter, "|"),
+            (TokenKind::Text, "alias"),
+            (TokenKind::Delimiter, "]]"),
+        ]
+    );
+}
+
+mod arbitrary {
+    use proptest::prelude::*;
+    use simple_markdown_parser::cst::SyntaxTree;
+
+    proptest! {
+        #[test]
+        fn tokens_cover_source(source in "[-#>|`$%{}/* a-z1.\\[\\]\n\r\t]{0,120}") {
+            if let Ok(tree) = SyntaxTree::parse(&source) {
+                let tokens = tree.root().descendant_tokens();
+                let text: String = tokens.iter().map(|token| token.text).collect();
+                prop_assert_eq!(text, source);
+            }
+        }
+    }
+}
@@ -0,0 +1,337 @@
+use simple_markdown_parser::document::{Block, Document, Heading, Inline, List, ListItem, Section};
+
+fn paragraph(text: &str) -> Block {
+    Block::Paragraph(vec![Inline::Plain(text.to_owned())])
+}
+
+fn heading(level: u8, text: &str) -> Heading {
+    Heading {
+        level,
+        content: vec![Inline::Plain(text.to_owned())],
+    }
+}
+
+#[test]
+fn sections() {
+    let source = "Intro
+
+# A
+In A
+## A.1
+### A.1.1
+In A.1.1
+## A.2
+# B
+In B";
+
+    let document = Document::parse(source).unwrap();
+
+    pretty_assertions::assert_eq!(
+        document.children,
+        vec![
+            paragraph("Intro"),
+            Block::Section(Section {
+                heading: heading(1, "A"),
+                children: vec![
+                    paragraph("In A"),
+                    Block::Section(Section {
+                        heading: heading(2, "A.1"),
+                        children: vec![Block::Section(Section {
+                            heading: heading(3, "A.1.1"),
+                            children: vec![paragraph("In A.1.1")],
+                        })],
+                    }),
+                    Block::Section(Section {
+                        heading: heading(2, "A.2"),
+                        children: Vec::new(),
+                    }),
+                ],
+            }),
+            Block::Section(Section {
+                heading: heading(1, "B"),
+                children: vec![paragraph("In B")],
+            }),
+        ]
+    );
+
+    let titles: Vec<_> = document
+        .sections()
+        .into_iter()
+        .map(|section| section.heading.text())
+        .collect();
+    pretty_assertions::assert_eq!(titles, vec!["A", "A.1", "A.1.1", "A.2", "B"]);
+}
+
+#[test]
+fn lists_and_inlines() {
+    let source = "- **a** [link *text*](/to)
+  continued
+  - b
+1. c
+2. d";
+
+    let document = Document::parse(source).unwrap();
+
+    pretty_assertions::assert_eq!(
+        document.children,
+        vec![
+            Block::List(List {
+                start: None,
+                loose: false,
+                items: vec![ListItem {
+                    content: vec![
+                        Inline::Bold("a".to_owned()),
+                        Inline::Plain(" ".to_owned()),
+                        Inline::Link {
+                            content: vec![
+                                Inline::Plain("link ".to_owned()),
+                                Inline::Italic("text".to_owned())
+                            ],
+                            to: "/to".to_owned()
+                        }
+                    ],
+                    children: vec![
+                        paragraph("continued"),
+                        Block::List(List {
+                            start: None,
+                            loose: false,
+                            items: vec![ListItem {
+                                content: vec![Inline::Plain("b".to_owned())],
+                                children: Vec::new(),
+                            }],
+                        })
+                    ],
+                }],
+            }),
+            Block::List(List {
+                start: Some(1),
+                loose: false,
+                items: vec![
+                    ListItem {
+                        content: vec![Inline::Plain("c".to_owned())],
+                        children: Vec::new(),
+                    },
+                    ListItem {
+                        content: vec![Inline::Plain("d".to_owned())],
+                        children: Vec::new(),
+                    }
+                ],
+            }),
+        ]
+    );
+}
+
+#[test]
+fn quotes() {
+    // Consecutive lines are one quote and a blank line starts another
+    let document = Document::parse("> a\n> b\n\n> c\n>\n> > d\n> > e").unwrap();
+    pretty_assertions::assert_eq!(
+        document.children,
+        vec![
+            Block::Quote(vec![paragraph("a"), paragraph("b")]),
+            Block::Quote(vec![
+                paragraph("c"),
+                Block::Quote(vec![paragraph("d"), paragraph("e")])
+            ]),
+        ]
+    );
+}
+
+#[test]
+fn commands() {
+    let source = "{% note kind=\"info\" %}
+Inside
+{% /note %}";
+
+    let document = Document::parse(source).unwrap();
+    let [Block::Command(command)] = document.children.as_slice() else {
+        panic!("expected command, found {:?}", document.children);
+    };
+    assert_eq!(command.name, "note");
+    assert_eq!(
+        command
+            .arguments()
+            .unwrap()
+            .get("kind")
+            .and_then(|value| value.as_str()),
+        Some("info")
+    );
+    pretty_assertions::assert_eq!(command.children, vec![paragraph("Inside")]);
+
+    assert!(Document::parse("{% note %}\nUnclosed").is_err());
+}
+
+#[test]
+fn as_markdown() {
+    let source = "---
+title: Example
+---
+# Heading with **bold** and `code`
+Text with *italic*, ~~strike~~, ==highlight==, $x^2$, :smile: and #tag
+## Lists
+- one [link](to.md)
+  - nested ![alt](image.png)
+3. three
+4. four
+> quote
+| a | b |
+|---|---|
+| c | d |
+```rs
+let a = 2;
+```
+$$
+\\sin
+$$
+{% note kind=\"info\" %}
+Inside {% inline /%}
+{% /note %}
+%% comment %%
+---";
+
+    let document = Document::parse(source).unwrap();
+    let expected = "---
+title: Example
+---
+
+# Heading with **bold** and `code`
+
+Text with *italic*, ~~strike~~, ==highlight==, $x^2$, :smile: and #tag
+
+## Lists
+
+- one [link](to.md)
+  - nested ![alt](image.png)
+3. three
+4. four
+
+> quote
+
+| a | b |
+|---|---|
+| c | d |
+
+```rs
+let a = 2;
+```
+
+$$\\sin$$
+
+{% note kind=\"info\" %}
+Inside {% inline /%}
+{% /note %}
+
+%% comment %%
+
+---
+";
+    pretty_assertions::assert_eq!(document.as_markdown(), expected);
+    pretty_assertions::assert_eq!(Document::parse(expected).unwrap(), document);
+
+    // Delimiters that would be joined or paired with text use the other marker
+    for (source, expected) in [
+        ("***a** b*", "**a**_ b_\n"),
+        ("[x](y) [z]", "[x](y) [z]\n"),
+        ("`a*b` *c* [d", "`a*b` _c_ [d\n"),
+    ] {
+        let document = Document::parse(source).unwrap();
+        let markdown = document.as_markdown();
+        assert_eq!(markdown, expected);
+        assert_eq!(Document::parse(&markdown).unwrap(), document);
+    }
+}
+
+mod round_trip {
+    use proptest::prelude::*;
+    use simple_markdown_parser::document::Document;
+
+    fn text() -> impl Strategy<Value = String> {
+        let part = prop::sample::select(vec![
+            "word",
+            "two words",
+            "**bold**",
+            "__bold__",
+            "*italic*",
+            "_italic_",
+            "***both***",
+            "`code`",
+            "`` a ` b ``",
+            "~~strike~~",
+            "==highlight==",
+            "^super^",
+            "~sub~",
+            ":smile:",
+            ":unknown:",
+            "#tag",
+            "#nested/tag",
+            "$x^2$",
+            "\\(x\\)",
+            "$$y$$",
+            "\\[y\\]",
+            "{expression}",
+            "[link](to.md)",
+            "[**bold** link](to)",
+            "<https://example.com>",
+            "![alt](image.png)",
+            "{% inline a=1 /%}",
+            "5",
+            "$",
+            "[z]",
+            "[unclosed",
+            "![unclosed",
+        ]);
+        // Delimiters are paired across whitespace, so unbalanced ones are only at the end. The
+        // parser gives emphasis that cannot be written for some that are paired (see
+        // `Document::as_markdown`)
+        let unbalanced = prop::sample::select(vec!["", " *", " _", " 2 * 3", " snake_case"]);
+        (prop::collection::vec(part, 1..5), unbalanced)
+            .prop_map(|(parts, unbalanced)| parts.join(" ") + unbalanced)
+    }
+
+    fn line() -> impl Strategy<Value = String> {
+        prop_oneof![
+            text(),
+            (1..4usize, text()).prop_map(|(level, text)| format!("{} {text}", "#".repeat(level))),
+            (
+                prop::sample::select(vec!["", "  ", "    ", "\t"]),
+                prop::sample::select(vec!["-", "1.", "2)", "10."]),
+                text()
+            )
+                .prop_map(|(indentation, marker, text)| format!("{indentation}{marker} {text}")),
+            text().prop_map(|text| format!("  {text}")),
+            text().prop_map(|text| format!("> {text}")),
+            Just(String::new()),
+            Just("---".to_owned()),
+            Just("```rs\nlet a = 2;\n\nlet b = 3;\n```".to_owned()),
+            Just("  ```\n  indented\n  ```".to_owned()),
+            Just("$$\n\\sin\n$$".to_owned()),
+            Just("\\[x\\]".to_owned()),
+            Just("| a | **b** |\n|---|---|\n| c | d |".to_owned()),
+            Just("{% note kind=\"info\" %}\n- inside\n{% /note %}".to_owned()),
+            Just("{% note /%}".to_owned()),
+            Just("%% comment %%".to_owned()),
+            Just("%%\nmulti line\n%%".to_owned()),
+        ]
+    }
+
+    fn document() -> impl Strategy<Value = String> {
+        (any::<bool>(), prop::collection::vec(line(), 0..12)).prop_map(|(frontmatter, lines)| {
+            let body = lines.join("\n");
+            if frontmatter {
+                format!("---\ntitle: a\n---\n{body}")
+            } else {
+                body
+            }
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn parse_serialize_parse(source in document()) {
+            if let Ok(document) = Document::parse(&source) {
+                let markdown = document.as_markdown();
+                prop_assert_eq!(Document::parse(&markdown), Ok(document), "{}", markdown);
+            }
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use simple_markdown_parser::{
+    extras::emit::{
+        markdown_to_html, markdown_to_html_from_reader, markdown_to_html_with_options,
+        BlankFeatureEmitter,
+    },
+    ParseOptions,
+};
+
+fn to_html(source: &str) -> String {
+    let mut output = Vec::new();
+    markdown_to_html(source, &mut output, &mut BlankFeatureEmitter).unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn tight_and_loose_lists() {
+    pretty_assertions::assert_eq!(
+        to_html("- a\n  b\n- c\n\nEnd"),
+        "<ul>\n<li>\na\nb\n</li>\n<li>\nc\n</li>\n</ul>\n<p>\nEnd</p>\n"
+    );
+    pretty_assertions::assert_eq!(
+        to_html("- a\n\n  b\n- c\n\nEnd"),
+        "<ul>\n<li>\n<p>\na</p>\n<p>\nb</p>\n</li>\n<li>\n<p>\nc</p>\n</li>\n</ul>\n<p>\nEnd</p>\n"
+    );
+}
+
+#[test]
+fn nested_lists() {
+    pretty_assertions::assert_eq!(
+        to_html("- a\n  - b\n    - c\n- d"),
+        "<ul>\n<li>\na\n<ul>\n<li>\nb\n<ul>\n<li>\nc\n</li>\n</ul>\n</li>\n</ul>\n</li>\n<li>\nd\n</li>\n</ul>\n"
+    );
+    pretty_assertions::assert_eq!(
+        to_html("3. a\n   - b\n4. c\n- d\n\nEnd"),
+        "<ol start=\"3\">\n<li>\na\n<ul>\n<li>\nb\n</li>\n</ul>\n</li>\n<li>\nc\n</li>\n</ol>\n<ul>\n<li>\nd\n</li>\n</ul>\n<p>\nEnd</p>\n"
+    );
+    // Continues the outer item after the nested list
+    pretty_assertions::assert_eq!(
+        to_html("- a\n  - b\n\n  para"),
+        "<ul>\n<li>\n<p>\na</p>\n<ul>\n<li>\nb\n</li>\n</ul>\n<p>\npara</p>\n</li>\n</ul>\n"
+    );
+}
+
+#[test]
+fn from_reader() {
+    let source = "# Title\n- a\n  - b\n\n- c\n| x | y |\n|---|---|\n| 1 | 2 |\n```rs\nlet a = 2;\n```\nEnd é";
+    let mut output = Vec::new();
+    let reader = std::io::BufReader::with_capacity(3, source.as_bytes());
+    markdown_to_html_from_reader(
+        reader,
+        &ParseOptions::default(),
+        &mut output,
+        &mut BlankFeatureEmitter,
+    )
+    .unwrap();
+    pretty_assertions::assert_eq!(String::from_utf8(output).unwrap(), to_html(source));
+
+    // With the given options
+    let source = "Lines of\ntext $x$";
+    let options = ParseOptions::commonmark();
+    let mut output = Vec::new();
+    markdown_to_html_from_reader(
+        source.as_bytes(),
+        &options,
+        &mut output,
+        &mut BlankFeatureEmitter,
+    )
+    .unwrap();
+    let mut expected = Vec::new();
+    markdown_to_html_with_options(source, &options, &mut expected, &mut BlankFeatureEmitter)
+        .unwrap();
+    assert_eq!(output, expected);
+    assert_eq!(String::from_utf8(output).unwrap().matches("<p>").count(), 1);
+}
+
+#[test]
+fn long_tables_from_reader() {
+    // Given in parts, which are joined again
+    let source = format!("| x | y |\n|---|---|\n{}End", "| 1 | 2 |\n".repeat(10_000));
+    let mut output = Vec::new();
+    let reader = std::io::BufReader::with_capacity(64, source.as_bytes());
+    markdown_to_html_from_reader(
+        reader,
+        &ParseOptions::default(),
+        &mut output,
+        &mut BlankFeatureEmitter,
+    )
+    .unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert_eq!(output.matches("<table>").count(), 1);
+    assert_eq!(output, to_html(&source));
+}
+
+#[test]
+fn malformed_command_arguments() {
+    use simple_markdown_parser::{
+        arguments::{ArgumentsError, ArgumentsErrorReason, CommandArguments},
+        extras::emit::FeatureEmitter,
+    };
+    use std::cell::RefCell;
+
+    #[derive(Default)]
+    struct Errors(RefCell<Vec<ArgumentsError>>);
+
+    impl FeatureEmitter for Errors {
+        fn code_block(&self, _language: &str, code: &str) -> String {
+            code.to_owned()
+        }
+
+        fn command(&self, name: &str, _args: &CommandArguments, _inner: &str) -> String {
+            format!("[{name}]")
+        }
+
+        fn malformed_command(
+            &self,
+            name: &str,
+            arguments: &str,
+            error: &ArgumentsError,
+            inner: &str,
+        ) -> String {
+            self.0.borrow_mut().push(error.clone());
+            format!("[{name} {arguments}]{inner}")
+        }
+    }
+
+    // The rest of the document is rendered and the errors are positioned in the source
+    let source = "{% note title=\"unclosed %}\nText\n{% /note %}\nText {% badge key= /%} after";
+    let mut emitter = Errors::default();
+    let mut output = Vec::new();
+    markdown_to_html(source, &mut output, &mut emitter).unwrap();
+    pretty_assertions::assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "[note title=\"unclosed]Text\n\n<p>\nText [badge key=] after</p>\n"
+    );
+    let errors = emitter.0.into_inner();
+    assert_eq!(
+        errors
+            .iter()
+            .map(|error| (&error.reason, &source[error.position.clone()]))
+            .collect::<Vec<_>>(),
+        [
+            (&ArgumentsErrorReason::UnclosedString, "\"unclosed"),
+            (&ArgumentsErrorReason::ExpectedValue, "")
+        ]
+    );
+
+    // The default is the command without arguments
+    pretty_assertions::assert_eq!(
+        to_html("Text {% badge key= /%} after"),
+        "<p>\nText  after</p>\n"
+    );
+}
+
+#[test]
+fn default_features() {
+    // As their source
+    pretty_assertions::assert_eq!(
+        to_html("Text $x^2$ {name}\n{% note %}\nInside\n{% /note %}"),
+        "<p>\nText x^2 name</p>\nInside\n\n"
+    );
+}
@@ -0,0 +1,436 @@
+use simple_markdown_parser::{
+    document::{Block, Document, Inline},
+    extensions::{
+        BlockExtension, BlockLine, CustomBlock, CustomData, CustomInline, CustomValue,
+        InlineExtension,
+    },
+    extras::emit::{markdown_to_html_with_options, BlankFeatureEmitter, FeatureEmitter},
+    incremental::{ElementsChange, ParsedElements, TextEdit},
+    parse_with_positions,
+    streaming::StreamingParser,
+    MarkdownElement, MarkdownTextElement, ParseOptions, RawText,
+};
+use std::sync::Arc;
+
+/// `:::kind` ... `:::`
+#[derive(Debug)]
+struct Admonition;
+
+impl BlockExtension for Admonition {
+    fn name(&self) -> &'static str {
+        "admonition"
+    }
+
+    fn starts(&self, line: &str) -> bool {
+        line.starts_with(":::") && line.len() > 3
+    }
+
+    fn next_line(&self, _opening: &str, line: &str) -> BlockLine {
+        if line.trim() == ":::" {
+            BlockLine::Close
+        } else {
+            BlockLine::Continue
+        }
+    }
+
+    fn element<'a>(&self, opening: &'a str, body: &'a str) -> CustomData<'a> {
+        CustomData::new(opening[3..].trim(), body)
+    }
+}
+
+/// Consecutive lines starting with `;`
+#[derive(Debug)]
+struct Lisp;
+
+impl BlockExtension for Lisp {
+    fn name(&self) -> &'static str {
+        "lisp"
+    }
+
+    fn starts(&self, line: &str) -> bool {
+        line.starts_with(';')
+    }
+
+    fn next_line(&self, _opening: &str, line: &str) -> BlockLine {
+        if line.starts_with(';') {
+            BlockLine::Continue
+        } else {
+            BlockLine::End
+        }
+    }
+
+    fn element<'a>(&self, opening: &'a str, body: &'a str) -> CustomData<'a> {
+        CustomData::new(opening, body)
+    }
+}
+
+fn options() -> ParseOptions {
+    ParseOptions::default()
+        .block_extension(Admonition)
+        .block_extension(Lisp)
+}
+
+fn elements(source: &str) -> Vec<(MarkdownElement<'_>, std::ops::Range<usize>)> {
+    let mut elements = Vec::new();
+    parse_with_positions(source, &options(), |element, position| {
+        elements.push((element, position));
+    })
+    .unwrap();
+    elements
+}
+
+#[test]
+fn block_extensions() {
+    let source = "# Title\n:::note\nSome *text*\n:::\n; a\n; b\nAfter";
+    assert_eq!(
+        elements(source),
+        [
+            (
+                MarkdownElement::Heading {
+                    level: 1,
+                    text: RawText("Title")
+                },
+                0..7
+            ),
+            (
+                MarkdownElement::Custom(CustomBlock {
+                    name: "admonition",
+                    source: ":::note\nSome *text*\n:::",
+                    data: CustomData::new("note", "Some *text*\n")
+                }),
+                8..31
+            ),
+            (
+                MarkdownElement::Custom(CustomBlock {
+                    name: "lisp",
+                    source: "; a\n; b",
+                    data: CustomData::new("; a", "; b\n")
+                }),
+                32..39
+            ),
+            (MarkdownElement::Paragraph(RawText("After")), 40..45),
+        ]
+    );
+
+    // Unclosed blocks end with the source
+    assert_eq!(
+        elements(":::note\ntext"),
+        [(
+            MarkdownElement::Custom(CustomBlock {
+                name: "admonition",
+                source: ":::note\ntext",
+                data: CustomData::new("note", "text")
+            }),
+            0..12
+        )]
+    );
+
+    // Multiline paragraphs end before custom blocks
+    let options = ParseOptions::commonmark().block_extension(Lisp);
+    let mut names = Vec::new();
+    parse_with_positions("text\n; `a`", &options, |element, _| {
+        names.push(element.debug_without_text());
+    })
+    .unwrap();
+    assert_eq!(names, ["Paragraph", "Custom (lisp)"]);
+}
+
+#[test]
+fn custom_blocks_in_other_modules() {
+    let source = ":::note\nText\n:::\n\nAfter";
+    let document = Document::parse_with_options(source, &options()).unwrap();
+    assert_eq!(
+        document.children[0],
+        Block::Custom {
+            name: "admonition".to_owned(),
+            arguments: "note".to_owned(),
+            content: "Text\n".to_owned(),
+            source: ":::note\nText\n:::".to_owned(),
+            value: None,
+        }
+    );
+    assert_eq!(
+        Document::parse_with_options(&document.as_markdown(), &options()).unwrap(),
+        document
+    );
+
+    let mut streamed = Vec::new();
+    let mut parser = StreamingParser::new(&options());
+    for chunk in [":::no", "te\nTe", "xt\n:", "::\n\nAfter"] {
+        parser
+            .push(chunk, |element| streamed.push(format!("{element:?}")))
+            .unwrap();
+    }
+    parser
+        .finish(|element| streamed.push(format!("{element:?}")))
+        .unwrap();
+    let mut parsed = Vec::new();
+    parse_with_positions(source, &options(), |element, _| {
+        parsed.push(format!("{element:?}"));
+    })
+    .unwrap();
+    assert_eq!(streamed, parsed);
+}
+
+/// Lines starting with a prefix chosen at runtime, with the number of lines as the value
+#[derive(Debug)]
+struct Prefixed(String);
+
+impl BlockExtension for Prefixed {
+    fn name(&self) -> &'static str {
+        "prefixed"
+    }
+
+    fn starts(&self, line: &str) -> bool {
+        line.starts_with(&self.0)
+    }
+
+    fn next_line(&self, _opening: &str, line: &str) -> BlockLine {
+        if self.starts(line) {
+            BlockLine::Continue
+        } else {
+            BlockLine::End
+        }
+    }
+
+    fn element<'a>(&self, opening: &'a str, body: &'a str) -> CustomData<'a> {
+        let lines = 1 + body.lines().count();
+        CustomData::new(opening, body).value(CustomValue::new(lines))
+    }
+}
+
+#[test]
+fn runtime_extensions() {
+    let prefix = String::from("//");
+    let options = ParseOptions::default().block_extension(Prefixed(prefix));
+    let mut blocks = Vec::new();
+    parse_with_positions("// a\n// b\n// c\nAfter", &options, |element, _| {
+        if let MarkdownElement::Custom(block) = element {
+            blocks.push(block);
+        }
+    })
+    .unwrap();
+    assert_eq!(blocks.len(), 1);
+    assert_eq!(blocks[0].value::<usize>(), Some(&3));
+    assert_eq!(blocks[0].value::<String>(), None);
+
+    // Through the owned document
+    let document = Document::parse_with_options("// a", &options).unwrap();
+    let Block::Custom { value, .. } = &document.children[0] else {
+        panic!("{document:?}")
+    };
+    assert_eq!(
+        value.as_ref().and_then(CustomValue::downcast_ref),
+        Some(&1usize)
+    );
+
+    // Options are equal when they share the instances
+    let shared: Arc<dyn BlockExtension> = Arc::new(Lisp);
+    assert_eq!(
+        ParseOptions::default().shared_block_extension(Arc::clone(&shared)),
+        ParseOptions::default().shared_block_extension(shared)
+    );
+    assert_ne!(
+        ParseOptions::default().block_extension(Lisp),
+        ParseOptions::default().block_extension(Lisp)
+    );
+
+    // Values from separate parses are compared by their type's equality
+    let reparsed = Document::parse_with_options("// a", &options).unwrap();
+    assert_eq!(reparsed, document);
+    assert_eq!(CustomValue::new(1usize), CustomValue::new(1usize));
+    assert_ne!(CustomValue::new(1usize), CustomValue::new(2usize));
+    assert_ne!(CustomValue::new(1usize), CustomValue::new(1u8));
+
+    // So an edit after a custom block does not change it
+    let source = "// a\n// b\nText";
+    let parsed = ParsedElements::parse(source, &options).unwrap();
+    let edit = TextEdit {
+        range: 10..14,
+        new_text: "Word".to_owned(),
+    };
+    let new_source = edit.apply(source);
+    let (_, change) = parsed.reparse(&new_source, &edit).unwrap();
+    assert_eq!(
+        change,
+        ElementsChange {
+            old: 1..2,
+            new: 1..2
+        }
+    );
+}
+
+struct AdmonitionEmitter;
+
+impl FeatureEmitter for AdmonitionEmitter {
+    fn code_block(&self, _language: &str, code: &str) -> String {
+        code.to_owned()
+    }
+
+    fn custom_block(&self, block: &CustomBlock) -> String {
+        match block.name {
+            "admonition" => format!(
+                "<aside class=\"{kind}\">{content}</aside>",
+                kind = block.data.arguments,
+                content = block.data.content.trim_end()
+            ),
+            _ => String::new(),
+        }
+    }
+}
+
+#[test]
+fn custom_blocks_to_html() {
+    let mut output = Vec::new();
+    markdown_to_html_with_options(
+        ":::tip\nHi\n:::\n; code",
+        &options(),
+        &mut output,
+        &mut AdmonitionEmitter,
+    )
+    .unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "<aside class=\"tip\">Hi</aside>\n\n"
+    );
+
+    // Default rendering
+    let mut output = Vec::new();
+    markdown_to_html_with_options(
+        "; a\n; b",
+        &options(),
+        &mut output,
+        &mut BlankFeatureEmitter,
+    )
+    .unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "<div data-block=\"lisp\">\n; b\n</div>\n"
+    );
+}
+
+/// `||spoiler||`
+#[derive(Debug)]
+struct Spoiler;
+
+impl InlineExtension for Spoiler {
+    fn name(&self) -> &'static str {
+        "spoiler"
+    }
+
+    fn triggers(&self) -> &[char] {
+        &['|']
+    }
+
+    fn parse<'a>(&self, rest: &'a str, _preceding: Option<char>) -> Option<(&'a str, usize)> {
+        let inner = rest.strip_prefix("||")?;
+        let end = inner.find("||")?;
+        Some((&inner[..end], end + 4))
+    }
+}
+
+/// `{{variable}}`
+#[derive(Debug)]
+struct Variable;
+
+impl InlineExtension for Variable {
+    fn name(&self) -> &'static str {
+        "variable"
+    }
+
+    fn triggers(&self) -> &[char] {
+        &['{']
+    }
+
+    fn parse<'a>(&self, rest: &'a str, preceding: Option<char>) -> Option<(&'a str, usize)> {
+        // Lengths past the text are ignored
+        if preceding == Some('!') {
+            return Some(("", rest.len() + 1));
+        }
+        let inner = rest.strip_prefix("{{")?;
+        let end = inner.find("}}")?;
+        Some((inner[..end].trim(), end + 4))
+    }
+}
+
+fn inline_options() -> ParseOptions {
+    ParseOptions::default()
+        .inline_extension(Spoiler)
+        .inline_extension(Variable)
+}
+
+#[test]
+fn inline_extensions() {
+    let parts: Vec<_> = RawText("Hi {{ name }}, ||secret|| {expression} !{x}")
+        .parts_with_options(&inline_options())
+        .collect();
+    assert_eq!(
+        parts,
+        [
+            MarkdownTextElement::Plain("Hi "),
+            MarkdownTextElement::Custom(CustomInline {
+                name: "variable",
+                source: "{{ name }}",
+                content: "name"
+            }),
+            MarkdownTextElement::Plain(", "),
+            MarkdownTextElement::Custom(CustomInline {
+                name: "spoiler",
+                source: "||secret||",
+                content: "secret"
+            }),
+            MarkdownTextElement::Plain(" "),
+            MarkdownTextElement::Expression("expression"),
+            MarkdownTextElement::Plain(" !"),
+            MarkdownTextElement::Expression("x"),
+        ]
+    );
+
+    // Not in code
+    let parts: Vec<_> = RawText("`||a||`")
+        .parts_with_options(&inline_options())
+        .collect();
+    assert_eq!(parts, [MarkdownTextElement::Code("||a||")]);
+}
+
+#[test]
+fn custom_inlines_in_other_modules() {
+    let source = "Text ||hidden|| [in ||link||](to)";
+    let options = inline_options();
+    let document = Document::parse_with_options(source, &options).unwrap();
+    let Block::Paragraph(content) = &document.children[0] else {
+        panic!("{document:?}")
+    };
+    assert_eq!(
+        content[1],
+        Inline::Custom {
+            name: "spoiler".to_owned(),
+            content: "hidden".to_owned(),
+            source: "||hidden||".to_owned(),
+        }
+    );
+    assert_eq!(document.as_markdown().trim_end(), source);
+
+    let mut output = Vec::new();
+    markdown_to_html_with_options(source, &options, &mut output, &mut SpoilerEmitter).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "<p>\nText <span class=\"spoiler\">hidden</span> <a href=\"to\">in <span class=\"spoiler\">link</span></a></p>\n"
+    );
+}
+
+struct SpoilerEmitter;
+
+impl FeatureEmitter for SpoilerEmitter {
+    fn code_block(&self, _language: &str, code: &str) -> String {
+        code.to_owned()
+    }
+
+    fn custom_inline(&self, inline: &CustomInline) -> String {
+        format!(
+            "<span class=\"{name}\">{content}</span>",
+            name = inline.name,
+            content = inline.content
+        )
+    }
+}
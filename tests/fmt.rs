@@ -0,0 +1,249 @@
+use simple_markdown_parser::fmt::{
+    format, BulletMarker, EmphasisMarker, FormatOptions, HeadingStyle, OrderedListDelimiter,
+};
+
+#[test]
+fn style() {
+    let source = "---
+title: Notes
+---
+#   Heading with _italic_ and __bold__
+Text
+- a
+    - b
+1) one
+1) two
+| a | long header |
+|-|-|
+|longer cell|b|
+%% kept %%
+{% note kind=\"info\" %}
+Inside
+{% /note %}";
+
+    pretty_assertions::assert_eq!(
+        format(source, &FormatOptions::default()).unwrap(),
+        "---
+title: Notes
+---
+
+# Heading with *italic* and **bold**
+
+Text
+
+- a
+  - b
+1. one
+2. two
+
+| a           | long header |
+| ----------- | ----------- |
+| longer cell | b           |
+
+%% kept %%
+
+{% note kind=\"info\" %}
+Inside
+{% /note %}
+"
+    );
+
+    let options = FormatOptions {
+        emphasis: EmphasisMarker::Underscore,
+        strong: EmphasisMarker::Underscore,
+        ordered_list_delimiter: OrderedListDelimiter::Parenthesis,
+        blank_lines: 0,
+        ..FormatOptions::default()
+    };
+    pretty_assertions::assert_eq!(
+        format("# *a* **b**\n\n\n3. c\n4. d\n\nText", &options).unwrap(),
+        "# _a_ __b__\n3) c\n4) d\nText\n"
+    );
+
+    let options = FormatOptions {
+        bullet: BulletMarker::Asterisk,
+        heading_style: HeadingStyle::AtxClosed,
+        fence_length: 4,
+        ..FormatOptions::default()
+    };
+    pretty_assertions::assert_eq!(
+        format("## Title\n+ a\n  - b\n```rs\ncode\n```", &options).unwrap(),
+        "## Title ##\n\n* a\n  * b\n\n````rs\ncode\n````\n"
+    );
+    // Fences are longer than the ones in the code and `#` ending headings are kept
+    pretty_assertions::assert_eq!(
+        format("# C # #\n````\n```\n````", &FormatOptions::default()).unwrap(),
+        "# C # #\n\n````\n```\n````\n"
+    );
+
+    // Column alignment is kept
+    pretty_assertions::assert_eq!(
+        format(
+            "|a|b|c|d|\n|:-|:-:|-:|-|\n|1|2|3|4|",
+            &FormatOptions::default()
+        )
+        .unwrap(),
+        "| a   | b   | c   | d   |\n| :-- | :-: | --: | --- |\n| 1   | 2   | 3   | 4   |\n"
+    );
+}
+
+#[test]
+fn text() {
+    // Bracketed text without a destination is kept as it is
+    for source in [
+        "- [ ] task\n- [x] done\n",
+        "See [[Wiki|alias]] and [[Page]]\n",
+    ] {
+        pretty_assertions::assert_eq!(format(source, &FormatOptions::default()).unwrap(), source);
+    }
+
+    // Lines of a paragraph stay one paragraph
+    pretty_assertions::assert_eq!(
+        format("line one\nline two", &FormatOptions::default()).unwrap(),
+        "line one\nline two\n"
+    );
+    pretty_assertions::assert_eq!(
+        format("## Notes\nfirst\n   second", &FormatOptions::default()).unwrap(),
+        "## Notes\n\nfirst\nsecond\n"
+    );
+
+    // As are lines of quotes, and their paragraphs stay separate
+    for source in ["> a\n> b\n", "> a\n>\n> b\n\n> c\n"] {
+        pretty_assertions::assert_eq!(format(source, &FormatOptions::default()).unwrap(), source);
+    }
+}
+
+#[test]
+fn reflow() {
+    let options = FormatOptions {
+        max_width: Some(20),
+        ..FormatOptions::default()
+    };
+    pretty_assertions::assert_eq!(
+        format(
+            "A long line with [a link that is long](to) and - dashes\n\n- an item that needs wrapping too",
+            &options
+        )
+        .unwrap(),
+        "A long line with
+[a link that is long](to)
+and - dashes
+
+- an item that needs
+  wrapping too
+"
+    );
+
+    // Lines of text are one paragraph, so wrapped text is joined again
+    pretty_assertions::assert_eq!(
+        format(
+            "A line\nthat continues  *on\nthe* next\n- item\n  continued",
+            &options
+        )
+        .unwrap(),
+        "A line that\ncontinues *on the*\nnext\n\n- item continued\n"
+    );
+}
+
+#[test]
+fn idempotent() {
+    let source = "# Title
+Some *text* with `code`, $x$ and [links](to.md)
+- item
+  continued
+
+  - loose nested
+| a | b |
+|---|---|
+| c | d |";
+
+    for options in [
+        FormatOptions::default(),
+        FormatOptions {
+            blank_lines: 2,
+            ..FormatOptions::default()
+        },
+    ] {
+        let formatted = format(source, &options).unwrap();
+        pretty_assertions::assert_eq!(format(&formatted, &options).unwrap(), formatted);
+    }
+}
+
+#[test]
+fn long_paragraphs() {
+    // Marker choice is found once per paragraph
+    let source = "a _ ".repeat(50_000);
+    let formatted = format(&source, &FormatOptions::default()).unwrap();
+    assert!(formatted.starts_with("a _ a _ "));
+
+    // `$` math before a digit
+    assert_eq!(
+        format("\\(x\\) \\(y\\)1", &FormatOptions::default()).unwrap(),
+        "$x$ \\(y\\)1\n"
+    );
+}
+
+mod arbitrary {
+    use proptest::prelude::*;
+    use simple_markdown_parser::fmt::{format, BulletMarker, FormatOptions, HeadingStyle};
+
+    fn line() -> impl Strategy<Value = String> {
+        let word = prop::sample::select(vec![
+            "word",
+            "a",
+            "longer",
+            "*italic*",
+            "**bold**",
+            "`code`",
+            "[link](to)",
+            "$x$",
+            "-",
+            "1.",
+            "#",
+            ">",
+            "|",
+            "{x}",
+            "12",
+        ]);
+        // Lines start with text, so the syntax only starts blocks if wrapping moves it
+        let text = prop::collection::vec(word, 0..12).prop_map(|words| {
+            std::iter::once("text")
+                .chain(words)
+                .collect::<Vec<_>>()
+                .join(" ")
+        });
+        prop_oneof![
+            text.clone(),
+            text.clone().prop_map(|text| format!("- {text}")),
+            text.clone().prop_map(|text| format!("  - {text}")),
+            text.clone().prop_map(|text| format!("1. {text}")),
+            text.clone().prop_map(|text| format!("  {text}")),
+            text.prop_map(|text| format!("# {text}")),
+            Just(String::new()),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn idempotent_reflow(
+            lines in prop::collection::vec(line(), 0..8),
+            max_width in 5..40usize,
+            bullet in prop::sample::select(vec![
+                BulletMarker::Dash,
+                BulletMarker::Asterisk,
+                BulletMarker::Plus,
+            ]),
+            heading_style in prop::sample::select(vec![HeadingStyle::Atx, HeadingStyle::AtxClosed]),
+        ) {
+            let options = FormatOptions {
+                max_width: Some(max_width),
+                bullet,
+                heading_style,
+                ..FormatOptions::default()
+            };
+            if let Ok(formatted) = format(&lines.join("\n"), &options) {
+                prop_assert_eq!(format(&formatted, &options).unwrap(), formatted);
+            }
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use simple_markdown_parser::{
+    incremental::{ElementsChange, ParsedElements, TextEdit},
+    parse_with_positions, MarkdownElement, ParseError, ParseOptions,
+};
+
+type Elements<'a> = Vec<(MarkdownElement<'a>, std::ops::Range<usize>)>;
+
+fn full_parse(source: &str) -> Result<Elements<'_>, ParseError> {
+    let mut elements = Vec::new();
+    parse_with_positions(source, &ParseOptions::default(), |element, position| {
+        elements.push((element, position));
+    })?;
+    Ok(elements)
+}
+
+fn edit(range: std::ops::Range<usize>, new_text: &str) -> TextEdit {
+    TextEdit {
+        range,
+        new_text: new_text.to_owned(),
+    }
+}
+
+#[test]
+fn changed_elements() {
+    let source = "# A\nText\n\n# B\nMore\n\n# C";
+    let parsed = ParsedElements::parse(source, &ParseOptions::default()).unwrap();
+
+    // Renaming a heading
+    let rename = edit(11..12, "Renamed");
+    let new_source = rename.apply(source);
+    let (reparsed, change) = parsed.reparse(&new_source, &rename).unwrap();
+    assert_eq!(reparsed.elements(), full_parse(&new_source).unwrap());
+    assert_eq!(
+        change,
+        ElementsChange {
+            old: 2..3,
+            new: 2..3
+        }
+    );
+
+    // Opening a code block changes everything after it
+    let fence = edit(4..4, "```\n");
+    let new_source = fence.apply(source);
+    let (reparsed, change) = parsed.reparse(&new_source, &fence).unwrap();
+    assert_eq!(reparsed.elements(), full_parse(&new_source).unwrap());
+    assert_eq!(
+        change,
+        ElementsChange {
+            old: 1..5,
+            new: 1..1
+        }
+    );
+
+    // Closing it again
+    let (parsed, _) = (reparsed, change);
+    let close = edit(13..13, "```\n");
+    let closed_source = close.apply(&new_source);
+    let (reparsed, change) = parsed.reparse(&closed_source, &close).unwrap();
+    assert_eq!(reparsed.elements(), full_parse(&closed_source).unwrap());
+    assert_eq!(
+        change,
+        ElementsChange {
+            old: 1..1,
+            new: 1..5
+        }
+    );
+}
+
+#[test]
+fn lists() {
+    let source = "- a\n- b\n- c\n\nAfter";
+    let parsed = ParsedElements::parse(source, &ParseOptions::default()).unwrap();
+
+    // A blank line makes the whole list loose
+    let blank = edit(8..8, "\n");
+    let new_source = blank.apply(source);
+    let (reparsed, change) = parsed.reparse(&new_source, &blank).unwrap();
+    assert_eq!(reparsed.elements(), full_parse(&new_source).unwrap());
+    assert_eq!(
+        change,
+        ElementsChange {
+            old: 0..3,
+            new: 0..3
+        }
+    );
+}
+
+#[test]
+fn errors() {
+    let source = "{% note %}\nInside\n{% /note %}\nAfter";
+    let parsed = ParsedElements::parse(source, &ParseOptions::default()).unwrap();
+
+    let remove_close = edit(18..29, "");
+    let new_source = remove_close.apply(source);
+    assert_eq!(
+        parsed.reparse(&new_source, &remove_close).map(|_| ()),
+        full_parse(&new_source).map(|_| ())
+    );
+}
+
+mod arbitrary {
+    use super::full_parse;
+    use proptest::prelude::*;
+    use simple_markdown_parser::{
+        incremental::{ParsedElements, TextEdit},
+        ParseOptions,
+    };
+
+    fn line() -> impl Strategy<Value = String> {
+        prop::sample::select(vec![
+            "text",
+            "# heading",
+            "- item",
+            "  - nested",
+            "  indented",
+            "1. ordered",
+            "- [ ] task",
+            "> quote",
+            "",
+            "---",
+            "```",
+            "```rs",
+            "$$",
+            "%%",
+            "%% comment %%",
+            "| a | b |",
+            "|---|---|",
+            "{% note %}",
+            "{% /note %}",
+            "{% inline /%}",
+        ])
+        .prop_map(str::to_owned)
+    }
+
+    fn source_and_edit() -> impl Strategy<Value = (String, TextEdit)> {
+        let new_text = prop::sample::select(vec![
+            "",
+            "x",
+            "\n",
+            "\n\n",
+            "  ",
+            "- ",
+            "# ",
+            "```\n",
+            "$$\n",
+            "%%",
+            "|",
+            "---\n",
+            "{% note %}\n",
+            "{% /note %}\n",
+            "\t",
+        ]);
+        (
+            prop::collection::vec(line(), 0..15),
+            any::<prop::sample::Index>(),
+            any::<prop::sample::Index>(),
+            new_text,
+        )
+            .prop_map(|(lines, start, length, new_text)| {
+                let source = lines.join("\n");
+                let start = start.index(source.len() + 1);
+                let end = start + length.index((source.len() - start).min(12) + 1);
+                let edit = TextEdit {
+                    range: start..end,
+                    new_text: new_text.to_owned(),
+                };
+                (source, edit)
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn same_as_full_parse((source, edit) in source_and_edit()) {
+            let Ok(parsed) = ParsedElements::parse(&source, &ParseOptions::default()) else {
+                return Ok(());
+            };
+            let new_source = edit.apply(&source);
+            let expected = full_parse(&new_source);
+            match parsed.reparse(&new_source, &edit) {
+                Ok((reparsed, change)) => {
+                    let expected = expected.unwrap();
+                    prop_assert_eq!(reparsed.elements(), expected.as_slice(), "{:?}", new_source);
+
+                    // Elements outside of the change are the same
+                    let old = parsed.elements();
+                    prop_assert_eq!(change.old.start, change.new.start);
+                    prop_assert_eq!(old.len() - change.old.end, expected.len() - change.new.end);
+                    for (old, new) in old[..change.old.start].iter().zip(&expected) {
+                        prop_assert_eq!(old, new);
+                    }
+                    for (old, new) in old[change.old.end..].iter().zip(&expected[change.new.end..]) {
+                        prop_assert_eq!(&old.0, &new.0);
+                    }
+                }
+                Err(error) => prop_assert_eq!(Err(error), expected.map(|_| ())),
+            }
+        }
+    }
+}
@@ -41,8 +41,282 @@ The `parse` API takes a callback that is called on each [markdown](https://www.m
                         MarkdownTextElement::Plain(" to get the individual sections.")
                     ]
                 );
+                pretty_assertions::assert_eq!(
+                    text.no_decoration(),
+                    "The parse API takes a callback that is called on each markdown item. The inner text decoration is skipped on the first pass but can be picked up later using RawText::parts, which returns a PartsIterator to get the individual sections."
+                );
             }
             item => panic!("not expecting {item:?}"),
         }
     });
 }
+
+#[test]
+fn code_spans() {
+    use simple_markdown_parser::{MarkdownTextElement, RawText};
+
+    pretty_assertions::assert_eq!(
+        RawText("use `` a ` b `` or ` `` ` here")
+            .parts()
+            .collect::<Vec<_>>(),
+        vec![
+            MarkdownTextElement::Plain("use "),
+            MarkdownTextElement::Code("a ` b"),
+            MarkdownTextElement::Plain(" or "),
+            MarkdownTextElement::Code("``"),
+            MarkdownTextElement::Plain(" here"),
+        ]
+    );
+
+    pretty_assertions::assert_eq!(
+        RawText("unmatched `` here `and` after")
+            .parts()
+            .collect::<Vec<_>>(),
+        vec![
+            MarkdownTextElement::Plain("unmatched `` here "),
+            MarkdownTextElement::Code("and"),
+            MarkdownTextElement::Plain(" after"),
+        ]
+    );
+
+    pretty_assertions::assert_eq!(
+        RawText("`  ` and ` a`").parts().collect::<Vec<_>>(),
+        vec![
+            MarkdownTextElement::Code("  "),
+            MarkdownTextElement::Plain(" and "),
+            MarkdownTextElement::Code(" a"),
+        ]
+    );
+}
+
+#[cfg(feature = "emoji")]
+#[test]
+fn emoji() {
+    use simple_markdown_parser::{MarkdownTextElement, RawText};
+
+    let text = RawText("launch :rocket: at 12:30 :notanemoji: :+1:");
+    pretty_assertions::assert_eq!(
+        text.parts().collect::<Vec<_>>(),
+        vec![
+            MarkdownTextElement::Plain("launch "),
+            MarkdownTextElement::Emoji("rocket"),
+            MarkdownTextElement::Plain(" at 12:30 :notanemoji: "),
+            MarkdownTextElement::Emoji("+1"),
+        ]
+    );
+    pretty_assertions::assert_eq!(text.no_decoration(), "launch 🚀 at 12:30 :notanemoji: 👍");
+
+    let mut output = Vec::new();
+    let _ = simple_markdown_parser::extras::emit::markdown_to_html(
+        "Shipped :tada: :unknown:",
+        &mut output,
+        &mut simple_markdown_parser::extras::emit::BlankFeatureEmitter,
+    );
+    pretty_assertions::assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "<p>\nShipped 🎉 :unknown:</p>\n"
+    );
+}
+
+#[test]
+fn tags() {
+    use simple_markdown_parser::{
+        utilities::{collect_tags, collect_tags_with_options, TagOccurrence},
+        MarkdownTextElement, ParseOptions, RawText,
+    };
+
+    pretty_assertions::assert_eq!(
+        RawText("C# issue #12 see https://example.com/#top #project/alpha-1, #2024_notes")
+            .parts()
+            .collect::<Vec<_>>(),
+        vec![
+            MarkdownTextElement::Plain("C# issue #12 see https://example.com/#top "),
+            MarkdownTextElement::Tag("project/alpha-1"),
+            MarkdownTextElement::Plain(", "),
+            MarkdownTextElement::Tag("2024_notes"),
+        ]
+    );
+
+    pretty_assertions::assert_eq!(
+        MarkdownTextElement::Tag("project/alpha")
+            .tag_segments()
+            .map(Iterator::collect::<Vec<_>>),
+        Some(vec!["project", "alpha"])
+    );
+
+    let source = "# Notes #inbox\n\n#todo at line start\n- item with [#linked](x)\n";
+    pretty_assertions::assert_eq!(
+        collect_tags(source),
+        vec![
+            TagOccurrence {
+                tag: "inbox",
+                position: 8..14
+            },
+            TagOccurrence {
+                tag: "todo",
+                position: 16..21
+            },
+            TagOccurrence {
+                tag: "linked",
+                position: 49..56
+            },
+        ]
+    );
+
+    // Where the tag also appears in other syntax or in the markers of the element, and in list
+    // item children, emphasis, command bodies and table cells
+    for (source, expected) in [
+        (
+            "## ## #a #\r\n> `x #a` #a\n- [y](#b) #b\n  #b\n  - #c **#d** [[#e]](to)",
+            vec![("a", 6), ("a", 21), ("b", 34), ("b", 39), ("c", 46), ("d", 51), ("e", 58)],
+        ),
+        (
+            "- [y](#b) #b\n  #b\n\n**#d** and *#e*\n",
+            vec![("b", 10), ("b", 15), ("d", 21), ("e", 31)],
+        ),
+        (
+            "| #x | y |\n|---|---|\n| #z | w |\n{% note %}\nInside #in\n{% /note %}\n- a\n\n    - b\n\n      #deep",
+            vec![("x", 2), ("z", 23), ("in", 50), ("deep", 86)],
+        ),
+    ] {
+        let tags = collect_tags(source);
+        for tag in &tags {
+            assert_eq!(&source[tag.position.clone()], format!("#{}", tag.tag));
+        }
+        assert_eq!(
+            tags.iter()
+                .map(|tag| (tag.tag, tag.position.start))
+                .collect::<Vec<_>>(),
+            expected
+        );
+    }
+
+    // With the syntax of the given options
+    assert_eq!(
+        collect_tags_with_options("#a **#b**", &ParseOptions::default().tags(false)),
+        []
+    );
+}
+
+#[test]
+fn math() {
+    use simple_markdown_parser::{MarkdownTextElement, RawText};
+
+    pretty_assertions::assert_eq!(
+        RawText(r"$x^2$ and \(y\) inline with $$\int x$$ and \[z\] display")
+            .parts()
+            .collect::<Vec<_>>(),
+        vec![
+            MarkdownTextElement::Latex("x^2"),
+            MarkdownTextElement::Plain(" and "),
+            MarkdownTextElement::Latex("y"),
+            MarkdownTextElement::Plain(" inline with "),
+            MarkdownTextElement::DisplayLatex(r"\int x"),
+            MarkdownTextElement::Plain(" and "),
+            MarkdownTextElement::DisplayLatex("z"),
+            MarkdownTextElement::Plain(" display"),
+        ]
+    );
+
+    for text in [
+        "costs $5 and $10",
+        "not $ x$",
+        "or 5$x$",
+        "or $x$5",
+        "but $$ unclosed",
+    ] {
+        pretty_assertions::assert_eq!(
+            RawText(text).parts().collect::<Vec<_>>(),
+            vec![MarkdownTextElement::Plain(text)]
+        );
+    }
+
+    // Openers without a closer do not each search the rest of the text
+    let start = std::time::Instant::now();
+    for opening in ["$", "\\[", "\\("] {
+        let source = format!("{opening}a ").repeat(40_000);
+        assert!(RawText(&source)
+            .parts()
+            .all(|part| matches!(part, MarkdownTextElement::Plain(_))));
+    }
+    let source = "$a$ $b ".repeat(20_000);
+    assert_eq!(RawText(&source).parts().count(), 40_000);
+    assert!(start.elapsed() < std::time::Duration::from_secs(5));
+}
+
+#[test]
+fn syntax_options() {
+    use simple_markdown_parser::{MarkdownTextElement, ParseOptions, RawText};
+
+    let text = RawText("#tag {x} :smile: $y$");
+    let options = ParseOptions::default()
+        .tags(false)
+        .expressions(false)
+        .emoji(false)
+        .latex(false);
+    pretty_assertions::assert_eq!(
+        text.parts_with_options(&options).collect::<Vec<_>>(),
+        vec![MarkdownTextElement::Plain(text.0)]
+    );
+
+    // Commands are still parsed without expressions
+    pretty_assertions::assert_eq!(
+        RawText("{a} {% b /%}")
+            .parts_with_options(&ParseOptions::default().expressions(false))
+            .map(|part| part.no_decoration())
+            .collect::<Vec<_>>(),
+        vec!["{a} ", ""]
+    );
+}
+
+#[test]
+fn wikilinks() {
+    use simple_markdown_parser::{MarkdownTextElement, ParseOptions, RawText};
+
+    let text = RawText("See [[Page]], [[Other#Heading|this]] and ![[image.png]]");
+    pretty_assertions::assert_eq!(
+        text.parts_with_options(&ParseOptions::obsidian())
+            .collect::<Vec<_>>(),
+        vec![
+            MarkdownTextElement::Plain("See "),
+            MarkdownTextElement::Link {
+                on: RawText("Page"),
+                to: "Page"
+            },
+            MarkdownTextElement::Plain(", "),
+            MarkdownTextElement::Link {
+                on: RawText("this"),
+                to: "Other#Heading"
+            },
+            MarkdownTextElement::Plain(" and "),
+            MarkdownTextElement::Media {
+                alt: "",
+                source: "image.png"
+            },
+        ]
+    );
+}
+
+#[test]
+fn emphasis() {
+    use simple_markdown_parser::{MarkdownTextElement, RawText};
+
+    // A delimiter is only closed by a run of the same length
+    assert_eq!(
+        RawText("*a** b*").parts().collect::<Vec<_>>(),
+        [MarkdownTextElement::Italic("a** b")]
+    );
+    assert_eq!(
+        RawText("a **é").parts().collect::<Vec<_>>(),
+        [MarkdownTextElement::Plain("a **é")]
+    );
+
+    // Unclosed delimiters do not each scan the rest of the text
+    let source = "*".repeat(99_999);
+    assert_eq!(
+        RawText(&source).parts().collect::<Vec<_>>(),
+        [MarkdownTextElement::Plain(&source)]
+    );
+    let source = "a _ b __ ".repeat(20_000);
+    assert_eq!(RawText(&source).parts().count(), 40_001);
+}
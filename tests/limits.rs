@@ -0,0 +1,261 @@
+use simple_markdown_parser::{
+    document::Document,
+    extras::emit::{element_to_html, markdown_to_html_with_options, BlankFeatureEmitter},
+    incremental::{ParsedElements, TextEdit},
+    parse_with_options,
+    streaming::StreamingParser,
+    MarkdownElement, MarkdownTextElement, ParseError, ParseErrorReason, ParseOptions, RawText,
+};
+
+fn parse_error(source: &str, options: &ParseOptions) -> Option<ParseErrorReason> {
+    parse_with_options(source, options, |_| {})
+        .err()
+        .map(|error| error.reason)
+}
+
+#[test]
+fn nested_argument_lists() {
+    use simple_markdown_parser::arguments::{
+        ArgumentsError, ArgumentsErrorReason, CommandArguments,
+    };
+
+    let options = ParseOptions::default().max_nesting_depth(Some(3));
+    assert!(CommandArguments::parse_with_options("[[[1]]]", &options).is_ok());
+    assert_eq!(
+        CommandArguments::parse_with_options("a [[[[1]]]]", &options),
+        Err(ArgumentsError {
+            reason: ArgumentsErrorReason::TooDeep,
+            position: 5..6
+        })
+    );
+
+    // On by default, so the arguments of commands do not overflow the stack
+    let source = format!("{{% x {} /%}}", "[".repeat(200_000));
+    let mut output = Vec::new();
+    markdown_to_html_with_options(
+        &source,
+        &ParseOptions::default(),
+        &mut output,
+        &mut BlankFeatureEmitter,
+    )
+    .unwrap();
+}
+
+#[test]
+fn nesting_depth() {
+    let options = ParseOptions::default().max_nesting_depth(Some(3));
+    assert_eq!(parse_error("> > > quote", &options), None);
+    assert_eq!(
+        parse_with_options("text\n>>>> quote", &options, |_| {}),
+        Err(ParseError {
+            reason: ParseErrorReason::NestingTooDeep { limit: 3 },
+            position: 5..15
+        })
+    );
+    assert_eq!(
+        parse_error("- a\n  - b\n    - c\n      - d", &options),
+        Some(ParseErrorReason::NestingTooDeep { limit: 3 })
+    );
+    assert_eq!(
+        parse_error(
+            "{% a %}\n{% b %}\n{% c %}\n{% d %}\n{% /d %}\n{% /c %}\n{% /b %}\n{% /a %}",
+            &options
+        ),
+        Some(ParseErrorReason::NestingTooDeep { limit: 3 })
+    );
+
+    // On by default, so deeply nested quotes do not overflow the stack of the document tree
+    let source = ">".repeat(100_000);
+    assert_eq!(
+        Document::parse(&source).map(|_| ()),
+        Err(ParseError {
+            reason: ParseErrorReason::NestingTooDeep { limit: 128 },
+            position: 0..100_000
+        })
+    );
+    let mut output = Vec::new();
+    assert!(markdown_to_html_with_options(
+        &source,
+        &ParseOptions::default(),
+        &mut output,
+        &mut BlankFeatureEmitter
+    )
+    .is_err());
+
+    // Link text nested deeper than the limit is text
+    let parts: Vec<_> = RawText("[[[[a](b)](c)](d)](e)")
+        .parts_with_options(&options)
+        .collect();
+    assert_eq!(parts[0], MarkdownTextElement::Plain("["));
+    assert!(matches!(
+        parts[1],
+        MarkdownTextElement::Link { to: "d", .. }
+    ));
+}
+
+#[test]
+fn line_length() {
+    let options = ParseOptions::default().max_line_length(Some(8));
+    assert_eq!(
+        parse_error("# Heading\n", &options),
+        Some(ParseErrorReason::LineTooLong { limit: 8 })
+    );
+    assert_eq!(
+        parse_with_options("short\r\nlong line", &options, |_| {}),
+        Err(ParseError {
+            reason: ParseErrorReason::LineTooLong { limit: 8 },
+            position: 7..16
+        })
+    );
+
+    // Errors before the line is complete rather than buffering it
+    let mut parser = StreamingParser::new(&options);
+    parser.push("short\nlong", |_| {}).unwrap();
+    assert_eq!(
+        parser.push(" line", |_| {}),
+        Err(ParseError {
+            reason: ParseErrorReason::LineTooLong { limit: 8 },
+            position: 6..15
+        })
+    );
+}
+
+#[test]
+fn element_count() {
+    let options = ParseOptions::default().max_elements(Some(2));
+    assert_eq!(parse_error("# a\nb", &options), None);
+    assert_eq!(
+        parse_with_options("# a\nb\n\n- c", &options, |_| {}),
+        Err(ParseError {
+            reason: ParseErrorReason::TooManyElements { limit: 2 },
+            position: 7..10
+        })
+    );
+
+    // Counts across chunks
+    let mut parser = StreamingParser::new(&options);
+    parser.push("# a\n", |_| {}).unwrap();
+    parser.push("b\n", |_| {}).unwrap();
+    parser.push("# c\n", |_| {}).unwrap();
+    assert_eq!(
+        parser.finish(|_| {}),
+        Err(ParseError {
+            reason: ParseErrorReason::TooManyElements { limit: 2 },
+            position: 6..9
+        })
+    );
+
+    // Reused elements count when reparsing
+    let parsed = ParsedElements::parse("# a\nb", &options).unwrap();
+    let edit = TextEdit {
+        range: 3..3,
+        new_text: "\nc".to_owned(),
+    };
+    assert_eq!(
+        parsed.reparse(&edit.apply("# a\nb"), &edit).map(|_| ()),
+        Err(ParseError {
+            reason: ParseErrorReason::TooManyElements { limit: 2 },
+            position: 6..7
+        })
+    );
+}
+
+/// Sources that used to panic
+#[test]
+fn malformed() {
+    for source in ["![x]y", "#######", "*😀", "_😀_", "|", "| a\n|", "```\n- a"] {
+        for options in [ParseOptions::default(), ParseOptions::commonmark()] {
+            parse_with_options(source, &options, |element| {
+                if let Some(text) = element.parts_like() {
+                    for _ in text.parts_with_options(&options) {}
+                }
+                if let MarkdownElement::Table(table) = element {
+                    for row in table.rows() {
+                        for _ in row.cells() {}
+                    }
+                }
+            })
+            .unwrap();
+            let mut output = Vec::new();
+            markdown_to_html_with_options(source, &options, &mut output, &mut BlankFeatureEmitter)
+                .unwrap();
+        }
+    }
+
+    let parts: Vec<_> = RawText("![x]y").parts().collect();
+    assert_eq!(
+        parts,
+        [
+            MarkdownTextElement::Plain("![x]"),
+            MarkdownTextElement::Plain("y")
+        ]
+    );
+}
+
+#[test]
+fn headings_deeper_than_html() {
+    // Elements can be made without the parser
+    let mut output = Vec::new();
+    element_to_html(
+        &mut output,
+        &mut BlankFeatureEmitter,
+        MarkdownElement::Heading {
+            level: 9,
+            text: RawText("deep"),
+        },
+    )
+    .unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), "<h6>\ndeep</h6>\n");
+}
+
+mod arbitrary {
+    use proptest::prelude::*;
+    use simple_markdown_parser::{
+        cst::SyntaxTree,
+        document::Document,
+        extras::emit::{markdown_to_html_with_options, BlankFeatureEmitter},
+        fmt::{format_document, FormatOptions},
+        parse_with_positions, MarkdownElement, MarkdownTextElement, ParseOptions,
+    };
+
+    fn options() -> impl Strategy<Value = ParseOptions> {
+        prop::sample::select(vec![
+            ParseOptions::default(),
+            ParseOptions::commonmark(),
+            ParseOptions::gfm(),
+            ParseOptions::obsidian(),
+            ParseOptions::default().max_nesting_depth(Some(2)),
+        ])
+    }
+
+    proptest! {
+        /// Parsing and everything built on it does not panic
+        #[test]
+        fn no_panics(
+            source in "[-#>|`$%{}/*_~=^:!\\[\\]()<>a-z1. \n\r\té😀\\\\+\"',]{0,200}",
+            options in options(),
+        ) {
+            let _ = parse_with_positions(&source, &options, |element, _| {
+                if let Some(text) = element.parts_like() {
+                    for part in text.parts_with_options(&options) {
+                        if let MarkdownTextElement::Link { on, .. } = part {
+                            for _ in on.parts_with_options(&options) {}
+                        }
+                    }
+                }
+                if let MarkdownElement::Table(table) = element {
+                    for row in table.rows() {
+                        for _ in row.cells() {}
+                    }
+                }
+                let _ = element.as_markdown();
+            });
+            let mut output = Vec::new();
+            let _ = markdown_to_html_with_options(&source, &options, &mut output, &mut BlankFeatureEmitter);
+            let _ = SyntaxTree::parse_with_options(&source, &options);
+            if let Ok(document) = Document::parse_with_options(&source, &options) {
+                let _ = format_document(&document, &FormatOptions::default());
+            }
+        }
+    }
+}
@@ -0,0 +1,172 @@
+#![cfg(feature = "serde")]
+
+use serde_json::{json, Value};
+use simple_markdown_parser::{extras::pandoc::markdown_to_pandoc, ParseOptions};
+
+fn blocks(source: &str, options: &ParseOptions) -> Vec<Value> {
+    let pandoc = markdown_to_pandoc(source, options).unwrap();
+    assert_eq!(pandoc["pandoc-api-version"], json!([1, 23, 1]));
+    pandoc["blocks"].as_array().unwrap().clone()
+}
+
+fn str(text: &str) -> Value {
+    json!({ "t": "Str", "c": text })
+}
+
+fn space() -> Value {
+    json!({ "t": "Space" })
+}
+
+fn no_attributes() -> Value {
+    json!(["", [], []])
+}
+
+#[test]
+fn blocks_and_inlines() {
+    let source = "# Notes {#top .intro}\n\nSome **bold** and `code`\nwith $x^2$\n\n1. a\n2. b\n\n```rust file=main.rs\nfn main() {}\n```\n\n---";
+    assert_eq!(
+        blocks(source, &ParseOptions::default()),
+        [
+            json!({ "t": "Header", "c": [1, ["top", ["intro"], []], [str("Notes")]] }),
+            json!({ "t": "Para", "c": [
+                str("Some"),
+                space(),
+                { "t": "Strong", "c": [str("bold")] },
+                space(),
+                str("and"),
+                space(),
+                { "t": "Code", "c": [no_attributes(), "code"] },
+            ] }),
+            json!({ "t": "Para", "c": [
+                str("with"),
+                space(),
+                { "t": "Math", "c": [{ "t": "InlineMath" }, "x^2"] },
+            ] }),
+            json!({ "t": "OrderedList", "c": [
+                [1, { "t": "Decimal" }, { "t": "Period" }],
+                [[{ "t": "Plain", "c": [str("a")] }], [{ "t": "Plain", "c": [str("b")] }]],
+            ] }),
+            json!({ "t": "CodeBlock", "c": [["", ["rust"], [["file", "main.rs"]]], "fn main() {}"] }),
+            json!({ "t": "HorizontalRule" }),
+        ]
+    );
+
+    // Line breaks in paragraphs
+    assert_eq!(
+        blocks("a\nb", &ParseOptions::commonmark()),
+        [json!({ "t": "Para", "c": [str("a"), { "t": "SoftBreak" }, str("b")] })]
+    );
+}
+
+#[test]
+fn heading_identifiers() {
+    let blocks = blocks(
+        "# Hello, *World*!\n## Hello, World\n## 2. Setup",
+        &ParseOptions::default(),
+    );
+    let identifiers: Vec<_> = blocks.iter().map(|block| &block["c"][1][0]).collect();
+    assert_eq!(identifiers, ["hello-world", "hello-world-1", "setup"]);
+}
+
+#[test]
+fn notes() {
+    let blocks = blocks(
+        "Text[^1] and [a link](to).\n\n[^1]: The *note*",
+        &ParseOptions::default(),
+    );
+    // Definitions are moved into references
+    assert_eq!(
+        blocks,
+        [json!({ "t": "Para", "c": [
+            str("Text"),
+            { "t": "Note", "c": [{ "t": "Para", "c": [
+                str("The"),
+                space(),
+                { "t": "Emph", "c": [str("note")] },
+            ] }] },
+            space(),
+            str("and"),
+            space(),
+            { "t": "Link", "c": [no_attributes(), [str("a"), space(), str("link")], ["to", ""]] },
+            str("."),
+        ] })]
+    );
+}
+
+#[test]
+fn tables() {
+    let blocks = blocks("| a | b |\n|:-|-:|\n| 1 | 2 |", &ParseOptions::default());
+    let cell = |text: &str| json!([no_attributes(), { "t": "AlignDefault" }, 1, 1, [{ "t": "Plain", "c": [str(text)] }]]);
+    assert_eq!(
+        blocks,
+        [json!({ "t": "Table", "c": [
+            no_attributes(),
+            [null, []],
+            [[{ "t": "AlignLeft" }, { "t": "ColWidthDefault" }], [{ "t": "AlignRight" }, { "t": "ColWidthDefault" }]],
+            [no_attributes(), [[no_attributes(), [cell("a"), cell("b")]]]],
+            [[no_attributes(), 0, [], [[no_attributes(), [cell("1"), cell("2")]]]]],
+            [no_attributes(), []],
+        ] })]
+    );
+}
+
+#[test]
+fn commands_and_quotes() {
+    let commands = blocks(
+        "{% note kind=\"info\" open %}\nInside\n{% /note %}\n\n> a\n> b",
+        &ParseOptions::default(),
+    );
+    assert_eq!(
+        commands,
+        [
+            json!({ "t": "Div", "c": [
+                ["", ["note", "open"], [["kind", "info"]]],
+                [{ "t": "Para", "c": [str("Inside")] }],
+            ] }),
+            json!({ "t": "BlockQuote", "c": [
+                { "t": "Para", "c": [str("a")] },
+                { "t": "Para", "c": [str("b")] },
+            ] }),
+        ]
+    );
+
+    // As Pandoc's alerts
+    let callout = blocks(
+        "> [!warning]\n> Careful",
+        &ParseOptions::default().callouts(true),
+    );
+    assert_eq!(
+        callout,
+        [json!({ "t": "Div", "c": [
+            ["", ["warning"], []],
+            [
+                { "t": "Div", "c": [["", ["title"], []], [{ "t": "Para", "c": [str("Warning")] }]] },
+                { "t": "Para", "c": [str("Careful")] },
+            ],
+        ] })]
+    );
+}
+
+#[test]
+fn nested_notes() {
+    // Including references to themselves
+    let blocks = blocks(
+        "Text[^1].\n\n[^1]: See[^1] and[^2].\n\n[^2]: Other",
+        &ParseOptions::default(),
+    );
+    assert_eq!(
+        blocks,
+        [json!({ "t": "Para", "c": [
+            str("Text"),
+            { "t": "Note", "c": [{ "t": "Para", "c": [
+                str("See"),
+                str("[^1]"),
+                space(),
+                str("and"),
+                str("[^2]"),
+                str("."),
+            ] }] },
+            str("."),
+        ] })]
+    );
+}
@@ -0,0 +1,283 @@
+#![cfg(feature = "serde")]
+
+use serde_json::{json, Value};
+use simple_markdown_parser::{
+    document::Document, extensions::InlineExtension, extras::mdast::to_mdast, parse, ParseOptions,
+    RawText,
+};
+
+#[test]
+fn elements() {
+    let mut elements = Vec::new();
+    parse("# Title\n\n{% note %}\nText\n{% /note %}", |element| {
+        elements.push(element);
+    })
+    .unwrap();
+    let json = serde_json::to_value(&elements).unwrap();
+    assert_eq!(
+        json[0],
+        json!({ "Heading": { "level": 1, "text": "Title" } })
+    );
+    assert_eq!(
+        json[1],
+        json!({ "CommandBlock": { "name": "note", "arguments": "", "inner": "Text\n" } })
+    );
+
+    let parts: Vec<_> = RawText("[a](b) `c`").parts().collect();
+    assert_eq!(
+        serde_json::to_value(&parts).unwrap(),
+        json!([
+            { "Link": { "on": "a", "to": "b" } },
+            { "Plain": " " },
+            { "Code": "c" },
+        ])
+    );
+}
+
+#[derive(Debug)]
+struct Mention;
+
+impl InlineExtension for Mention {
+    fn name(&self) -> &'static str {
+        "mention"
+    }
+
+    fn triggers(&self) -> &[char] {
+        &['@']
+    }
+
+    fn parse<'a>(&self, rest: &'a str, _preceding: Option<char>) -> Option<(&'a str, usize)> {
+        let name = &rest[1..];
+        let length = name
+            .find(|chr: char| !chr.is_alphanumeric())
+            .unwrap_or(name.len());
+        (length > 0).then(|| (&name[..length], 1 + length))
+    }
+}
+
+#[test]
+fn custom_elements() {
+    let options = ParseOptions::default().inline_extension(Mention);
+    let parts: Vec<_> = RawText("@ben").parts_with_options(&options).collect();
+    let json = serde_json::to_value(&parts).unwrap();
+    assert_eq!(
+        json,
+        json!([{ "Custom": { "name": "mention", "source": "@ben", "content": "ben" } }])
+    );
+}
+
+#[test]
+fn document() {
+    let source = "# Title\n\n- a\n  - b\n\n> \"quoted\"\n\n| x |\n|---|\n| `y` |";
+    let document = Document::parse(source).unwrap();
+    let json = serde_json::to_string(&document).unwrap();
+    assert_eq!(serde_json::from_str::<Document>(&json).unwrap(), document);
+
+    // Strings with escapes in the JSON
+    let source = "Say \"hi\" \\ bye\n\n```\nline \"one\"\n\tline two\n```";
+    let document = Document::parse(source).unwrap();
+    let json = serde_json::to_string(&document).unwrap();
+    assert!(json.contains(r#"line \"one\"\n\tline two\n"#), "{json}");
+    assert_eq!(serde_json::from_str::<Document>(&json).unwrap(), document);
+    let reader = std::io::Cursor::new(json.into_bytes());
+    assert_eq!(
+        serde_json::from_reader::<_, Document>(reader).unwrap(),
+        document
+    );
+}
+
+/// Nodes without their positions
+fn without_positions(mut node: Value) -> Value {
+    if let Some(node) = node.as_object_mut() {
+        node.remove("position");
+        if let Some(Value::Array(children)) = node.get_mut("children") {
+            for child in children {
+                *child = without_positions(child.take());
+            }
+        }
+    }
+    node
+}
+
+#[test]
+fn mdast() {
+    let source = "# Title\n\n> Some **bold**\n> [link](to)\n\n1. a\n   - b\n2. c\n\n```rust title\ncode\n```";
+    let root = to_mdast(source, &ParseOptions::default()).unwrap();
+    assert_eq!(
+        without_positions(root),
+        json!({
+            "type": "root",
+            "children": [
+                {
+                    "type": "heading",
+                    "depth": 1,
+                    "children": [{ "type": "text", "value": "Title" }],
+                },
+                {
+                    "type": "blockquote",
+                    "children": [
+                        {
+                            "type": "paragraph",
+                            "children": [
+                                { "type": "text", "value": "Some " },
+                                { "type": "strong", "children": [{ "type": "text", "value": "bold" }] },
+                            ],
+                        },
+                        {
+                            "type": "paragraph",
+                            "children": [{
+                                "type": "link",
+                                "url": "to",
+                                "title": null,
+                                "children": [{ "type": "text", "value": "link" }],
+                            }],
+                        },
+                    ],
+                },
+                {
+                    "type": "list",
+                    "ordered": true,
+                    "start": 1,
+                    "spread": false,
+                    "children": [
+                        {
+                            "type": "listItem",
+                            "spread": false,
+                            "checked": null,
+                            "children": [
+                                { "type": "paragraph", "children": [{ "type": "text", "value": "a" }] },
+                                {
+                                    "type": "list",
+                                    "ordered": false,
+                                    "start": null,
+                                    "spread": false,
+                                    "children": [{
+                                        "type": "listItem",
+                                        "spread": false,
+                                        "checked": null,
+                                        "children": [
+                                            { "type": "paragraph", "children": [{ "type": "text", "value": "b" }] },
+                                        ],
+                                    }],
+                                },
+                            ],
+                        },
+                        {
+                            "type": "listItem",
+                            "spread": false,
+                            "checked": null,
+                            "children": [
+                                { "type": "paragraph", "children": [{ "type": "text", "value": "c" }] },
+                            ],
+                        },
+                    ],
+                },
+                { "type": "code", "lang": "rust", "meta": "title", "value": "code" },
+            ],
+        })
+    );
+}
+
+/// Source of a node, from its offsets
+fn source_of<'a>(source: &'a str, node: &Value) -> &'a str {
+    let utf16_to_byte = |offset: &Value| {
+        let offset = usize::try_from(offset.as_u64().unwrap()).unwrap();
+        let mut units = 0;
+        source
+            .char_indices()
+            .find_map(|(idx, chr)| {
+                let found = (units >= offset).then_some(idx);
+                units += chr.len_utf16();
+                found
+            })
+            .unwrap_or(source.len())
+    };
+    let position = &node["position"];
+    &source[utf16_to_byte(&position["start"]["offset"])..utf16_to_byte(&position["end"]["offset"])]
+}
+
+#[test]
+fn mdast_positions() {
+    let source = "😀 *a*\n\n- b\n\n    c `d`\n- e";
+    let root = to_mdast(source, &ParseOptions::default()).unwrap();
+
+    let paragraph = &root["children"][0];
+    assert_eq!(source_of(source, paragraph), "😀 *a*");
+    assert_eq!(source_of(source, &paragraph["children"][1]), "*a*");
+    // In UTF-16 code units
+    assert_eq!(
+        paragraph["children"][1]["position"]["start"],
+        json!({ "line": 1, "column": 4, "offset": 3 })
+    );
+
+    // Through dedented list item contents
+    let item = &root["children"][1]["children"][0];
+    let code = &item["children"][1]["children"][1];
+    assert_eq!(code["type"], "inlineCode");
+    assert_eq!(source_of(source, code), "`d`");
+    assert_eq!(
+        code["position"]["start"],
+        json!({ "line": 5, "column": 7, "offset": 19 })
+    );
+}
+
+#[test]
+fn mdast_long_lines() {
+    // Points are found without counting from the start of the line
+    let source = "😀 *a* ".repeat(10_000);
+    let root = to_mdast(&source, &ParseOptions::default()).unwrap();
+    let children = root["children"][0]["children"].as_array().unwrap();
+    let last = children
+        .iter()
+        .rev()
+        .find(|node| node["type"] == "emphasis");
+    assert_eq!(
+        last.unwrap()["position"]["start"],
+        json!({ "line": 1, "column": 69_997, "offset": 69_996 })
+    );
+}
+
+mod arbitrary {
+    use super::source_of;
+    use proptest::prelude::*;
+    use serde_json::Value;
+    use simple_markdown_parser::{document::Document, extras::mdast::to_mdast, ParseOptions};
+
+    /// Checks that offsets are in order and in the source
+    fn check_positions(source: &str, node: &Value, parent: (u64, u64)) {
+        let position = &node["position"];
+        if position.is_null() {
+            return;
+        }
+        let start = position["start"]["offset"].as_u64().unwrap();
+        let end = position["end"]["offset"].as_u64().unwrap();
+        assert!(
+            parent.0 <= start && start <= end && end <= parent.1,
+            "{node}"
+        );
+        let _ = source_of(source, node);
+        if let Some(children) = node["children"].as_array() {
+            for child in children {
+                check_positions(source, child, (start, end));
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn document_roundtrip(source in "[-#>|`*_{}%\\[\\]()a-z1. \n\"\\\\]{0,120}") {
+            if let Ok(document) = Document::parse(&source) {
+                let json = serde_json::to_string(&document).unwrap();
+                prop_assert_eq!(serde_json::from_str::<Document>(&json).unwrap(), document);
+            }
+        }
+
+        #[test]
+        fn mdast_positions(source in "[-#>|`*_{}%\\[\\]()a-zé😀1. \n]{0,120}") {
+            if let Ok(root) = to_mdast(&source, &ParseOptions::default()) {
+                let length = source.encode_utf16().count() as u64;
+                check_positions(&source, &root, (0, length));
+            }
+        }
+    }
+}
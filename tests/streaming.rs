@@ -0,0 +1,265 @@
+use simple_markdown_parser::{
+    parse_with_options,
+    streaming::{parse_reader, StreamError, StreamingParser},
+    MarkdownElement, ParseError, ParseOptions,
+};
+
+/// Elements as `Debug` strings, as streamed elements only live for the callback
+fn full_parse(source: &str) -> Result<Vec<String>, ParseError> {
+    full_parse_with_options(source, &ParseOptions::default())
+}
+
+fn full_parse_with_options(
+    source: &str,
+    options: &ParseOptions,
+) -> Result<Vec<String>, ParseError> {
+    let mut elements = Vec::new();
+    parse_with_options(source, options, |element| {
+        elements.push(format!("{element:?}"));
+    })?;
+    Ok(elements)
+}
+
+fn streamed(chunks: &[&str]) -> Result<Vec<String>, ParseError> {
+    streamed_with_options(chunks, &ParseOptions::default())
+}
+
+fn streamed_with_options(
+    chunks: &[&str],
+    options: &ParseOptions,
+) -> Result<Vec<String>, ParseError> {
+    let mut elements = Vec::new();
+    let mut parser = StreamingParser::new(options);
+    for chunk in chunks {
+        parser.push(chunk, |element| elements.push(format!("{element:?}")))?;
+    }
+    parser.finish(|element| elements.push(format!("{element:?}")))?;
+    Ok(elements)
+}
+
+const SOURCE: &str = "\u{feff}---
+title: a
+---
+# Heading\r
+Text
+
+- a
+  continued
+
+- b
+  - c
+After
+| a | b |
+|---|---|
+| c | d |
+```rs
+let a = 2;
+```
+{% note %}
+Inside
+{% /note %}
+---
+$$
+x
+$$
+End";
+
+#[test]
+fn chunks() {
+    let expected = full_parse(SOURCE).unwrap();
+    for size in [1, 2, 3, 5, 8, 13, 64] {
+        let chunks: Vec<_> = SOURCE
+            .char_indices()
+            .step_by(size)
+            .map(|(start, _)| start)
+            .chain([SOURCE.len()])
+            .collect::<Vec<_>>()
+            .windows(2)
+            .map(|window| &SOURCE[window[0]..window[1]])
+            .collect();
+        pretty_assertions::assert_eq!(streamed(&chunks).unwrap(), expected, "chunks of {size}");
+    }
+}
+
+#[test]
+fn elements_as_input_arrives() {
+    let mut parser = StreamingParser::new(&ParseOptions::default());
+    let mut elements = Vec::new();
+    for line in ["# One\n", "Two\n", "Three\n", "- a\n", "- b\n"] {
+        parser
+            .push(line, |element| elements.push(format!("{element:?}")))
+            .unwrap();
+    }
+    // Later lines could be part of the list. Parsing resumes from the paragraph before it
+    assert_eq!(elements.len(), 2);
+    assert_eq!(parser.buffered(), "Three\n- a\n- b\n".len());
+
+    parser
+        .push("Four\n", |element| elements.push(format!("{element:?}")))
+        .unwrap();
+    assert_eq!(elements.len(), 5);
+    assert_eq!(parser.buffered(), "Four\n".len());
+
+    parser
+        .finish(|element| elements.push(format!("{element:?}")))
+        .unwrap();
+    assert_eq!(elements.len(), 6);
+}
+
+#[test]
+fn bounded_memory() {
+    let mut parser = StreamingParser::new(&ParseOptions::default());
+    let mut count = 0;
+    for index in 0..10_000 {
+        parser
+            .push(&format!("Paragraph {index}\n\n"), |_| count += 1)
+            .unwrap();
+        assert!(parser.buffered() < 32);
+    }
+    parser.finish(|_| count += 1).unwrap();
+    assert_eq!(count, 10_000);
+}
+
+#[test]
+fn long_lists_and_tables() {
+    // Given in parts, so memory stays bounded
+    let tight = "- item\n  - nested\n".repeat(5_000);
+    let loose = "- item\n\n".repeat(5_000);
+    for source in [tight, loose] {
+        let mut parser = StreamingParser::new(&ParseOptions::default()).split_after(1024);
+        let mut elements = Vec::new();
+        for line in source.split_inclusive('\n') {
+            parser
+                .push(line, |element| elements.push(format!("{element:?}")))
+                .unwrap();
+            assert!(parser.buffered() < 1100);
+        }
+        parser
+            .finish(|element| elements.push(format!("{element:?}")))
+            .unwrap();
+        assert_eq!(elements, full_parse(&source).unwrap());
+    }
+
+    let table = format!("| a | b |\n|---|---|\n{}", "| c | d |\n".repeat(5_000));
+    let mut parser = StreamingParser::new(&ParseOptions::default()).split_after(1024);
+    let mut parts = Vec::new();
+    let mut on_element = |element: MarkdownElement<'_>| {
+        if let MarkdownElement::Table(table) = element {
+            assert_eq!(table.rows().next().unwrap().cells().count(), 2);
+            parts.push((table.continues(), table.rows().count() - 1));
+        }
+    };
+    for line in table.split_inclusive('\n') {
+        parser.push(line, &mut on_element).unwrap();
+        assert!(parser.buffered() < 1100);
+    }
+    parser.finish(&mut on_element).unwrap();
+    assert!(parts.len() > 1);
+    assert!(parts.iter().skip(1).all(|(continues, _)| *continues) && !parts[0].0);
+    assert_eq!(parts.iter().map(|(_, rows)| rows).sum::<usize>(), 5_000);
+
+    // Positions are in the whole source
+    let source = format!("{table}{}", "x".repeat(100));
+    let options = ParseOptions::default().max_line_length(Some(50));
+    let mut parser = StreamingParser::new(&options).split_after(256);
+    let error = source
+        .split_inclusive('\n')
+        .try_for_each(|line| parser.push(line, |_| {}))
+        .and_then(|()| parser.finish(|_| {}))
+        .unwrap_err();
+    assert_eq!(error.position, table.len()..source.len());
+}
+
+#[test]
+fn long_blocks() {
+    // Only lines that could close the block are parsed again
+    let source = format!("Text\n```rs\n{}```\nAfter", "let a = 2;\n".repeat(50_000));
+    let chunks: Vec<_> = source
+        .as_bytes()
+        .chunks(64)
+        .map(|chunk| std::str::from_utf8(chunk).unwrap())
+        .collect();
+    assert_eq!(streamed(&chunks).unwrap(), full_parse(&source).unwrap());
+}
+
+#[test]
+fn errors() {
+    let source = "Text\n{% a %}\n{% /b %}\nMore";
+    assert_eq!(streamed(&[source]), full_parse(source));
+    assert_eq!(
+        streamed(&["Text\n", "{% a %}\n", "{% /b %}\n", "More"]),
+        full_parse(source)
+    );
+
+    let unclosed = "Text\n\n{% a %}\nInside";
+    assert_eq!(
+        streamed(&["Text\n\n", "{% a %}\n", "Inside"]),
+        full_parse(unclosed)
+    );
+}
+
+#[test]
+fn reader() {
+    let source = "# Ünïcödé\nText ✓\n";
+    let mut elements = Vec::new();
+    let reader = std::io::BufReader::with_capacity(1, source.as_bytes());
+    parse_reader(reader, &ParseOptions::default(), |element| {
+        elements.push(format!("{element:?}"));
+    })
+    .unwrap();
+    assert_eq!(elements, full_parse(source).unwrap());
+
+    let invalid: &[u8] = b"Text\n\xff";
+    let result = parse_reader(invalid, &ParseOptions::default(), |_| {});
+    assert!(matches!(result, Err(StreamError::Io(_))));
+}
+
+mod arbitrary {
+    use super::{full_parse_with_options, streamed_with_options};
+    use proptest::prelude::*;
+    use simple_markdown_parser::ParseOptions;
+
+    fn line() -> impl Strategy<Value = &'static str> {
+        prop::sample::select(vec![
+            "text",
+            "# heading",
+            "- item",
+            "  - nested",
+            "  indented",
+            "1. ordered",
+            "> quote",
+            "",
+            "---",
+            "```",
+            "$$",
+            "%%",
+            "| a | b |",
+            "|---|---|",
+            "{% note %}",
+            "{% /note %}",
+        ])
+    }
+
+    proptest! {
+        #[test]
+        fn same_as_full_parse(
+            lines in prop::collection::vec(line(), 0..20),
+            line_ending in prop::sample::select(vec!["\n", "\r\n", "\r"]),
+            splits in prop::collection::vec(any::<prop::sample::Index>(), 0..8),
+            multiline_paragraphs in any::<bool>(),
+        ) {
+            let options = ParseOptions::default().multiline_paragraphs(multiline_paragraphs);
+            let source = lines.join(line_ending);
+            let mut splits: Vec<_> = splits.iter().map(|index| index.index(source.len() + 1)).collect();
+            splits.extend([0, source.len()]);
+            splits.sort_unstable();
+            let chunks: Vec<_> = splits.windows(2).map(|window| &source[window[0]..window[1]]).collect();
+            prop_assert_eq!(
+                streamed_with_options(&chunks, &options),
+                full_parse_with_options(&source, &options),
+                "{:?}",
+                chunks
+            );
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use simple_markdown_parser::document::{Block, Document, Heading, Inline};
+use simple_markdown_parser::visitor::{walk_blocks_mut, Visitor, VisitorMut};
+
+#[test]
+fn collect_code_blocks() {
+    struct Languages(Vec<String>);
+
+    impl Visitor for Languages {
+        fn visit_code_block(&mut self, language: &str, _code: &str) {
+            self.0.push(language.to_owned());
+        }
+    }
+
+    let source = "```rs
+a
+```
+# Heading
+- item
+  ```ts
+  b
+  ```
+> ```py";
+
+    let document = Document::parse(source).unwrap();
+    let mut languages = Languages(Vec::new());
+    languages.visit_document(&document);
+    pretty_assertions::assert_eq!(languages.0, vec!["rs", "ts"]);
+}
+
+#[test]
+fn transforms() {
+    struct DemoteHeadings;
+
+    impl VisitorMut for DemoteHeadings {
+        fn visit_heading(&mut self, heading: &mut Heading) {
+            heading.level += 1;
+        }
+    }
+
+    struct MarkdownLinksToHtml;
+
+    impl VisitorMut for MarkdownLinksToHtml {
+        fn visit_link(&mut self, _content: &mut Vec<Inline>, to: &mut String) {
+            if let Some(stem) = to.strip_suffix(".md") {
+                *to = format!("{stem}.html");
+            }
+        }
+    }
+
+    /// Removes sections titled "Drafts"
+    struct RemoveDrafts;
+
+    impl VisitorMut for RemoveDrafts {
+        fn visit_blocks(&mut self, blocks: &mut Vec<Block>) {
+            blocks.retain(|block| {
+                !matches!(block, Block::Section(section) if section.heading.text() == "Drafts")
+            });
+            walk_blocks_mut(self, blocks);
+        }
+    }
+
+    let source = "# Title
+See [other](./other.md) and [site](https://example.com)
+## Drafts
+- [draft](draft.md)
+## Final
+- [final](final.md)";
+
+    let mut document = Document::parse(source).unwrap();
+    DemoteHeadings.visit_document(&mut document);
+    MarkdownLinksToHtml.visit_document(&mut document);
+    RemoveDrafts.visit_document(&mut document);
+
+    let expected = "## Title
+See [other](./other.html) and [site](https://example.com)
+### Final
+- [final](final.html)";
+
+    let expected = Document::parse(expected).unwrap();
+    pretty_assertions::assert_eq!(document, expected);
+}